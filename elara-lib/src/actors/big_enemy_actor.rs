@@ -1,6 +1,7 @@
 use crate::simulation::{
     Actor, BigEnemyAnimState, BumpAnimData, OrientationWithDiagonals, Pos, State,
 };
+use crate::spatial_index::SpatialIndex;
 
 use super::{can_move_to, Bounds, MoveDirection, TurnDirection};
 
@@ -26,10 +27,15 @@ enum BigEvilRoverAction {
 
 /// Returns true if we can move to the desired position. Accounts for the entire
 /// body of the enemy, not just one space.
-fn can_move_entire_body(state: &State, bounds: &Bounds, desired_pos: &Pos) -> bool {
+fn can_move_entire_body(
+    state: &State,
+    spatial_index: &SpatialIndex,
+    bounds: &Bounds,
+    desired_pos: &Pos,
+) -> bool {
     for x in desired_pos.x..desired_pos.x + BIG_ENEMY_SIZE {
         for y in desired_pos.y..desired_pos.y + BIG_ENEMY_SIZE {
-            if !can_move_to(state, bounds, &Pos::new(x, y)) {
+            if !can_move_to(state, spatial_index, bounds, &Pos::new(x, y)) {
                 return false;
             }
         }
@@ -103,14 +109,9 @@ impl BigEnemyActor {
         if curr_orientation == desired_direction {
             return BigEvilRoverAction::Move(MoveDirection::Forward);
         }
-        println!("desired_direction: {:?}", desired_direction);
         let clockwise_dist = curr_orientation.clockwise_distance(&desired_direction);
         let counter_clockwise_dist =
             curr_orientation.counter_clockwise_distance(&desired_direction);
-        println!(
-            "clockwise_dist: {}, counter_clockwise_dist: {}",
-            clockwise_dist, counter_clockwise_dist
-        );
         if clockwise_dist < counter_clockwise_dist {
             BigEvilRoverAction::Turn(TurnDirection::Right)
         } else {
@@ -145,7 +146,7 @@ impl BigEnemyActor {
         }
     }
 
-    fn get_next_action(&self, state: &State) -> BigEvilRoverAction {
+    fn get_next_action(&self, state: &State, spatial_index: &SpatialIndex) -> BigEvilRoverAction {
         let player_pos = &state.player.pos;
         let enemy = &state.big_enemies[self.index];
         // Note: BigEnemy.pos represents the top-left position (which is easier
@@ -163,6 +164,7 @@ impl BigEnemyActor {
             if player_pos.y < center_pos.y
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y - 1),
                 )
@@ -171,6 +173,7 @@ impl BigEnemyActor {
             } else if player_pos.y > center_pos.y
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y + 1),
                 )
@@ -181,6 +184,7 @@ impl BigEnemyActor {
             if player_pos.x < center_pos.x
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x - 1, enemy.pos.y),
                 )
@@ -189,6 +193,7 @@ impl BigEnemyActor {
             } else if player_pos.x > center_pos.x
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x + 1, enemy.pos.y),
                 )
@@ -211,6 +216,7 @@ impl BigEnemyActor {
             if player_pos.x < center_pos.x
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x - 1, enemy.pos.y),
                 )
@@ -219,6 +225,7 @@ impl BigEnemyActor {
             } else if player_pos.x > center_pos.x
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x + 1, enemy.pos.y),
                 )
@@ -229,6 +236,7 @@ impl BigEnemyActor {
             if player_pos.y < center_pos.y
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y - 1),
                 )
@@ -237,6 +245,7 @@ impl BigEnemyActor {
             } else if player_pos.y > center_pos.y
                 && can_move_entire_body(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y + 1),
                 )
@@ -256,14 +265,14 @@ impl BigEnemyActor {
 }
 
 impl Actor for BigEnemyActor {
-    fn apply(&mut self, state: State) -> State {
+    fn apply(&mut self, state: State, spatial_index: &SpatialIndex) -> State {
         let mut state = state.clone();
 
         // Default to Idle state.
         state.big_enemies[self.index].anim_state = BigEnemyAnimState::Idle;
 
         // Update own state based on desired action.
-        let action = self.get_next_action(&state);
+        let action = self.get_next_action(&state, spatial_index);
 
         match action {
             BigEvilRoverAction::Move(direction) => {
@@ -531,7 +540,7 @@ mod test {
         for (i, tc) in test_cases.iter().enumerate() {
             base_state.big_enemies[0].pos = tc.enemy_pos.clone();
             base_state.big_enemies[0].facing = tc.enemy_facing;
-            let action = actor.get_next_action(&base_state);
+            let action = actor.get_next_action(&base_state, &SpatialIndex::new(&base_state));
             assert_eq!(
                 action, tc.expected_action,
                 "\n{}\n(Test case {})",
@@ -1,6 +1,7 @@
 use crate::simulation::{
     Actor, BumpAnimData, EnemyAnimState, Orientation, Pos, State, TeleAnimData,
 };
+use crate::spatial_index::SpatialIndex;
 
 use super::{can_move_to, get_telepad_at, Bounds, MoveDirection, TurnDirection};
 
@@ -23,8 +24,13 @@ enum EvilRoverAction {
 
 /// Returns true if we can move to the desired position *and* it is not currently occupied
 /// by another enemy.
-fn can_move_and_is_empty(state: &State, bounds: &Bounds, desired_pos: &Pos) -> bool {
-    return can_move_to(state, bounds, desired_pos)
+fn can_move_and_is_empty(
+    state: &State,
+    spatial_index: &SpatialIndex,
+    bounds: &Bounds,
+    desired_pos: &Pos,
+) -> bool {
+    return can_move_to(state, spatial_index, bounds, desired_pos)
         && !state.enemies.iter().any(|enemy| enemy.pos == *desired_pos);
 }
 
@@ -136,7 +142,7 @@ impl EvilRoverActor {
         }
     }
 
-    fn get_next_action(&self, state: &State) -> EvilRoverAction {
+    fn get_next_action(&self, state: &State, spatial_index: &SpatialIndex) -> EvilRoverAction {
         let player_pos = &state.player.pos;
         let enemy = &state.enemies[self.index];
 
@@ -147,6 +153,7 @@ impl EvilRoverActor {
             if player_pos.y < enemy.pos.y
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y - 1),
                 )
@@ -155,6 +162,7 @@ impl EvilRoverActor {
             } else if player_pos.y > enemy.pos.y
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y + 1),
                 )
@@ -165,6 +173,7 @@ impl EvilRoverActor {
             if player_pos.x < enemy.pos.x
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x - 1, enemy.pos.y),
                 )
@@ -173,6 +182,7 @@ impl EvilRoverActor {
             } else if player_pos.x > enemy.pos.x
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x + 1, enemy.pos.y),
                 )
@@ -195,6 +205,7 @@ impl EvilRoverActor {
             if player_pos.x < enemy.pos.x
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x - 1, enemy.pos.y),
                 )
@@ -203,6 +214,7 @@ impl EvilRoverActor {
             } else if player_pos.x > enemy.pos.x
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x + 1, enemy.pos.y),
                 )
@@ -213,6 +225,7 @@ impl EvilRoverActor {
             if player_pos.y < enemy.pos.y
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y - 1),
                 )
@@ -221,6 +234,7 @@ impl EvilRoverActor {
             } else if player_pos.y > enemy.pos.y
                 && can_move_and_is_empty(
                     state,
+                    spatial_index,
                     &self.bounds,
                     &Pos::new(enemy.pos.x, enemy.pos.y + 1),
                 )
@@ -240,14 +254,14 @@ impl EvilRoverActor {
 }
 
 impl Actor for EvilRoverActor {
-    fn apply(&mut self, state: State) -> State {
+    fn apply(&mut self, state: State, spatial_index: &SpatialIndex) -> State {
         let mut state = state.clone();
 
         // Default to Idle state.
         state.enemies[self.index].anim_state = EnemyAnimState::Idle;
 
         // Update own state based on desired action.
-        let action = self.get_next_action(&state);
+        let action = self.get_next_action(&state, spatial_index);
 
         match action {
             EvilRoverAction::Move(direction) => {
@@ -256,7 +270,7 @@ impl Actor for EvilRoverActor {
                     MoveDirection::Backward => self.backward_pos(&state),
                 };
                 // If there is a telepad at the desired position, teleport.
-                if let Some(telepad) = get_telepad_at(&state, &desired_pos) {
+                if let Some(telepad) = get_telepad_at(&state, spatial_index, &desired_pos) {
                     let anim_data = TeleAnimData {
                         start_pos: state.enemies[self.index].pos.clone(),
                         enter_pos: telepad.start_pos,
@@ -403,7 +417,7 @@ mod test {
         for tc in test_cases {
             base_state.enemies[0].pos = tc.enemy_pos;
             base_state.enemies[0].facing = tc.enemy_facing;
-            let action = actor.get_next_action(&base_state);
+            let action = actor.get_next_action(&base_state, &SpatialIndex::new(&base_state));
             assert_eq!(action, tc.expected_action);
         }
     }
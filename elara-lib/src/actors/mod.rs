@@ -3,8 +3,10 @@ mod evil_rover_actor;
 mod player_actor;
 
 use crate::{
+    builtins::Builtin,
     constants::{HEIGHT, WIDTH},
-    simulation::{Pos, State, Telepad},
+    simulation::{Pos, State, Telepad, TermData},
+    spatial_index::SpatialIndex,
 };
 
 pub use big_enemy_actor::BigEnemyActor;
@@ -12,7 +14,7 @@ pub use big_enemy_actor::BIG_ENEMY_SIZE;
 pub use evil_rover_actor::EvilRoverActor;
 pub use player_actor::PlayerChannelActor;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum MoveDirection {
     Forward,
     Backward,
@@ -31,6 +33,33 @@ pub enum Action {
     Say(String),
     ReadData,
     PressButton,
+    WriteData(TermData),
+}
+
+/// An Action along with the built-in function which performed it. The
+/// PlayerChannelActor uses the built-in function to look up the cost of the
+/// action (see Builtin::cost).
+pub struct PlayerAction {
+    pub action: Action,
+    pub builtin: &'static Builtin,
+}
+
+/// The result of an Action. This is sent back from the PlayerChannelActor so
+/// that built-in functions can tell the script what actually happened.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ActionResult {
+    /// The rover moved to a new position (possibly by teleporting).
+    Moved,
+    /// The rover tried to move but something was in the way.
+    Blocked,
+    /// The rover tried to move but was out of energy.
+    OutOfEnergy,
+    /// Data was written to an input terminal and the level accepted it.
+    Accepted,
+    /// Data was written to an input terminal but the level rejected it.
+    Rejected,
+    /// Any other action was performed.
+    Done,
 }
 
 pub struct Bounds {
@@ -50,96 +79,43 @@ impl Bounds {
         }
     }
 
-    pub fn default() -> Bounds {
+    /// Returns bounds which cover an entire grid with the given width and
+    /// height.
+    pub fn for_grid(width: u32, height: u32) -> Bounds {
         Bounds {
             min_x: 0,
-            max_x: (WIDTH - 1) as i32,
+            max_x: width as i32 - 1,
             min_y: 0,
-            max_y: (HEIGHT - 1) as i32,
-        }
-    }
-}
-
-fn is_obstacle_at(state: &State, pos: &Pos) -> bool {
-    for obstacle in &state.obstacles {
-        if obstacle.pos == *pos {
-            return true;
-        }
-    }
-    // Data points are treated as simple obstacles since
-    // they can never move or be opened.
-    for data_point in &state.data_points {
-        if data_point.pos == *pos {
-            return true;
-        }
-    }
-    // Buttons can also not be moved.
-    for button in &state.buttons {
-        if button.pos == *pos {
-            return true;
-        }
-    }
-    false
-}
-
-fn is_closed_gate_at(state: &State, pos: &Pos) -> bool {
-    for gate in &state.gates {
-        if gate.pos == *pos && !gate.open {
-            return true;
+            max_y: height as i32 - 1,
         }
     }
-    false
-}
 
-fn is_closed_password_gate_at(state: &State, pos: &Pos) -> bool {
-    for gate in &state.password_gates {
-        if gate.pos == *pos && !gate.open {
-            return true;
-        }
+    pub fn default() -> Bounds {
+        Bounds::for_grid(WIDTH, HEIGHT)
     }
-    false
 }
 
-fn get_telepad_at(state: &State, pos: &Pos) -> Option<Telepad> {
-    for telepad in &state.telepads {
-        if telepad.start_pos == *pos {
-            return Some(telepad.clone());
-        }
-    }
-    None
+fn get_telepad_at(state: &State, spatial_index: &SpatialIndex, pos: &Pos) -> Option<Telepad> {
+    spatial_index
+        .telepad_at(pos)
+        .map(|telepad_index| state.telepads[telepad_index].clone())
 }
 
-fn is_outside_bounds(bounds: &Bounds, pos: &Pos) -> bool {
+pub(crate) fn is_outside_bounds(bounds: &Bounds, pos: &Pos) -> bool {
     pos.x > bounds.max_x || pos.y > bounds.max_y || pos.x < bounds.min_x || pos.y < bounds.min_y
 }
 
 /// Returns true if the given position is a valid position for an actor to move to.
 /// This applies to player and enemy actors, basically anything that moves around
 /// the board.
-fn can_move_to(state: &State, bounds: &Bounds, desired_pos: &Pos) -> bool {
-    !is_obstacle_at(state, desired_pos)
+fn can_move_to(
+    state: &State,
+    spatial_index: &SpatialIndex,
+    bounds: &Bounds,
+    desired_pos: &Pos,
+) -> bool {
+    !spatial_index.is_obstacle_at(desired_pos)
         && !is_outside_bounds(bounds, desired_pos)
-        && !is_closed_gate_at(state, desired_pos)
-        && !is_closed_password_gate_at(state, desired_pos)
-}
-
-/// Returns the index of any password gates adjacent to the given position.
-/// Returns an empty vector if there is no adjacent gate.
-fn get_adjacent_password_gates(state: &State, pos: &Pos) -> Vec<usize> {
-    let mut gate_indexes = vec![];
-    for (i, gate) in state.password_gates.iter().enumerate() {
-        if gate.pos.x == pos.x && gate.pos.y == pos.y + 1 {
-            gate_indexes.push(i);
-        }
-        if pos.y != 0 && gate.pos.x == pos.x && gate.pos.y == pos.y - 1 {
-            gate_indexes.push(i);
-        }
-        if gate.pos.x == pos.x + 1 && gate.pos.y == pos.y {
-            gate_indexes.push(i);
-        }
-        if pos.x != 0 && gate.pos.x == pos.x - 1 && gate.pos.y == pos.y {
-            gate_indexes.push(i);
-        }
-    }
-    gate_indexes
+        && !spatial_index.is_closed_gate_at(state, desired_pos)
+        && !spatial_index.is_closed_password_gate_at(state, desired_pos)
 }
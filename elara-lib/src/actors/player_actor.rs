@@ -3,42 +3,82 @@ use std::rc::Rc;
 use std::sync::mpsc;
 
 use crate::constants::ENERGY_CELL_AMOUNT;
+use crate::levels::Level;
 use crate::simulation::{
-    get_adjacent_button, get_adjacent_point, Actor, BumpAnimData, ButtonConnection, Orientation,
-    PlayerAnimState, Pos, State, TeleAnimData,
+    Actor, BumpAnimData, ButtonConnection, Orientation, PlayerAnimState, Pos, State, TeleAnimData,
+    TermData,
 };
+use crate::spatial_index::SpatialIndex;
 
 use super::{
-    can_move_to, get_adjacent_password_gates, get_telepad_at, Action, Bounds, MoveDirection,
+    can_move_to, get_telepad_at, Action, ActionResult, Bounds, MoveDirection, PlayerAction,
     TurnDirection,
 };
 
 pub struct PlayerChannelActor {
-    rx: Rc<RefCell<mpsc::Receiver<Action>>>,
+    rx: Rc<RefCell<mpsc::Receiver<PlayerAction>>>,
+    /// Used to send the result of each action back to the script (if set).
+    result_tx: Option<mpsc::Sender<ActionResult>>,
     bounds: Bounds,
+    /// Used to check data written to input terminals (if set). Otherwise all
+    /// data is rejected.
+    level: Option<&'static dyn Level>,
 }
 
 impl PlayerChannelActor {
-    pub fn new(rx: Rc<RefCell<mpsc::Receiver<Action>>>, bounds: Bounds) -> PlayerChannelActor {
-        PlayerChannelActor { rx, bounds }
+    pub fn new(
+        rx: Rc<RefCell<mpsc::Receiver<PlayerAction>>>,
+        bounds: Bounds,
+    ) -> PlayerChannelActor {
+        PlayerChannelActor {
+            rx,
+            result_tx: None,
+            bounds,
+            level: None,
+        }
     }
 
     pub fn set_bounds(&mut self, bounds: Bounds) {
         self.bounds = bounds;
     }
+
+    /// Sets the level which is used to check data written to input terminals.
+    pub fn set_level(&mut self, level: &'static dyn Level) {
+        self.level = Some(level);
+    }
+
+    /// Sets the channel that the result of each action will be sent to.
+    pub fn set_result_tx(&mut self, result_tx: mpsc::Sender<ActionResult>) {
+        self.result_tx = Some(result_tx);
+    }
+
+    fn send_result(&self, result: ActionResult) {
+        if let Some(result_tx) = &self.result_tx {
+            // It's fine if no one is listening for the result.
+            let _ = result_tx.send(result);
+        }
+    }
 }
 
 impl Actor for PlayerChannelActor {
-    fn apply(&mut self, state: State) -> State {
+    fn apply(&mut self, state: State, spatial_index: &SpatialIndex) -> State {
         let mut state = state.clone();
 
         // Reset the player message every time an action is taken.
         // We only want messages to persist for one step.
         state.player.message = String::new();
 
-        // Reset the reading state of all data points.
-        for d_point in state.data_points.iter_mut() {
-            d_point.reading = false;
+        // Reset the reading state of all data points. We check first to avoid
+        // copying the data points when there is nothing to reset.
+        if state.data_points.iter().any(|d_point| d_point.reading) {
+            for d_point in Rc::make_mut(&mut state.data_points).iter_mut() {
+                d_point.reading = false;
+            }
+        }
+
+        // Reset the writing state of all input terminals.
+        for terminal in state.input_terminals.iter_mut() {
+            terminal.writing = false;
         }
 
         // Reset the pressed state of all buttons.
@@ -53,24 +93,41 @@ impl Actor for PlayerChannelActor {
 
         let rx = self.rx.clone();
         match rx.borrow().try_recv() {
-            Ok(Action::Wait) => {}
-            Ok(Action::Move(direction)) => {
+            Ok(PlayerAction {
+                action: Action::Wait,
+                ..
+            }) => self.send_result(ActionResult::Done),
+            Ok(PlayerAction {
+                action: Action::Move(direction),
+                builtin,
+            }) => {
                 // We can't move if we're out of energy.
-                if state.player.energy == 0 {
+                let energy_cost = builtin.cost.energy_per_space;
+                if state.player.energy < energy_cost {
+                    self.send_result(ActionResult::OutOfEnergy);
                     return state;
                 }
 
-                // Moving in any direction costs one energy.
-                state.player.energy -= 1;
-                state.player.total_energy_used += 1;
+                // The energy cost of moving comes from the built-in function.
+                state.player.energy -= energy_cost;
+                state.player.total_energy_used += energy_cost;
                 // Update the position and animation state. Note that the player may not
                 // be able to actually move if there are obstacles in the way.
-                let (new_pos, new_facing, new_anim_state) = self.try_to_move(&state, direction);
+                let (new_pos, new_facing, new_anim_state) =
+                    self.try_to_move(&state, spatial_index, direction);
+                if matches!(new_anim_state, PlayerAnimState::Bumping(_)) {
+                    self.send_result(ActionResult::Blocked);
+                } else {
+                    self.send_result(ActionResult::Moved);
+                }
                 state.player.pos = new_pos;
                 state.player.facing = new_facing;
                 state.player.anim_state = new_anim_state;
             }
-            Ok(Action::Turn(direction)) => {
+            Ok(PlayerAction {
+                action: Action::Turn(direction),
+                ..
+            }) => {
                 state.player.anim_state = PlayerAnimState::Turning;
                 if direction == TurnDirection::Right {
                     state.player.facing = match state.player.facing {
@@ -87,10 +144,15 @@ impl Actor for PlayerChannelActor {
                         Orientation::Left => Orientation::Down,
                     };
                 }
+                self.send_result(ActionResult::Done);
             }
-            Ok(Action::Say(message)) => {
+            Ok(PlayerAction {
+                action: Action::Say(message),
+                ..
+            }) => {
                 // If we're next to any password gates and we said the password, toggle the gate.
-                get_adjacent_password_gates(&state, &state.player.pos)
+                spatial_index
+                    .adjacent_password_gates(&state.player.pos)
                     .iter()
                     .for_each(|&gate_index| {
                         let gate = &state.password_gates[gate_index];
@@ -104,20 +166,49 @@ impl Actor for PlayerChannelActor {
 
                 state.player.anim_state = PlayerAnimState::Idle;
                 state.player.message = message;
+                self.send_result(ActionResult::Done);
             }
-            Ok(Action::ReadData) => {
+            Ok(PlayerAction {
+                action: Action::ReadData,
+                ..
+            }) => {
                 // If we're next to a data point, mark it as being currently read.
                 // (The reading state only affects the UI).
-                if let Some(d_point_index) = get_adjacent_point(&state, &state.player.pos) {
-                    state.data_points[d_point_index].reading = true;
+                if let Some(d_point_index) = spatial_index.adjacent_data_point(&state.player.pos) {
+                    Rc::make_mut(&mut state.data_points)[d_point_index].reading = true;
                 }
                 state.player.anim_state = PlayerAnimState::Idle;
+                self.send_result(ActionResult::Done);
             }
-            Ok(Action::PressButton) => {
-                if let Some(button_index) = get_adjacent_button(&state, &state.player.pos) {
+            Ok(PlayerAction {
+                action: Action::PressButton,
+                ..
+            }) => {
+                if let Some(button_index) = spatial_index.adjacent_button(&state.player.pos) {
                     self.handle_button_press(&mut state, button_index);
                 }
                 state.player.anim_state = PlayerAnimState::Idle;
+                self.send_result(ActionResult::Done);
+            }
+            Ok(PlayerAction {
+                action: Action::WriteData(data),
+                ..
+            }) => {
+                let mut result = ActionResult::Rejected;
+                if let Some(terminal_index) =
+                    spatial_index.adjacent_input_terminal(&state.player.pos)
+                {
+                    let accepted = match self.level {
+                        Some(level) => level.check_input(&state, terminal_index, &data),
+                        None => false,
+                    };
+                    self.handle_write_data(&mut state, terminal_index, data, accepted);
+                    if accepted {
+                        result = ActionResult::Accepted;
+                    }
+                }
+                state.player.anim_state = PlayerAnimState::Idle;
+                self.send_result(result);
             }
             Err(_) => {}
         }
@@ -142,6 +233,7 @@ impl PlayerChannelActor {
     fn try_to_move(
         &self,
         state: &State,
+        spatial_index: &SpatialIndex,
         direction: MoveDirection,
     ) -> (Pos, Orientation, PlayerAnimState) {
         let delta = match direction {
@@ -154,7 +246,7 @@ impl PlayerChannelActor {
             Orientation::Left => Pos::new(state.player.pos.x - delta, state.player.pos.y),
             Orientation::Right => Pos::new(state.player.pos.x + delta, state.player.pos.y),
         };
-        if let Some(telepad) = get_telepad_at(state, &desired_pos) {
+        if let Some(telepad) = get_telepad_at(state, spatial_index, &desired_pos) {
             return (
                 telepad.end_pos.clone(),
                 telepad.end_facing,
@@ -165,7 +257,7 @@ impl PlayerChannelActor {
                 }),
             );
         }
-        if can_move_to(state, &self.bounds, &desired_pos) {
+        if can_move_to(state, spatial_index, &self.bounds, &desired_pos) {
             (desired_pos, state.player.facing, PlayerAnimState::Moving)
         } else {
             (
@@ -193,6 +285,33 @@ impl PlayerChannelActor {
             }
         }
     }
+
+    // Update the state based on data being written to an input terminal.
+    fn handle_write_data(
+        &self,
+        state: &mut State,
+        terminal_index: usize,
+        data: TermData,
+        accepted: bool,
+    ) {
+        let terminal = &mut state.input_terminals[terminal_index];
+        terminal.last_input = Some(data);
+        terminal.accepted = accepted;
+        terminal.writing = true;
+        if !accepted {
+            return;
+        }
+        match terminal.connection {
+            ButtonConnection::None => {
+                // Don't do anything.
+            }
+            ButtonConnection::Gate(gate_index) => {
+                // Unlike buttons, terminals only ever open the gate. Writing
+                // the correct data again should not close it.
+                state.gates[gate_index].open = true;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,11 +320,28 @@ mod test {
     use crate::{
         constants::MAX_ENERGY,
         simulation::{
-            Button, DataPoint, Gate, GateVariant, Obstacle, PasswordGate, Player, PlayerAnimState,
-            Pos, State, Telepad,
+            Button, DataPoint, Gate, GateVariant, InputTerminal, Obstacle, PasswordGate, Player,
+            PlayerAnimState, Pos, State, Telepad,
         },
+        BUILTIN_FUNCTIONS,
     };
 
+    /// Sends the given action as if it was performed by the corresponding
+    /// built-in function.
+    fn send(tx: &mpsc::Sender<PlayerAction>, action: Action) {
+        let name = match action {
+            Action::Wait => "wait",
+            Action::Move(_) => "move_forward",
+            Action::Turn(_) => "turn_right",
+            Action::Say(_) => "say",
+            Action::ReadData => "read_data",
+            Action::PressButton => "press_button",
+            Action::WriteData(_) => "write_data",
+        };
+        let builtin = BUILTIN_FUNCTIONS.get(name).unwrap();
+        tx.send(PlayerAction { action, builtin }).unwrap();
+    }
+
     #[test]
     fn basic_movement() {
         let bounds = Bounds {
@@ -219,8 +355,8 @@ mod test {
         let mut state = State::new();
         state.player = Player::new(1, 1, MAX_ENERGY, Orientation::Right);
 
-        tx.send(Action::Move(MoveDirection::Forward)).unwrap();
-        let new_state = actor.apply(state.clone());
+        send(&tx, Action::Move(MoveDirection::Forward));
+        let new_state = actor.apply(state.clone(), &SpatialIndex::new(&state));
         assert_eq!(
             new_state.player,
             Player {
@@ -234,8 +370,8 @@ mod test {
         );
         state = new_state;
 
-        tx.send(Action::Turn(TurnDirection::Right)).unwrap();
-        let new_state = actor.apply(state.clone());
+        send(&tx, Action::Turn(TurnDirection::Right));
+        let new_state = actor.apply(state.clone(), &SpatialIndex::new(&state));
         assert_eq!(
             new_state.player,
             Player {
@@ -249,8 +385,8 @@ mod test {
         );
         state = new_state;
 
-        tx.send(Action::Move(MoveDirection::Forward)).unwrap();
-        let new_state = actor.apply(state.clone());
+        send(&tx, Action::Move(MoveDirection::Forward));
+        let new_state = actor.apply(state.clone(), &SpatialIndex::new(&state));
         assert_eq!(
             new_state.player,
             Player {
@@ -280,38 +416,54 @@ mod test {
         // outside the bounds.
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 0)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 2)
         );
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 2)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 0)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(0, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(2, 1)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(2, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(0, 1)
         );
     }
@@ -332,43 +484,59 @@ mod test {
         state.player.pos = Pos::new(0, 0);
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(0, 0)
         );
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(0, 0)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(0, 0)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(0, 0)
         );
         state.player.pos = Pos::new(2, 2);
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(2, 2)
         );
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(2, 2)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(2, 2)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(2, 2)
         );
     }
@@ -384,7 +552,7 @@ mod test {
         let actor = PlayerChannelActor::new(Rc::new(RefCell::new(mpsc::channel().1)), bounds);
         let mut state = State::new();
         state.player = Player::new(1, 1, MAX_ENERGY, Orientation::Right);
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 0),
             Obstacle::new(1, 0),
             Obstacle::new(2, 0),
@@ -393,43 +561,59 @@ mod test {
             Obstacle::new(1, 2),
             Obstacle::new(0, 2),
             Obstacle::new(0, 1),
-        ];
+        ]);
 
         // We can't move past obstacles.
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
     }
@@ -438,38 +622,54 @@ mod test {
     fn assert_player_can_move_in_any_direction(state: &mut State, actor: &PlayerChannelActor) {
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 0)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 2)
         );
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 2)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 0)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(0, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(2, 1)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(2, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(0, 1)
         );
     }
@@ -478,38 +678,54 @@ mod test {
     fn assert_player_cannot_move_in_any_direction(state: &mut State, actor: &PlayerChannelActor) {
         state.player.facing = Orientation::Up;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Down;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Left;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
         state.player.facing = Orientation::Right;
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Forward)
+                .0,
             Pos::new(1, 1)
         );
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Backward).0,
+            actor
+                .try_to_move(&state, &SpatialIndex::new(state), MoveDirection::Backward)
+                .0,
             Pos::new(1, 1)
         );
     }
@@ -653,7 +869,7 @@ mod test {
         let actor = PlayerChannelActor::new(Rc::new(RefCell::new(mpsc::channel().1)), bounds);
         let mut state = State::new();
         state.player = Player::new(1, 1, MAX_ENERGY, Orientation::Right);
-        state.data_points = vec![
+        state.data_points = Rc::new(vec![
             DataPoint::new(0, 0, "apples".into()),
             DataPoint::new(1, 0, "apples".into()),
             DataPoint::new(2, 0, "apples".into()),
@@ -662,7 +878,7 @@ mod test {
             DataPoint::new(1, 2, "apples".into()),
             DataPoint::new(0, 2, "apples".into()),
             DataPoint::new(0, 1, "apples".into()),
-        ];
+        ]);
         assert_player_cannot_move_in_any_direction(&mut state, &actor)
     }
 
@@ -681,7 +897,7 @@ mod test {
 
         // Should teleport to end_pos and be facing the new direction.
         assert_eq!(
-            actor.try_to_move(&state, MoveDirection::Forward),
+            actor.try_to_move(&state, &SpatialIndex::new(&state), MoveDirection::Forward),
             (
                 Pos::new(4, 4),
                 Orientation::Left,
@@ -715,8 +931,8 @@ mod test {
         )];
 
         // Say the wrong password.
-        tx.send(Action::Say("wrong password".to_string())).unwrap();
-        let new_state = actor.apply(state.clone());
+        send(&tx, Action::Say("wrong password".to_string()));
+        let new_state = actor.apply(state.clone(), &SpatialIndex::new(&state));
 
         // The PasswordGate should be updated to indicate the wrong password was said.
         assert_eq!(
@@ -732,8 +948,8 @@ mod test {
         );
 
         // Take any other action (e.g. turn)
-        tx.send(Action::Turn(TurnDirection::Right)).unwrap();
-        let new_state = actor.apply(new_state.clone());
+        send(&tx, Action::Turn(TurnDirection::Right));
+        let new_state = actor.apply(new_state.clone(), &SpatialIndex::new(&new_state));
 
         // The wrong_password field should now be set to false, but the gate
         // should still be closed.
@@ -750,8 +966,8 @@ mod test {
         );
 
         // Say the correct password.
-        tx.send(Action::Say("password".to_string())).unwrap();
-        let new_state = actor.apply(new_state.clone());
+        send(&tx, Action::Say("password".to_string()));
+        let new_state = actor.apply(new_state.clone(), &SpatialIndex::new(&new_state));
 
         // The PasswordGate should be updated to indicate the wrong password was said.
         assert_eq!(
@@ -766,4 +982,116 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn action_results() {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut actor = PlayerChannelActor::new(Rc::new(RefCell::new(rx)), Bounds::default());
+        actor.set_result_tx(result_tx);
+        let mut state = State::new();
+        state.player = Player::new(1, 1, 2, Orientation::Right);
+        state.obstacles = Rc::new(vec![Obstacle::new(3, 1)]);
+        let spatial_index = SpatialIndex::new(&state);
+
+        let actions = vec![
+            (Action::Move(MoveDirection::Forward), ActionResult::Moved),
+            (Action::Move(MoveDirection::Forward), ActionResult::Blocked),
+            (
+                Action::Move(MoveDirection::Forward),
+                ActionResult::OutOfEnergy,
+            ),
+            (Action::Turn(TurnDirection::Left), ActionResult::Done),
+        ];
+        for (action, expected) in actions {
+            send(&tx, action);
+            state = actor.apply(state, &spatial_index);
+            assert_eq!(result_rx.try_recv(), Ok(expected));
+        }
+
+        // No result is sent if there was no action.
+        actor.apply(state, &spatial_index);
+        assert!(result_rx.try_recv().is_err());
+    }
+
+    /// A level which only accepts the number 3 as input.
+    struct ExpectsThree {}
+
+    impl Level for ExpectsThree {
+        fn name(&self) -> &'static str {
+            "Expects Three"
+        }
+        fn short_name(&self) -> &'static str {
+            "expects_three"
+        }
+        fn objective(&self) -> &'static str {
+            ""
+        }
+        fn initial_code(&self) -> &'static str {
+            ""
+        }
+        fn initial_states(&self) -> Vec<State> {
+            vec![State::new()]
+        }
+        fn actors(&self) -> Vec<Box<dyn Actor>> {
+            vec![]
+        }
+        fn check_win(&self, state: &State) -> crate::levels::Outcome {
+            crate::levels::input_terminals_check_win(state)
+        }
+        fn check_input(&self, _state: &State, _terminal_index: usize, data: &TermData) -> bool {
+            *data == TermData::Int(3)
+        }
+    }
+
+    #[test]
+    fn write_data() {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut actor = PlayerChannelActor::new(Rc::new(RefCell::new(rx)), Bounds::default());
+        actor.set_result_tx(result_tx);
+        actor.set_level(&ExpectsThree {});
+        let mut state = State::new();
+        state.player = Player::new(1, 1, MAX_ENERGY, Orientation::Right);
+        state.input_terminals = vec![InputTerminal::new(2, 1, ButtonConnection::Gate(0))];
+        state.gates = vec![Gate::new(5, 5, false, GateVariant::NWSE)];
+        let spatial_index = SpatialIndex::new(&state);
+
+        // Incorrect data is recorded but rejected.
+        send(&tx, Action::WriteData(TermData::from("3")));
+        state = actor.apply(state, &spatial_index);
+        assert_eq!(result_rx.try_recv(), Ok(ActionResult::Rejected));
+        assert_eq!(
+            state.input_terminals[0].last_input,
+            Some(TermData::from("3"))
+        );
+        assert!(state.input_terminals[0].writing);
+        assert!(!state.input_terminals[0].accepted);
+        assert!(!state.gates[0].open);
+
+        // Correct data opens the connected gate and stays open if written again.
+        for _ in 0..2 {
+            send(&tx, Action::WriteData(TermData::from(3)));
+            state = actor.apply(state, &spatial_index);
+            assert_eq!(result_rx.try_recv(), Ok(ActionResult::Accepted));
+            assert!(state.input_terminals[0].accepted);
+            assert!(state.gates[0].open);
+        }
+        assert_eq!(
+            crate::levels::input_terminals_check_win(&state),
+            crate::levels::Outcome::Success
+        );
+
+        // The writing state only lasts for one step.
+        send(&tx, Action::Wait);
+        state = actor.apply(state, &spatial_index);
+        assert_eq!(result_rx.try_recv(), Ok(ActionResult::Done));
+        assert!(!state.input_terminals[0].writing);
+
+        // Nothing happens if there is no adjacent terminal.
+        state.player.pos = Pos::new(5, 1);
+        send(&tx, Action::WriteData(TermData::from(3)));
+        actor.apply(state, &spatial_index);
+        assert_eq!(result_rx.try_recv(), Ok(ActionResult::Rejected));
+    }
 }
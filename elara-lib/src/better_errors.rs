@@ -2,18 +2,20 @@ use std::collections::HashMap;
 
 use regex::Regex;
 use rhai::EvalAltResult;
+use serde::{Deserialize, Serialize};
 
-use crate::constants::{
-    BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL, BUILTIN_FUNCTIONS,
-    ERR_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL, ERR_UNEXPECTED_SPACE_IN_FUNC_NAME,
-    ERR_UNEXPECTED_SPACE_IN_VAR_NAME,
-};
+use crate::builtins::BUILTIN_FUNCTIONS;
+use crate::constants::{ERR_UNEXPECTED_SPACE_IN_FUNC_NAME, ERR_UNEXPECTED_SPACE_IN_VAR_NAME};
+use crate::script_runner::{EngineLimits, LanguageFeature, OutputLine, POSITION_FUNCTIONS};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BetterError {
     pub message: String,
     pub line: Option<usize>,
     pub col: Option<usize>,
+    /// Any output from print or debug before the error occurred. This is
+    /// empty unless the error happened while running the script.
+    pub output: Vec<OutputLine>,
 }
 
 impl std::fmt::Display for BetterError {
@@ -26,7 +28,7 @@ impl std::fmt::Display for BetterError {
 ///
 /// Example:
 ///
-/// ```
+/// ```ignore
 ///    let trimmed = trim_message("Function not found: move_down () (line 5, position 1)");
 ///    assert_eq!(trimmed, "Function not found: move_down ()");
 /// ```
@@ -39,7 +41,7 @@ fn trim_message(message: &str) -> String {
 ///
 /// Example:
 ///
-/// ```
+/// ```ignore
 ///    let name = fn_name_from_sig("move_down (i64, i64)");
 ///    assert_eq!(name, "move_down");
 /// ```
@@ -91,6 +93,7 @@ fn convert_func_not_found_err(
             message: format!("Error: The {fn_name} function is disabled for this level"),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         };
     }
 
@@ -100,6 +103,7 @@ fn convert_func_not_found_err(
             message: format!("Error: You haven't unlocked the {fn_name} function yet"),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         };
     }
 
@@ -114,6 +118,7 @@ fn convert_func_not_found_err(
                 ),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             },
             1 => {
                 if builtin_fn.arg_types[0] == "any" {
@@ -124,6 +129,7 @@ fn convert_func_not_found_err(
                         ),
                         line: pos.line(),
                         col: pos.position(),
+                        output: vec![],
                     }
                 } else {
                     BetterError {
@@ -133,6 +139,7 @@ fn convert_func_not_found_err(
                         ),
                         line: pos.line(),
                         col: pos.position(),
+                        output: vec![],
                     }
                 }
             }
@@ -145,15 +152,49 @@ fn convert_func_not_found_err(
                 ),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             },
         };
     }
 
+    // Functions and operators which work with Positions are always available,
+    // so if we get here the inputs must be wrong.
+    if let Some((name, arg_types)) = POSITION_FUNCTIONS
+        .iter()
+        .find(|(name, _)| *name == fn_name.as_str())
+    {
+        let is_operator = !name.chars().any(char::is_alphanumeric);
+        if !is_operator {
+            return BetterError {
+                message: format!(
+                    "Error: Wrong inputs for the {} function. Should have {} inputs ({}).",
+                    name,
+                    arg_types.len(),
+                    arg_types.join(", ")
+                ),
+                line: pos.line(),
+                col: pos.position(),
+                output: vec![],
+            };
+        } else if fn_sig.contains("Position") {
+            return BetterError {
+                message: format!(
+                    "Error: The {} operator can only combine a Position with another Position.",
+                    name
+                ),
+                line: pos.line(),
+                col: pos.position(),
+                output: vec![],
+            };
+        }
+    }
+
     // If we reached here this is not a built-in function, just return a generic error.
     BetterError {
         message: format!("Error: There is no function named {fn_name} (maybe you made a typo?)"),
         line: pos.line(),
         col: pos.position(),
+        output: vec![],
     }
 }
 
@@ -223,6 +264,7 @@ fn convert_missing_semicolon_error(script: &str, desc: &str, pos: &rhai::Positio
             message: String::from(ERR_UNEXPECTED_SPACE_IN_VAR_NAME),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         };
     }
 
@@ -231,6 +273,7 @@ fn convert_missing_semicolon_error(script: &str, desc: &str, pos: &rhai::Positio
             message: String::from("Syntax Error: Missing semicolon ';' at end of line."),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         };
     }
     if desc == "to terminate this statement" {
@@ -242,12 +285,14 @@ fn convert_missing_semicolon_error(script: &str, desc: &str, pos: &rhai::Positio
                 message: String::from("Syntax Error: Unexpected extra parentheses '()'."),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             };
         } else if is_extra_closing_parentheses(script, pos) {
             return BetterError {
                 message: String::from("Syntax Error: Unexpected extra closing parentheses ')'."),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             };
         }
 
@@ -269,6 +314,7 @@ fn convert_missing_semicolon_error(script: &str, desc: &str, pos: &rhai::Positio
             message,
             line: Some(line),
             col: pos.position(),
+            output: vec![],
         };
     }
 
@@ -277,6 +323,7 @@ fn convert_missing_semicolon_error(script: &str, desc: &str, pos: &rhai::Positio
         message: String::from("Syntax Error: Missing semicolon ';' at end of line."),
         line: pos.line(),
         col: pos.position(),
+        output: vec![],
     }
 }
 
@@ -306,6 +353,7 @@ fn convert_var_not_found_error(var_name: &str, pos: &rhai::Position) -> BetterEr
             ),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         }
     } else if BUILTIN_FUNCTIONS.contains_key(var_name) {
         BetterError {
@@ -315,12 +363,14 @@ fn convert_var_not_found_error(var_name: &str, pos: &rhai::Position) -> BetterEr
             ),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         }
     } else {
         BetterError {
             message: format!("Error: Variable not found: {}", var_name),
             line: pos.line(),
             col: pos.position(),
+            output: vec![],
         }
     }
 }
@@ -383,6 +433,7 @@ fn convert_missing_comma_separate_args_error(
                 ),
                 line: Some(line),
                 col: Some(col),
+                output: vec![],
             };
         }
     }
@@ -399,6 +450,7 @@ fn convert_missing_comma_separate_args_error(
         ),
         line: Some(line),
         col: Some(col),
+        output: vec![],
     }
 }
 
@@ -415,6 +467,7 @@ fn convert_missing_fn_params_error(
             message: String::from(ERR_UNEXPECTED_SPACE_IN_FUNC_NAME),
             line: err_pos.line(),
             col: err_pos.position(),
+            output: vec![],
         }
     } else {
         // Otherwise, just wrap the original error.
@@ -425,7 +478,81 @@ fn convert_missing_fn_params_error(
             ),
             line: err_pos.line(),
             col: err_pos.position(),
+            output: vec![],
+        }
+    }
+}
+
+fn convert_disabled_feature_err(feature: &LanguageFeature, pos: &rhai::Position) -> BetterError {
+    let message = match feature {
+        LanguageFeature::Loop => "Error: The loop keyword is disabled for this level.",
+        LanguageFeature::WhileLoop => "Error: While loops are disabled for this level.",
+        LanguageFeature::ForLoop => "Error: For loops are disabled for this level.",
+        LanguageFeature::FunctionDefinitions => {
+            "Error: Defining your own functions is disabled for this level."
         }
+        LanguageFeature::Arrays => "Error: Arrays are disabled for this level.",
+    };
+    BetterError {
+        message: String::from(message),
+        line: pos.line(),
+        col: pos.position(),
+        output: vec![],
+    }
+}
+
+/// Converts errors which are caused by the restrictions of a level, i.e. using
+/// a disabled language feature or going over one of the engine limits (see
+/// Level::disabled_features and Level::engine_limits). Returns None if the
+/// error is not one of these.
+pub fn convert_level_restriction_err(
+    disabled_features: &[LanguageFeature],
+    limits: &EngineLimits,
+    err: &EvalAltResult,
+) -> Option<BetterError> {
+    match err {
+        // Rhai treats disabled keywords as reserved. Disabled symbols (i.e. '[')
+        // are reported as an unknown operator when they follow an expression.
+        EvalAltResult::ErrorParsing(
+            rhai::ParseErrorType::Reserved(symbol) | rhai::ParseErrorType::UnknownOperator(symbol),
+            pos,
+        ) => disabled_features
+            .iter()
+            .find(|feature| feature.symbols().contains(&symbol.as_str()))
+            .map(|feature| convert_disabled_feature_err(feature, pos)),
+        EvalAltResult::ErrorStackOverflow(pos) => Some(BetterError {
+            message: format!(
+                "Error: Too many nested function calls. Functions can only call other \
+                functions up to {} levels deep. (Hint: check for functions that call \
+                themselves.)",
+                limits.max_call_levels
+            ),
+            line: pos.line(),
+            col: pos.position(), output: vec![], }),
+        EvalAltResult::ErrorDataTooLarge(typ, pos) if typ == "Size of array" => {
+            Some(BetterError {
+                message: format!(
+                    "Error: Array is too big. Arrays can have at most {} items in this level.",
+                    limits.max_array_size
+                ),
+                line: pos.line(),
+                col: pos.position(), output: vec![], })
+        }
+        EvalAltResult::ErrorDataTooLarge(typ, pos) if typ == "Length of string" => {
+            Some(BetterError {
+                message: format!(
+                    "Error: String is too long. Strings can have at most {} characters in this level.",
+                    limits.max_string_size
+                ),
+                line: pos.line(),
+                col: pos.position(), output: vec![], })
+        }
+        // Limits are often reached inside of a function, in which case the
+        // original error is wrapped.
+        EvalAltResult::ErrorInFunctionCall(_, _, inner, _) => {
+            convert_level_restriction_err(disabled_features, limits, inner)
+        }
+        _ => None,
     }
 }
 
@@ -442,6 +569,7 @@ pub fn convert_err(
                 message: String::from("Error: Possible infinite loop detected."),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             };
         }
         EvalAltResult::ErrorParsing(
@@ -481,24 +609,9 @@ pub fn convert_err(
                 message: String::from("Error: String is missing a quotation mark at the end."),
                 line: pos.line(),
                 col: pos.position(),
+                output: vec![],
             };
         }
-        EvalAltResult::ErrorParsing(
-            rhai::ParseErrorType::BadInput(rhai::LexError::UnexpectedInput(ref input)),
-            ref pos,
-        ) => {
-            // This is a special case of 'unexpected input' that we added in script_runner.rs.
-            // What this really means is that the user tried breaking up function arguments across multiple
-            // lines. Normally this would be allowed, but it is not allowed in Elara because it makes the
-            // semicolon checker too complicated.
-            if input == BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL {
-                return BetterError {
-                    message: String::from(ERR_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL),
-                    line: pos.line(),
-                    col: pos.position(),
-                };
-            }
-        }
         _ => {}
     }
 
@@ -506,14 +619,17 @@ pub fn convert_err(
     let message = trim_message(err.to_string().as_str()).to_string();
     let line = err.position().line();
     let col = err.position().position();
-    BetterError { message, line, col }
+    BetterError {
+        message,
+        line,
+        col,
+        output: vec![],
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::constants::{
-        ERR_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL, ERR_UNEXPECTED_SPACE_IN_FUNC_NAME,
-    };
+    use crate::constants::ERR_UNEXPECTED_SPACE_IN_FUNC_NAME;
 
     use super::*;
 
@@ -566,6 +682,7 @@ mod tests {
                 message: String::from("Syntax Error: Missing semicolon ';' at end of line."),
                 line: Some(4),
                 col: Some(21),
+                output: vec![],
             }
         );
     }
@@ -594,6 +711,7 @@ mod tests {
                 message: String::from("Syntax Error: Missing semicolon ';' at end of line."),
                 line: Some(3),
                 col: Some(13),
+                output: vec![],
             }
         );
 
@@ -619,6 +737,7 @@ mod tests {
                 ),
                 line: Some(3),
                 col: Some(26),
+                output: vec![],
             }
         );
 
@@ -647,6 +766,7 @@ mod tests {
                 ),
                 line: Some(5),
                 col: Some(13),
+                output: vec![],
             }
         );
 
@@ -673,6 +793,7 @@ mod tests {
                 ),
                 line: Some(2),
                 col: Some(15),
+                output: vec![],
             }
         );
 
@@ -699,6 +820,7 @@ mod tests {
                 ),
                 line: Some(3),
                 col: Some(15),
+                output: vec![],
             }
         );
 
@@ -725,6 +847,7 @@ mod tests {
                 message: String::from("Syntax Error: Missing semicolon ';' at end of line."),
                 line: Some(1),
                 col: Some(1),
+                output: vec![],
             }
         );
     }
@@ -744,6 +867,7 @@ mod tests {
                 message: String::from("Error: You haven't unlocked the press_button function yet"),
                 line: Some(1),
                 col: Some(1),
+                output: vec![],
             }
         );
 
@@ -767,6 +891,7 @@ mod tests {
                 ),
                 line: Some(1),
                 col: Some(1),
+                output: vec![],
             }
         );
 
@@ -785,6 +910,7 @@ mod tests {
                 ),
                 line: Some(1),
                 col: Some(1),
+                output: vec![],
             }
         );
 
@@ -803,6 +929,7 @@ mod tests {
                 ),
                 line: Some(1),
                 col: Some(1),
+                output: vec![],
             }
         );
 
@@ -821,30 +948,7 @@ mod tests {
                 ),
                 line: Some(1),
                 col: Some(1),
-            }
-        );
-    }
-
-    #[test]
-    fn test_convert_err_line_break_in_function_call() {
-        let script = String::from(
-            r"move_forward(
-                1
-            );",
-        );
-        let err = EvalAltResult::ErrorParsing(
-            rhai::ParseErrorType::BadInput(rhai::LexError::UnexpectedInput(
-                BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL.to_string(),
-            )),
-            rhai::Position::new(1, 13),
-        );
-        let err = convert_err(&AVAIL_FUNCS, &NO_DISABLED_FUNCS, script, Box::new(err));
-        assert_eq!(
-            err,
-            BetterError {
-                message: String::from(ERR_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL),
-                line: Some(1),
-                col: Some(13),
+                output: vec![],
             }
         );
     }
@@ -866,6 +970,7 @@ mod tests {
                 message: String::from(ERR_UNEXPECTED_SPACE_IN_VAR_NAME),
                 line: Some(1),
                 col: Some(6),
+                output: vec![],
             }
         );
     }
@@ -884,6 +989,7 @@ mod tests {
                 message: String::from(ERR_UNEXPECTED_SPACE_IN_FUNC_NAME),
                 line: Some(1),
                 col: Some(7),
+                output: vec![],
             }
         );
     }
@@ -0,0 +1,423 @@
+//! elara-cli is a native command-line tool for running player scripts against
+//! levels without a browser. It is mainly intended for testing levels and
+//! scripts (e.g. in CI).
+//!
+//! Usage:
+//!
+//! ```text
+//! elara-cli levels
+//! elara-cli run (--level <short_name> | --level-file <level.json>) [options] <script.rhai | ->
+//! elara-cli check (--level <short_name> | --level-file <level.json>) [options] <script.rhai | ->
+//! ```
+//!
+//! The "run" command prints the outcome, stats, challenge result, the
+//! per-step trace and any output from print or debug (or the full versioned
+//! ScriptResult as JSON if --json is given). The "check" command only prints a
+//! short summary and exits with a non-zero status code if the script does not
+//! pass the level. If the script can't be run, both commands print the error
+//! along with any output from print or debug before it (or the versioned
+//! error as JSON if --json is given).
+
+use std::io::Read;
+use std::process::ExitCode;
+
+use elara_lib::{
+    to_json, BetterError, DataLevel, Game, Level, Outcome, OutputLine, ScriptResult,
+    BUILTIN_FUNCTIONS, LEVELS,
+};
+
+const USAGE: &str = "Usage:
+    elara-cli levels
+    elara-cli run (--level <short_name> | --level-file <level.json>) [options] <script.rhai | ->
+    elara-cli check (--level <short_name> | --level-file <level.json>) [options] <script.rhai | ->
+
+Options:
+    --level <short_name>   The short name of the level to run the script against.
+    --level-file <path>    A level definition file (JSON) to run the script against
+                           instead of a built-in level.
+    --funcs <f1,f2,...>    Comma-separated list of unlocked functions. Defaults to
+                           all built-in functions.
+    --seed <n>             Use the given RNG seed to decide the order in which the
+                           possible initial states are run. Runs with the same seed
+                           always give the same result.
+    --state <index>        Only run the script against the initial state with the
+                           given index.
+    --json                 (run only) Print the full result as JSON.
+    --challenge            (check only) Also require the level challenge to pass.";
+
+/// Exit code used when the script ran but did not pass the level.
+const EXIT_FAILURE: u8 = 1;
+/// Exit code used for invalid arguments or scripts that could not be run.
+const EXIT_ERROR: u8 = 2;
+
+enum Command {
+    Help,
+    Levels,
+    Run(RunArgs),
+    Check(RunArgs),
+}
+
+struct RunArgs {
+    level: &'static dyn Level,
+    unlocked_funcs: Vec<String>,
+    script: String,
+    require_challenge: bool,
+    json: bool,
+    seed: Option<u64>,
+    state_index: Option<usize>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Command::Levels => {
+            print_levels();
+            ExitCode::SUCCESS
+        }
+        Command::Run(args) => match run(&args) {
+            Ok(result) if args.json => match to_json(&result) {
+                Ok(json) => {
+                    println!("{}", json);
+                    ExitCode::SUCCESS
+                }
+                Err(err) => {
+                    eprintln!("Could not serialize result: {}", err);
+                    ExitCode::from(EXIT_ERROR)
+                }
+            },
+            Ok(result) => {
+                print_result(args.level, &result);
+                ExitCode::SUCCESS
+            }
+            Err(err) if args.json => {
+                match to_json(&err) {
+                    Ok(json) => println!("{}", json),
+                    Err(json_err) => eprintln!("Could not serialize error: {}", json_err),
+                }
+                ExitCode::from(EXIT_ERROR)
+            }
+            Err(err) => {
+                print_error(&err);
+                ExitCode::from(EXIT_ERROR)
+            }
+        },
+        Command::Check(args) => match run(&args) {
+            Ok(result) => {
+                if passes(args.level, &result, args.require_challenge) {
+                    println!("PASS {}", args.level.short_name());
+                    ExitCode::SUCCESS
+                } else {
+                    let reason = if result.outcome == Outcome::Success {
+                        String::from("challenge not passed")
+                    } else {
+                        outcome_to_string(&result.outcome)
+                    };
+                    // Include the initial state index so that the failing scenario can be
+                    // re-run with --state.
+                    println!(
+                        "FAIL {}: {} (initial state {})",
+                        args.level.short_name(),
+                        reason,
+                        result.initial_state_index
+                    );
+                    ExitCode::from(EXIT_FAILURE)
+                }
+            }
+            Err(err) => {
+                print_error(&err);
+                ExitCode::from(EXIT_ERROR)
+            }
+        },
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err(String::from("Missing command.")),
+    };
+    match command {
+        "levels" => Ok(Command::Levels),
+        "run" => Ok(Command::Run(parse_run_args(rest, false)?)),
+        "check" => Ok(Command::Check(parse_run_args(rest, true)?)),
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        _ => Err(format!("Unknown command: {}", command)),
+    }
+}
+
+fn parse_run_args(args: &[String], is_check: bool) -> Result<RunArgs, String> {
+    let mut level_name = None;
+    let mut level_file = None;
+    let mut unlocked_funcs = None;
+    let mut script_path = None;
+    let mut require_challenge = false;
+    let mut json = false;
+    let mut seed = None;
+    let mut state_index = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--level" => {
+                level_name = Some(iter.next().ok_or("Missing value for --level.")?.clone());
+            }
+            "--level-file" => {
+                level_file = Some(
+                    iter.next()
+                        .ok_or("Missing value for --level-file.")?
+                        .clone(),
+                );
+            }
+            "--funcs" => {
+                let funcs = iter.next().ok_or("Missing value for --funcs.")?;
+                unlocked_funcs = Some(
+                    funcs
+                        .split(',')
+                        .map(|f| f.trim().to_string())
+                        .filter(|f| !f.is_empty())
+                        .collect(),
+                );
+            }
+            "--seed" => {
+                let value = iter.next().ok_or("Missing value for --seed.")?;
+                seed = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for --seed: {}", value))?,
+                );
+            }
+            "--state" => {
+                let value = iter.next().ok_or("Missing value for --state.")?;
+                state_index = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid value for --state: {}", value))?,
+                );
+            }
+            "--challenge" if is_check => require_challenge = true,
+            "--json" if !is_check => json = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ => {
+                if script_path.is_some() {
+                    return Err(String::from("Only one script can be run at a time."));
+                }
+                script_path = Some(arg.clone());
+            }
+        }
+    }
+
+    let level = match (level_name, level_file) {
+        (Some(name), None) => match LEVELS.get(name.as_str()) {
+            Some(level) => level.as_ref(),
+            None => {
+                return Err(format!(
+                    "Unknown level: {} (run `elara-cli levels` to see all levels)",
+                    name
+                ))
+            }
+        },
+        (None, Some(path)) => load_level_file(&path)?,
+        (Some(_), Some(_)) => {
+            return Err(String::from(
+                "Only one of --level or --level-file can be used.",
+            ))
+        }
+        (None, None) => {
+            return Err(String::from(
+                "Missing required option --level or --level-file.",
+            ))
+        }
+    };
+    if seed.is_some() && state_index.is_some() {
+        return Err(String::from("Only one of --seed or --state can be used."));
+    }
+    let script_path = script_path.ok_or("Missing script path.")?;
+    let script = read_script(&script_path)?;
+    let unlocked_funcs = unlocked_funcs.unwrap_or_else(|| {
+        BUILTIN_FUNCTIONS
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    });
+
+    Ok(RunArgs {
+        level,
+        unlocked_funcs,
+        script,
+        require_challenge,
+        json,
+        seed,
+        state_index,
+    })
+}
+
+/// Loads a DataLevel from the given level definition file. The level lives for
+/// the rest of the program.
+fn load_level_file(path: &str) -> Result<&'static dyn Level, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+    let level = DataLevel::from_json(&json)
+        .map_err(|err| format!("Invalid level definition in {}: {}", path, err))?;
+    Ok(Box::leak(Box::new(level)))
+}
+
+/// Reads the script from the given path, or from stdin if the path is "-".
+fn read_script(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut script = String::new();
+        std::io::stdin()
+            .read_to_string(&mut script)
+            .map_err(|err| format!("Could not read script from stdin: {}", err))?;
+        Ok(script)
+    } else {
+        std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))
+    }
+}
+
+fn run(args: &RunArgs) -> Result<ScriptResult, BetterError> {
+    let mut game = Game::new();
+    let script = args.script.clone();
+    match (args.seed, args.state_index) {
+        (_, Some(state_index)) => game.run_player_script_for_state_internal(
+            args.level,
+            &args.unlocked_funcs,
+            script,
+            state_index,
+        ),
+        (Some(seed), None) => game.run_player_script_with_seed_internal(
+            args.level,
+            &args.unlocked_funcs,
+            script,
+            seed,
+        ),
+        (None, None) => game.run_player_script_internal(args.level, &args.unlocked_funcs, script),
+    }
+}
+
+/// Returns true if the result should be considered a pass for the "check"
+/// command. Levels without an objective pass as long as the script runs
+/// without errors.
+fn passes(level: &dyn Level, result: &ScriptResult, require_challenge: bool) -> bool {
+    match result.outcome {
+        Outcome::Success => {
+            !require_challenge || level.challenge().is_none() || result.passes_challenge
+        }
+        Outcome::NoObjective => true,
+        _ => false,
+    }
+}
+
+fn outcome_to_string(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Success => String::from("success"),
+        Outcome::Failure(msg) => format!("failure ({})", msg),
+        Outcome::Continue => String::from("continue (the objective was not reached)"),
+        Outcome::NoObjective => String::from("no_objective"),
+    }
+}
+
+fn print_levels() {
+    let mut levels: Vec<&dyn Level> = LEVELS.values().map(|level| level.as_ref() as _).collect();
+    levels.sort_by_key(|level| level.short_name());
+    for level in levels {
+        println!("{:<32} {}", level.short_name(), level.name());
+    }
+}
+
+/// Prints the error to stderr, after any output from print or debug before
+/// the error occurred.
+fn print_error(err: &BetterError) {
+    if !err.output.is_empty() {
+        eprintln!("Output:");
+        for output_line in &err.output {
+            eprintln!("{}", format_output_line(output_line));
+        }
+    }
+    match (err.line, err.col) {
+        (Some(line), Some(col)) => eprintln!("{} (line {}, col {})", err.message, line, col),
+        (Some(line), None) => eprintln!("{} (line {})", err.message, line),
+        _ => eprintln!("{}", err.message),
+    }
+}
+
+fn print_result(level: &dyn Level, result: &ScriptResult) {
+    println!("Level: {} ({})", level.name(), level.short_name());
+    println!("Outcome: {}", outcome_to_string(&result.outcome));
+    println!(
+        "Initial state: {} (of {})",
+        result.initial_state_index,
+        level.initial_states().len()
+    );
+    let stats = &result.stats;
+    println!(
+        "Stats: code_len={} energy_used={} time_taken={} operations={}",
+        stats.code_len, stats.energy_used, stats.time_taken, stats.operations
+    );
+    println!(
+        "Code metrics: statements={} ast_nodes={} user_functions={} max_loop_depth={} cyclomatic_complexity={}",
+        stats.statements,
+        stats.ast_nodes,
+        stats.user_functions,
+        stats.max_loop_depth,
+        stats.cyclomatic_complexity
+    );
+    let builtin_calls: Vec<String> = stats
+        .builtin_calls
+        .iter()
+        .map(|(name, count)| format!("{}={}", name, count))
+        .collect();
+    if builtin_calls.is_empty() {
+        println!("Built-in calls: none");
+    } else {
+        println!("Built-in calls: {}", builtin_calls.join(" "));
+    }
+    match level.challenge() {
+        Some(challenge) => println!(
+            "Challenge: {} ({})",
+            if result.passes_challenge {
+                "passed"
+            } else {
+                "not passed"
+            },
+            challenge
+        ),
+        None => println!("Challenge: none"),
+    }
+    println!("Trace:");
+    for (i, (state, lines)) in result.states.iter().zip(result.trace.iter()).enumerate() {
+        let player = &state.player;
+        let mut step = format!(
+            "  step {:>3}: lines {:?} pos ({}, {}) facing {:?} energy {}",
+            i, lines, player.pos.x, player.pos.y, player.facing, player.energy
+        );
+        if !player.message.is_empty() {
+            step.push_str(&format!(" says {:?}", player.message));
+        }
+        println!("{}", step);
+    }
+    if !result.output.is_empty() {
+        println!("Output:");
+        for output_line in &result.output {
+            println!("{}", format_output_line(output_line));
+        }
+    }
+}
+
+fn format_output_line(output_line: &OutputLine) -> String {
+    format!(
+        "  step {:>3}: line {} {:?} {}",
+        output_line.step, output_line.line, output_line.kind, output_line.text
+    )
+}
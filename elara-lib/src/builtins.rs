@@ -0,0 +1,890 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+use rhai::{Dynamic, Engine, EvalAltResult, NativeCallContext};
+
+use crate::actors::{
+    is_outside_bounds, Action, ActionResult, MoveDirection, PlayerAction, TurnDirection,
+    BIG_ENEMY_SIZE,
+};
+use crate::constants::{
+    ERR_INVALID_DIRECTION, ERR_INVALID_INPUT, ERR_NO_BUTTON, ERR_NO_DATA_POINT, ERR_NO_GATE,
+    ERR_NO_GOAL, ERR_NO_GOAL_POSITION, ERR_NO_INPUT_TERMINAL,
+};
+use crate::script_runner::VarSnapshot;
+use crate::simulation::{Orientation, Pos, Simulation, State, TermData};
+
+/// A function which is built into the game and can be called from user
+/// scripts. Every built-in function is defined in BUILTINS, and everything
+/// else (registering the function with the Rhai engine, which functions count
+/// as built-in for the debugger, error messages, etc.) is derived from that
+/// definition.
+pub struct Builtin {
+    pub name: &'static str,
+    /// The types of each argument as they are described to the user (e.g. in
+    /// error messages).
+    pub arg_types: &'static [&'static str],
+    /// A short description of what the function does. This is shown to the
+    /// player (e.g. in autocomplete), so it is written in the game's voice.
+    pub doc: &'static str,
+    /// How much energy and time the function costs each time it is called.
+    pub cost: Cost,
+    /// Registers the function with the given engine. The registered function
+    /// is responsible for advancing the simulation.
+    register: fn(&mut Engine, &BuiltinEnv),
+}
+
+impl Builtin {
+    pub fn register(&self, engine: &mut Engine, env: &BuiltinEnv) {
+        (self.register)(engine, env)
+    }
+}
+
+/// The energy and time that a built-in function costs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cost {
+    /// How much energy the function uses for each space that the rover tries
+    /// to move (even if something is in the way). The PlayerChannelActor
+    /// subtracts this from the rover's energy.
+    pub energy_per_space: u32,
+    /// How many steps the simulation advances each time the function is
+    /// called. The infinite loop detector uses this to tell which functions
+    /// step the simulation forward.
+    pub steps: Steps,
+}
+
+/// The number of steps that a built-in function takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Steps {
+    /// The function doesn't advance the simulation at all.
+    Zero,
+    /// The function always takes exactly one step.
+    One,
+    /// The function takes one step for each unit of its argument. E.g.
+    /// move_forward(3) takes three steps.
+    PerArg,
+    /// The function first turns the rover to face the given orientation (one
+    /// step per turn), and then takes one step for each unit of its argument.
+    FaceThenPerArg(Orientation),
+}
+
+/// The pointers that a built-in function needs in order to advance the
+/// simulation and record the trace.
+#[derive(Clone)]
+pub struct BuiltinEnv {
+    pub simulation: Rc<RefCell<Simulation>>,
+    /// Used to send actions to the PlayerChannelActor.
+    pub player_action_tx: Rc<RefCell<mpsc::Sender<PlayerAction>>>,
+    /// Used to receive the result of each action from the PlayerChannelActor.
+    pub action_result_rx: Rc<RefCell<mpsc::Receiver<ActionResult>>>,
+    /// The line numbers for each step in the simulation (see
+    /// ScriptResult::trace).
+    pub trace: Rc<RefCell<Vec<Vec<usize>>>>,
+    /// The variables in scope for each step in the simulation (see
+    /// ScriptResult::vars).
+    pub vars: Rc<RefCell<Vec<Vec<VarSnapshot>>>>,
+    /// The variables in scope for the latest call to a built-in function at
+    /// each depth of the call stack. Native functions can't access the scope,
+    /// so this is filled in by the debugger right before the call.
+    pub call_vars: Rc<RefCell<Vec<Vec<VarSnapshot>>>>,
+}
+
+impl BuiltinEnv {
+    /// Sends the given action to the PlayerChannelActor and then steps the
+    /// simulation forward so that it is performed. Returns the result of the
+    /// action, or None if the simulation has already ended. The action is
+    /// sent along with the built-in function that is currently being called
+    /// so that its cost can be looked up.
+    ///
+    /// If a new state was added, the current line numbers and variables are
+    /// recorded for it. This way the trace always matches the steps that were
+    /// actually taken.
+    fn perform(&self, context: &NativeCallContext, action: Action) -> Option<ActionResult> {
+        // Discard any results which were never received (e.g. from a previous
+        // run) so that they can't be mistaken for the result of this action.
+        while self.action_result_rx.borrow().try_recv().is_ok() {}
+        let builtin = BUILTIN_FUNCTIONS
+            .get(context.fn_name())
+            .expect("actions are only performed by built-in functions");
+        self.player_action_tx
+            .borrow()
+            .send(PlayerAction { action, builtin })
+            .unwrap();
+        let mut simulation = self.simulation.borrow_mut();
+        let history_len = simulation.history_len();
+        simulation.step_forward();
+        if simulation.history_len() == history_len {
+            return None;
+        }
+        let result = self.action_result_rx.borrow().try_recv().ok();
+
+        // The call stack includes this call as well as any user-defined
+        // function calls higher up in the call stack.
+        let call_stack = context.global_runtime_state().debugger().call_stack();
+        let lines = call_stack
+            .iter()
+            .map(|frame| frame.pos.line().unwrap_or_default())
+            .collect::<Vec<usize>>();
+        let depth = call_stack.len().saturating_sub(1);
+        let vars = self
+            .call_vars
+            .borrow()
+            .get(depth)
+            .cloned()
+            .unwrap_or_default();
+        self.trace.borrow_mut().push(lines);
+        self.vars.borrow_mut().push(vars);
+        result
+    }
+
+    /// Performs the given move action the given number of times. Returns the
+    /// number of spaces that the rover actually moved.
+    fn perform_moves(
+        &self,
+        context: &NativeCallContext,
+        direction: MoveDirection,
+        spaces: i64,
+    ) -> i64 {
+        let mut moved = 0;
+        for _ in 0..spaces {
+            if self.perform(context, Action::Move(direction)) == Some(ActionResult::Moved) {
+                moved += 1;
+            }
+        }
+        moved
+    }
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "wait",
+        arg_types: &["number"],
+        doc: "Wait for a number of steps without doing anything.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::PerArg,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("wait", move |context: NativeCallContext, duration: i64| {
+                for _ in 0..duration {
+                    env.perform(&context, Action::Wait);
+                }
+            });
+        },
+    },
+    Builtin {
+        name: "turn_right",
+        arg_types: &[],
+        doc: "Turn right (i.e. clockwise) by 90 degrees.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("turn_right", move |context: NativeCallContext| {
+                env.perform(&context, Action::Turn(TurnDirection::Right));
+            });
+        },
+    },
+    Builtin {
+        name: "turn_left",
+        arg_types: &[],
+        doc: "Turn left (i.e. counter-clockwise) by 90 degrees.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("turn_left", move |context: NativeCallContext| {
+                env.perform(&context, Action::Turn(TurnDirection::Left));
+            });
+        },
+    },
+    Builtin {
+        name: "move_forward",
+        arg_types: &["number"],
+        doc: "Move forward by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::PerArg,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn(
+                "move_forward",
+                move |context: NativeCallContext, spaces: i64| -> i64 {
+                    env.perform_moves(&context, MoveDirection::Forward, spaces)
+                },
+            );
+        },
+    },
+    Builtin {
+        name: "move_backward",
+        arg_types: &["number"],
+        doc: "Move backward by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::PerArg,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn(
+                "move_backward",
+                move |context: NativeCallContext, spaces: i64| -> i64 {
+                    env.perform_moves(&context, MoveDirection::Backward, spaces)
+                },
+            );
+        },
+    },
+    Builtin {
+        name: "move_right",
+        arg_types: &["number"],
+        doc: "Move right by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::FaceThenPerArg(Orientation::Right),
+        },
+        register: |engine, env| register_directional_move(engine, env, "move_right", Orientation::Right),
+    },
+    Builtin {
+        name: "move_left",
+        arg_types: &["number"],
+        doc: "Move left by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::FaceThenPerArg(Orientation::Left),
+        },
+        register: |engine, env| register_directional_move(engine, env, "move_left", Orientation::Left),
+    },
+    Builtin {
+        name: "move_up",
+        arg_types: &["number"],
+        doc: "Move up by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::FaceThenPerArg(Orientation::Up),
+        },
+        register: |engine, env| register_directional_move(engine, env, "move_up", Orientation::Up),
+    },
+    Builtin {
+        name: "move_down",
+        arg_types: &["number"],
+        doc: "Move down by a number of spaces. Outputs the number of spaces G.R.O.V.E.R. actually moved.",
+        cost: Cost {
+            energy_per_space: 1,
+            steps: Steps::FaceThenPerArg(Orientation::Down),
+        },
+        register: |engine, env| register_directional_move(engine, env, "move_down", Orientation::Down),
+    },
+    Builtin {
+        name: "get_position",
+        arg_types: &[],
+        doc: "Outputs the current position of G.R.O.V.E.R.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn("get_position", move || -> Pos {
+                simulation.borrow().curr_state().player.pos
+            });
+        },
+    },
+    Builtin {
+        name: "get_orientation",
+        arg_types: &[],
+        doc: "Outputs whatever direction G.R.O.V.E.R. is facing.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn("get_orientation", move || -> Dynamic {
+                let orientation = simulation.borrow().curr_state().player.facing;
+                let orientation_str = match orientation {
+                    Orientation::Up => "up",
+                    Orientation::Down => "down",
+                    Orientation::Left => "left",
+                    Orientation::Right => "right",
+                };
+                Dynamic::from(orientation_str)
+            });
+        },
+    },
+    Builtin {
+        name: "say",
+        arg_types: &["any"],
+        doc: "Cause G.R.O.V.E.R. to say something.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("say", move |context: NativeCallContext, s: Dynamic| {
+                // Dynamic doesn't know how to format custom types (e.g.
+                // Position), so we go through the engine's to_string instead.
+                let message = context
+                    .call_fn::<String>("to_string", (s.clone(),))
+                    .unwrap_or_else(|_| s.to_string());
+                env.perform(&context, Action::Say(message));
+            });
+        },
+    },
+    Builtin {
+        name: "add",
+        arg_types: &["number", "number"],
+        // Used for teaching about function outputs. (Normally you would use
+        // the + operator instead.)
+        doc: "Add two numbers together.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, _| {
+            engine.register_fn("add", |a: i64, b: i64| -> i64 { a + b });
+        },
+    },
+    Builtin {
+        name: "read_data",
+        arg_types: &[],
+        doc: "Get the data from a nearby data point.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn(
+                "read_data",
+                move |context: NativeCallContext| -> Result<Dynamic, Box<EvalAltResult>> {
+                    env.perform(&context, Action::ReadData);
+
+                    let simulation = env.simulation.borrow();
+                    let state = simulation.curr_state();
+                    let pos = &state.player.pos;
+                    let point_index = simulation.spatial_index().adjacent_data_point(pos);
+                    if let Some(point_index) = point_index {
+                        let data = state.data_points[point_index].data.clone();
+                        Ok(data.into())
+                    } else {
+                        // TODO(albrow): Can we determine the line number for the error message?
+                        Err(ERR_NO_DATA_POINT.into())
+                    }
+                },
+            );
+        },
+    },
+    Builtin {
+        name: "push",
+        arg_types: &["array", "any"],
+        doc: "Add an element to the end of an array.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        // push is provided by Rhai's standard library, so there is nothing
+        // to register.
+        register: |_, _| {},
+    },
+    Builtin {
+        name: "press_button",
+        arg_types: &[],
+        doc: "Press a nearby button. Outputs true if the button was pressed.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("press_button", move |context: NativeCallContext| -> Result<bool, Box<EvalAltResult>> {
+                let state = env.simulation.borrow().curr_state();
+                let pos = &state.player.pos;
+                let button_index = env.simulation.borrow().spatial_index().adjacent_button(pos);
+                if button_index.is_some() {
+                    let result = env.perform(&context, Action::PressButton);
+                    Ok(result == Some(ActionResult::Done))
+                } else {
+                    // TODO(albrow): Can we determine the line number for the error message?
+                    Err(ERR_NO_BUTTON.into())
+                }
+            });
+        },
+    },
+    Builtin {
+        name: "write_data",
+        arg_types: &["any"],
+        doc: "Write data to a nearby input terminal. Outputs true if the data was correct.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::One,
+        },
+        register: |engine, env| {
+            let env = env.clone();
+            engine.register_fn("write_data", move |context: NativeCallContext, value: Dynamic| -> Result<bool, Box<EvalAltResult>> {
+                let state = env.simulation.borrow().curr_state();
+                let pos = &state.player.pos;
+                let terminal_index = env.simulation.borrow().spatial_index().adjacent_input_terminal(pos);
+                if terminal_index.is_none() {
+                    return Err(ERR_NO_INPUT_TERMINAL.into());
+                }
+                match TermData::from_dynamic(&value) {
+                    Some(data) => {
+                        let result = env.perform(&context, Action::WriteData(data));
+                        Ok(result == Some(ActionResult::Accepted))
+                    }
+                    None => Err(ERR_INVALID_INPUT.into()),
+                }
+            });
+        },
+    },
+    Builtin {
+        name: "look_ahead",
+        arg_types: &[],
+        doc: "Outputs whatever is in the space in front of G.R.O.V.E.R. (e.g. \"obstacle\", \"gate\", or \"empty\").",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn("look_ahead", move || -> Dynamic {
+                let simulation = simulation.borrow();
+                let state = simulation.curr_state();
+                let pos = state.player.pos.adjacent(state.player.facing);
+                Dynamic::from(describe_pos(&simulation, &state, &pos))
+            });
+        },
+    },
+    Builtin {
+        name: "get_energy",
+        arg_types: &[],
+        doc: "Outputs how much energy G.R.O.V.E.R. has left.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn("get_energy", move || -> i64 {
+                simulation.borrow().curr_state().player.energy as i64
+            });
+        },
+    },
+    Builtin {
+        name: "is_gate_open",
+        arg_types: &["string"],
+        doc: "Outputs true if the gate next to G.R.O.V.E.R. in the given direction (\"up\", \"down\", \"left\", or \"right\") is open.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn(
+                "is_gate_open",
+                move |direction: &str| -> Result<bool, Box<EvalAltResult>> {
+                    let direction = match parse_direction(direction) {
+                        Some(direction) => direction,
+                        None => return Err(ERR_INVALID_DIRECTION.into()),
+                    };
+                    let simulation = simulation.borrow();
+                    let state = simulation.curr_state();
+                    let pos = state.player.pos.adjacent(direction);
+                    let spatial_index = simulation.spatial_index();
+                    if let Some(i) = spatial_index.gate_at(&pos) {
+                        Ok(state.gates[i].open)
+                    } else if let Some(i) = spatial_index.password_gate_at(&pos) {
+                        Ok(state.password_gates[i].open)
+                    } else {
+                        Err(ERR_NO_GATE.into())
+                    }
+                },
+            );
+        },
+    },
+    Builtin {
+        name: "distance_to_goal",
+        arg_types: &[],
+        doc: "Outputs how many spaces G.R.O.V.E.R. is from the nearest goal, ignoring anything in the way.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn(
+                "distance_to_goal",
+                move || -> Result<i64, Box<EvalAltResult>> {
+                    let state = simulation.borrow().curr_state();
+                    match nearest_goal(&state) {
+                        Some(goal_pos) => Ok(state.player.pos.manhattan_distance(&goal_pos)),
+                        None => Err(ERR_NO_GOAL.into()),
+                    }
+                },
+            );
+        },
+    },
+    Builtin {
+        name: "get_goal_position",
+        arg_types: &[],
+        doc: "Outputs the position of the nearest goal.",
+        cost: Cost {
+            energy_per_space: 0,
+            steps: Steps::Zero,
+        },
+        register: |engine, env| {
+            let simulation = env.simulation.clone();
+            engine.register_fn(
+                "get_goal_position",
+                move || -> Result<Pos, Box<EvalAltResult>> {
+                    let state = simulation.borrow().curr_state();
+                    nearest_goal(&state).ok_or_else(|| ERR_NO_GOAL_POSITION.into())
+                },
+            );
+        },
+    },
+];
+
+lazy_static! {
+    pub static ref BUILTIN_FUNCTIONS: HashMap<&'static str, &'static Builtin> = BUILTINS
+        .iter()
+        .map(|builtin| (builtin.name, builtin))
+        .collect();
+}
+
+/// Returns the position of the goal closest to the rover (if any).
+fn nearest_goal(state: &State) -> Option<Pos> {
+    state
+        .goals
+        .iter()
+        .min_by_key(|goal| state.player.pos.manhattan_distance(&goal.pos))
+        .map(|goal| goal.pos.clone())
+}
+
+/// Returns a description of what is at the given position (see look_ahead).
+fn describe_pos(simulation: &Simulation, state: &State, pos: &Pos) -> &'static str {
+    let spatial_index = simulation.spatial_index();
+    if is_outside_bounds(&simulation.curr_level().bounds(), pos) {
+        "edge"
+    } else if is_enemy_at(state, pos) {
+        "enemy"
+    } else if spatial_index.gate_at(pos).is_some() || spatial_index.password_gate_at(pos).is_some()
+    {
+        "gate"
+    } else if spatial_index.data_point_at(pos).is_some() {
+        "data_point"
+    } else if spatial_index.input_terminal_at(pos).is_some() {
+        "input_terminal"
+    } else if spatial_index.button_at(pos).is_some() {
+        "button"
+    } else if spatial_index.is_obstacle_at(pos) {
+        "obstacle"
+    } else if spatial_index.telepad_at(pos).is_some() {
+        "telepad"
+    } else if state.goals.iter().any(|goal| goal.pos == *pos) {
+        "goal"
+    } else if state
+        .energy_cells
+        .iter()
+        .any(|cell| cell.pos == *pos && !cell.collected)
+    {
+        "energy_cell"
+    } else {
+        "empty"
+    }
+}
+
+fn is_enemy_at(state: &State, pos: &Pos) -> bool {
+    let big_enemy_range = 0..BIG_ENEMY_SIZE;
+    state.enemies.iter().any(|enemy| enemy.pos == *pos)
+        || state.big_enemies.iter().any(|enemy| {
+            big_enemy_range.contains(&(pos.x - enemy.pos.x))
+                && big_enemy_range.contains(&(pos.y - enemy.pos.y))
+        })
+}
+
+/// Parses a direction in the same format returned by get_orientation.
+fn parse_direction(direction: &str) -> Option<Orientation> {
+    match direction {
+        "up" => Some(Orientation::Up),
+        "down" => Some(Orientation::Down),
+        "left" => Some(Orientation::Left),
+        "right" => Some(Orientation::Right),
+        _ => None,
+    }
+}
+
+/// move_right (and other directional move functions) work by first rotating
+/// the rover to the correct orientation, and then moving forward the given
+/// number of spaces.
+fn register_directional_move(
+    engine: &mut Engine,
+    env: &BuiltinEnv,
+    name: &str,
+    target: Orientation,
+) {
+    let env = env.clone();
+    engine.register_fn(
+        name,
+        move |context: NativeCallContext, spaces: i64| -> i64 {
+            let facing = env.simulation.borrow().curr_state().player.facing;
+            for direction in turns_to_face(facing, target) {
+                env.perform(&context, Action::Turn(direction));
+            }
+            env.perform_moves(&context, MoveDirection::Forward, spaces)
+        },
+    );
+}
+
+/// Returns the turns needed to go from facing one orientation to another.
+fn turns_to_face(from: Orientation, to: Orientation) -> Vec<TurnDirection> {
+    use Orientation::*;
+    use TurnDirection::{Left as TurnLeft, Right as TurnRight};
+    match (from, to) {
+        (Up, Right) | (Right, Down) | (Down, Left) | (Left, Up) => vec![TurnRight],
+        (Up, Left) | (Left, Down) | (Down, Right) | (Right, Up) => vec![TurnLeft],
+        // Turning around always goes to the right, except when the target is
+        // left.
+        (Right, Left) => vec![TurnLeft, TurnLeft],
+        (Left, Right) | (Up, Down) | (Down, Up) => vec![TurnRight, TurnRight],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::Outcome;
+
+    #[test]
+    fn builtin_names_are_unique() {
+        assert_eq!(BUILTIN_FUNCTIONS.len(), BUILTINS.len());
+    }
+
+    #[test]
+    fn energy_cost() {
+        let mut game = crate::Game::new();
+        let funcs: Vec<String> = vec!["move_right", "move_down", "turn_left"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        // Turning is free, and calling a function through a function pointer
+        // costs the same as calling it directly.
+        let script = r#"
+            move_down(1);
+            turn_left();
+            Fn("move_right").call(2);
+        "#;
+        let result = game
+            .run_player_script_internal(crate::levels::SANDBOX_LEVEL, &funcs, script.to_string())
+            .unwrap();
+        let energy_per_space = BUILTIN_FUNCTIONS["move_right"].cost.energy_per_space;
+        assert_eq!(
+            result.states.last().unwrap().player.total_energy_used,
+            3 * energy_per_space
+        );
+        assert_eq!(result.stats.energy_used, 3 * energy_per_space);
+    }
+
+    #[test]
+    fn sensors() {
+        let mut game = crate::Game::new();
+        let level = crate::LEVELS.get("gates").unwrap().as_ref();
+        let funcs: Vec<String> = vec![
+            "move_forward",
+            "turn_left",
+            "say",
+            "look_ahead",
+            "get_energy",
+            "is_gate_open",
+            "distance_to_goal",
+            "get_goal_position",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        // The rover starts at (0, 3) facing right. There is a password gate
+        // at (3, 3) and the goal is at (7, 3).
+        let script = r#"
+            let before = `${look_ahead()} ${get_energy()} ${distance_to_goal()} ${get_goal_position()}`;
+            move_forward(2);
+            let closed = `${look_ahead()} ${is_gate_open("right")}`;
+            say("lovelace");
+            let open = `${is_gate_open("right")} ${get_energy()} ${distance_to_goal()}`;
+            turn_left();
+            say(`${before} | ${closed} | ${open} | ${look_ahead()}`);
+        "#;
+        let result = game
+            .run_player_script_internal(level, &funcs, script.to_string())
+            .unwrap();
+        assert_eq!(
+            result.states.last().unwrap().player.message,
+            "empty 10 7 (7, 3) | gate false | true 8 5 | empty"
+        );
+
+        // There is no gate above the rover.
+        let result = game
+            .run_player_script_internal(level, &funcs, r#"is_gate_open("up");"#.to_string())
+            .unwrap();
+        assert!(matches!(result.outcome, Outcome::Failure(msg) if msg.contains(ERR_NO_GATE)));
+        let result = game
+            .run_player_script_internal(level, &funcs, r#"is_gate_open("north");"#.to_string())
+            .unwrap();
+        assert!(
+            matches!(result.outcome, Outcome::Failure(msg) if msg.contains(ERR_INVALID_DIRECTION))
+        );
+
+        // The rover is at the edge of the grid.
+        let result = game
+            .run_player_script_internal(
+                level,
+                &funcs,
+                "turn_left(); turn_left(); say(look_ahead());".to_string(),
+            )
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "edge");
+    }
+
+    #[test]
+    fn movement_results() {
+        let mut game = crate::Game::new();
+        let level = crate::LEVELS.get("gates").unwrap().as_ref();
+        let funcs: Vec<String> = vec!["move_forward", "move_down", "say"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        // The rover starts at (0, 3) facing right and the password gate at
+        // (3, 3) is closed.
+        let script = r#"
+            let blocked = move_forward(5);
+            say("lovelace");
+            let open = move_forward(2);
+            let down = move_down(2);
+            say(`${blocked} ${open} ${down}`);
+        "#;
+        let result = game
+            .run_player_script_internal(level, &funcs, script.to_string())
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "2 2 2");
+    }
+
+    #[test]
+    fn say_formats_custom_types() {
+        let mut game = crate::Game::new();
+        let level = crate::LEVELS.get("gates").unwrap().as_ref();
+        let funcs: Vec<String> = vec!["say", "get_position"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        // The rover starts at (0, 3).
+        let result = game
+            .run_player_script_internal(level, &funcs, "say(get_position());".to_string())
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "(0, 3)");
+
+        let result = game
+            .run_player_script_internal(level, &funcs, "say([get_position(), 1]);".to_string())
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "[(0, 3), 1]");
+
+        let result = game
+            .run_player_script_internal(level, &funcs, "say(\"hello\");".to_string())
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "hello");
+    }
+
+    /// A level where the objective is to write the sum of the array held by
+    /// the data point to the input terminal.
+    struct SumLevel {}
+
+    impl crate::Level for SumLevel {
+        fn name(&self) -> &'static str {
+            "Sum"
+        }
+        fn short_name(&self) -> &'static str {
+            "sum"
+        }
+        fn objective(&self) -> &'static str {
+            ""
+        }
+        fn initial_code(&self) -> &'static str {
+            ""
+        }
+        fn initial_states(&self) -> Vec<State> {
+            let mut state = State::new();
+            state.player = crate::simulation::Player::new(1, 1, 10, Orientation::Right);
+            state.data_points = std::rc::Rc::new(vec![crate::simulation::DataPoint::new(
+                1,
+                0,
+                TermData::from(vec![1, 2, 3]),
+            )]);
+            state.input_terminals = vec![crate::simulation::InputTerminal::new(
+                1,
+                2,
+                crate::simulation::ButtonConnection::None,
+            )];
+            vec![state]
+        }
+        fn actors(&self) -> Vec<Box<dyn crate::simulation::Actor>> {
+            vec![]
+        }
+        fn check_win(&self, state: &State) -> Outcome {
+            crate::levels::input_terminals_check_win(state)
+        }
+        fn check_input(&self, state: &State, _terminal_index: usize, data: &TermData) -> bool {
+            let sum = match &state.data_points[0].data {
+                TermData::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        TermData::Int(n) => *n,
+                        _ => 0,
+                    })
+                    .sum(),
+                _ => 0,
+            };
+            *data == TermData::Int(sum)
+        }
+    }
+
+    #[test]
+    fn write_data() {
+        let mut game = crate::Game::new();
+        let funcs: Vec<String> = vec!["read_data", "write_data", "say"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let script = r#"
+            let wrong = write_data(5);
+            let sum = 0;
+            for n in read_data() {
+                sum += n;
+            }
+            say(`${wrong}`);
+            write_data(sum);
+        "#;
+        let result = game
+            .run_player_script_internal(&SumLevel {}, &funcs, script.to_string())
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+        let states = &result.states;
+        assert_eq!(states[states.len() - 2].player.message, "false");
+
+        let result = game
+            .run_player_script_internal(&SumLevel {}, &funcs, "write_data(|| 6);".to_string())
+            .unwrap();
+        assert!(matches!(result.outcome, Outcome::Failure(msg) if msg.contains(ERR_INVALID_INPUT)));
+    }
+}
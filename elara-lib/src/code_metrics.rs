@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+
+use rhai::{ASTNode, Expr, Stmt, AST};
+
+use crate::builtins::BUILTIN_FUNCTIONS;
+use crate::script_query::is_loop;
+
+/// Metrics which are computed from the code itself (i.e. without running
+/// it). See ScriptStats for the metrics which depend on running the script.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CodeMetrics {
+    /// The number of statements, including statements inside of blocks and
+    /// function bodies.
+    pub statements: usize,
+    /// The total number of statements and expressions.
+    pub ast_nodes: usize,
+    /// The number of times each built-in function is called in the code.
+    /// Built-in functions which are never called are not included.
+    pub builtin_calls: BTreeMap<String, usize>,
+    /// The number of user-defined functions.
+    pub user_functions: usize,
+    /// The max number of loops nested inside of each other (0 if there are no
+    /// loops). Loops inside of a function body are not considered to be nested
+    /// inside of loops which call the function.
+    pub max_loop_depth: usize,
+    /// The number of independent paths through the code, i.e. 1 plus the
+    /// number of places where the code can take one of two or more branches
+    /// (if statements, loop conditions, switch cases, and the && and ||
+    /// operators).
+    pub cyclomatic_complexity: usize,
+}
+
+/// Computes the metrics for the given AST. The AST should be compiled without
+/// optimizations so that it reflects the code as it was written.
+pub fn compute_code_metrics(ast: &AST) -> CodeMetrics {
+    let mut metrics = CodeMetrics {
+        user_functions: ast.iter_fn_def().count(),
+        cyclomatic_complexity: 1,
+        ..CodeMetrics::default()
+    };
+    ast.walk(&mut |path| {
+        metrics.ast_nodes += 1;
+        // Loops inside of a function body are walked separately from the
+        // main body, so they are never counted as nested inside of the loops
+        // which call the function.
+        let loop_depth = path.iter().filter(|node| is_loop(node)).count();
+        metrics.max_loop_depth = metrics.max_loop_depth.max(loop_depth);
+        if let Some(ASTNode::Stmt(stmt)) = path.last() {
+            if !matches!(stmt, Stmt::Noop(_)) {
+                metrics.statements += 1;
+            }
+        }
+        metrics.cyclomatic_complexity += num_branches(path.last().unwrap());
+
+        let fn_call = match path.last() {
+            Some(ASTNode::Stmt(Stmt::FnCall(x, _))) | Some(ASTNode::Expr(Expr::FnCall(x, _))) => {
+                Some(x)
+            }
+            _ => None,
+        };
+        if let Some(fn_call) = fn_call {
+            if fn_call.namespace.is_empty() && BUILTIN_FUNCTIONS.contains_key(fn_call.name.as_str())
+            {
+                *metrics
+                    .builtin_calls
+                    .entry(fn_call.name.to_string())
+                    .or_default() += 1;
+            }
+        }
+        true
+    });
+    metrics
+}
+
+/// Returns the number of additional paths through the code introduced by the
+/// given node.
+fn num_branches(node: &ASTNode) -> usize {
+    match node {
+        // Note that a `loop` is represented as a while loop with a unit
+        // condition. It doesn't add a branch on its own (only a break inside
+        // of an if statement would).
+        ASTNode::Stmt(Stmt::While(x, _)) => !matches!(x.expr, Expr::Unit(_)) as usize,
+        ASTNode::Stmt(Stmt::If(..))
+        | ASTNode::Stmt(Stmt::Do(..))
+        | ASTNode::Stmt(Stmt::For(..))
+        | ASTNode::Stmt(Stmt::TryCatch(..))
+        | ASTNode::Expr(Expr::And(..))
+        | ASTNode::Expr(Expr::Or(..))
+        | ASTNode::Expr(Expr::Coalesce(..)) => 1,
+        ASTNode::Stmt(Stmt::Switch(x, _)) => {
+            let cases = &x.1;
+            cases.expressions.len() - cases.def_case.is_some() as usize
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rhai::{Engine, OptimizationLevel};
+
+    use super::*;
+
+    fn compute_script_metrics(script: &str) -> CodeMetrics {
+        let mut engine = Engine::new_raw();
+        engine.set_optimization_level(OptimizationLevel::None);
+        compute_code_metrics(&engine.compile(script).unwrap())
+    }
+
+    #[test]
+    fn simple_script() {
+        let metrics = compute_script_metrics(
+            r"// Comments don't count.
+            move_forward(2);
+            turn_right();
+            move_forward(1);",
+        );
+        assert_eq!(metrics.statements, 3);
+        // Each function call is an expression wrapped in a statement, plus one
+        // node for each argument.
+        assert_eq!(metrics.ast_nodes, 8);
+        assert_eq!(
+            metrics.builtin_calls,
+            BTreeMap::from([
+                (String::from("move_forward"), 2),
+                (String::from("turn_right"), 1)
+            ])
+        );
+        assert_eq!(metrics.user_functions, 0);
+        assert_eq!(metrics.max_loop_depth, 0);
+        assert_eq!(metrics.cyclomatic_complexity, 1);
+    }
+
+    #[test]
+    fn loops_and_functions() {
+        let metrics = compute_script_metrics(
+            r"fn go_around() {
+                for i in 0..4 {
+                    move_forward(1);
+                    turn_right();
+                }
+            }
+            loop {
+                let data = read_data();
+                while data != 0 && data != 1 {
+                    go_around();
+                    data -= 1;
+                }
+                if data == 0 {
+                    break;
+                }
+            }",
+        );
+        assert_eq!(metrics.user_functions, 1);
+        // The for loop is inside of a function, so it doesn't count as being
+        // nested inside of the other loops.
+        assert_eq!(metrics.max_loop_depth, 2);
+        // for, while, &&, and if.
+        assert_eq!(metrics.cyclomatic_complexity, 5);
+        assert_eq!(metrics.builtin_calls.get("read_data"), Some(&1));
+        // User-defined functions are not counted.
+        assert_eq!(metrics.builtin_calls.get("go_around"), None);
+    }
+}
@@ -1,6 +1,5 @@
-use std::collections::HashMap;
-
-/// Width and height of the grid.
+/// Default width and height of the grid. Levels can use a different size by
+/// overriding Level::width and Level::height.
 pub static WIDTH: u32 = 12;
 pub static HEIGHT: u32 = 8;
 
@@ -10,6 +9,11 @@ pub static MAX_ENERGY: u32 = 50;
 /// The amount of energy to add if the player is on a energy cell.
 pub static ENERGY_CELL_AMOUNT: u32 = 10;
 
+/// Max number of telepads in a single state. The number of possible initial
+/// states grows exponentially with the number of telepads (see
+/// make_all_initial_states_for_telepads), so we need to limit it.
+pub const MAX_TELEPADS: usize = 3;
+
 /// Various common error messages.
 pub static ERR_OUT_OF_ENERGY: &str = "G.R.O.V.E.R. ran out of energy!";
 pub static ERR_DESTROYED_BY_ENEMY: &str =
@@ -23,136 +27,33 @@ pub static ERR_SIMULATION_END: &str = "SIMULATION_END";
 pub static ERR_NO_DATA_POINT: &str = "read_data only works if you are next to a data point.";
 /// Returned from press_button if you call it when not adjacent to a button.
 pub static ERR_NO_BUTTON: &str = "press_button only works if you are next to a button.";
-/// Returned as the "input" to a BadInput error by our custom semicolon checker code
-/// if there is a line break in the middle of a function call.
-pub static BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL: &str = "line break in function call";
-/// Returned as the end-user error message if there is a line break in the middle of a function call.
-pub static ERR_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL: &str =
-    "Error: Unexpected line break. (Hint: you may need to move the function arguments to the same line as the function call.)";
+/// Returned from write_data if you call it when not adjacent to an input
+/// terminal.
+pub static ERR_NO_INPUT_TERMINAL: &str =
+    "write_data only works if you are next to an input terminal.";
+/// Returned from write_data if the value can't be written to a terminal.
+pub static ERR_INVALID_INPUT: &str =
+    "write_data only accepts strings, numbers, booleans, and arrays or maps of those.";
+/// Returned from is_gate_open if there is no gate in the given direction.
+pub static ERR_NO_GATE: &str =
+    "is_gate_open only works if there is a gate next to you in the given direction.";
+/// Returned from is_gate_open if the direction is not valid.
+pub static ERR_INVALID_DIRECTION: &str =
+    r#"is_gate_open expects a direction: "up", "down", "left", or "right"."#;
+/// Returned from distance_to_goal if the level doesn't have a goal.
+pub static ERR_NO_GOAL: &str = "distance_to_goal only works if there is a goal.";
+/// Returned from get_goal_position if the level doesn't have a goal.
+pub static ERR_NO_GOAL_POSITION: &str = "get_goal_position only works if there is a goal.";
+/// Returned when indexing a Position with anything other than 0 or 1.
+pub static ERR_POSITION_INDEX: &str =
+    "Positions only have two parts: use 0 for the x part or 1 for the y part.";
+/// Returned when creating a Position (or adding or subtracting Positions)
+/// would give an x or y part that is too big or too small.
+pub static ERR_POSITION_OUT_OF_RANGE: &str =
+    "That position is too far away. The x and y parts must be between -2147483648 and 2147483647.";
 pub static ERR_UNEXPECTED_SPACE_IN_VAR_NAME: &str = "Syntax Error: Variable names cannot contain spaces. (Hint: try using an underscore instead of a space.)";
 pub static ERR_UNEXPECTED_SPACE_IN_FUNC_NAME: &str = "Syntax Error: Function names cannot contain spaces. (Hint: try using an underscore instead of a space.)";
-pub struct BuiltinFunction {
-    pub name: &'static str,
-    pub arg_types: &'static [&'static str],
-}
-
-lazy_static! {
-    pub static ref BUILTIN_FUNCTIONS: HashMap<&'static str, BuiltinFunction> = {
-        let mut m: HashMap<&'static str, BuiltinFunction> = HashMap::new();
-
-        m.insert(
-            "turn_right",
-            BuiltinFunction {
-                name: "turn_right",
-                arg_types: &[],
-            },
-        );
-        m.insert(
-            "turn_left",
-            BuiltinFunction {
-                name: "turn_left",
-                arg_types: &[],
-            },
-        );
-        m.insert(
-            "move_forward",
-            BuiltinFunction {
-                name: "move_forward",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "move_backward",
-            BuiltinFunction {
-                name: "move_backward",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "move_down",
-            BuiltinFunction {
-                name: "move_down",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "move_up",
-            BuiltinFunction {
-                name: "move_up",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "move_left",
-            BuiltinFunction {
-                name: "move_left",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "move_right",
-            BuiltinFunction {
-                name: "move_right",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "say",
-            BuiltinFunction {
-                name: "say",
-                arg_types: &["any"],
-            },
-        );
-        m.insert(
-            "get_position",
-            BuiltinFunction {
-                name: "get_position",
-                arg_types: &[],
-            },
-        );
-        m.insert(
-            "get_orientation",
-            BuiltinFunction {
-                name: "get_orientation",
-                arg_types: &[],
-            },
-        );
-        m.insert(
-            "add",
-            BuiltinFunction {
-                name: "add",
-                arg_types: &["number", "number"],
-            },
-        );
-        m.insert(
-            "read_data",
-            BuiltinFunction {
-                name: "read_data",
-                arg_types: &[],
-            },
-        );
-        m.insert(
-            "wait",
-            BuiltinFunction {
-                name: "wait",
-                arg_types: &["number"],
-            },
-        );
-        m.insert(
-            "push",
-            BuiltinFunction {
-                name: "push",
-                arg_types: &["array", "any"],
-            },
-        );
-        m.insert(
-            "press_button",
-            BuiltinFunction {
-                name: "press_button",
-                arg_types: &[],
-            },
-        );
-
-        m
-    };
-}
+/// Returned by the infinite loop detector if a loop can never end.
+pub static ERR_INFINITE_LOOP: &str = "Error: Infinite loop detected. (Hint: make sure the loop can end, e.g. by using break or by moving G.R.O.V.E.R. inside the loop.)";
+/// Returned by the infinite loop detector if a function always calls itself.
+pub static ERR_INFINITE_RECURSION: &str = "Error: Infinite recursion detected. This function always calls itself. (Hint: use an if statement so that it only calls itself some of the time.)";
@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+
+use rhai::{ASTFlags, ASTNode, Expr, FnCallExpr, Position, Stmt, StmtBlock, AST};
+
+use crate::better_errors::BetterError;
+use crate::builtins::{Steps, BUILTIN_FUNCTIONS};
+use crate::constants::{ERR_INFINITE_LOOP, ERR_INFINITE_RECURSION};
+use crate::script_query::is_loop;
+
+/// Checks the AST for loops and recursive functions which can never end,
+/// without running the script. Returns an error for the first one found.
+///
+/// A loop is considered infinite if there is no way to break out of it, its
+/// condition can never change, and it never steps the simulation forward.
+/// Loops which step the simulation forward are allowed since the simulation
+/// will end on its own (e.g. when the objective is reached or G.R.O.V.E.R.
+/// runs out of energy). Similarly, a function is considered infinitely
+/// recursive if it always calls itself and never steps the simulation forward.
+///
+/// Calls to functions which are not built-in or defined in the script (e.g.
+/// calls through a function pointer or closure) could do anything, so they
+/// are assumed to step the simulation forward and change variables.
+///
+/// This is a best-effort check. It only catches simple cases and errs on the
+/// side of not reporting an error, since a false positive would prevent a
+/// valid script from running. Anything it misses will still be caught by the
+/// engine's max operations limit.
+pub fn check_infinite_loops(ast: &AST) -> Result<(), BetterError> {
+    let fns = find_script_fns(ast);
+    check_loops(ast, &fns)?;
+    check_recursion(ast, &fns)
+}
+
+/// The names of the functions defined in a script.
+struct ScriptFns {
+    /// All user-defined functions, including closures.
+    defined: HashSet<String>,
+    /// The user-defined functions which step the simulation forward (either
+    /// directly or by calling another function which does).
+    stepping: HashSet<String>,
+}
+
+fn find_script_fns(ast: &AST) -> ScriptFns {
+    let mut fns = ScriptFns {
+        defined: ast.iter_fn_def().map(|f| f.name.to_string()).collect(),
+        stepping: HashSet::new(),
+    };
+    // Keep going until we stop finding new functions. This handles
+    // functions which call other user-defined functions.
+    loop {
+        let mut found_new = false;
+        for fn_def in ast.iter_fn_def() {
+            if !fns.stepping.contains(fn_def.name.as_str())
+                && any_node_in_block(&fn_def.body, |path| steps(path, &fns))
+            {
+                fns.stepping.insert(fn_def.name.to_string());
+                found_new = true;
+            }
+        }
+        if !found_new {
+            return fns;
+        }
+    }
+}
+
+fn check_loops(ast: &AST, fns: &ScriptFns) -> Result<(), BetterError> {
+    let mut infinite_loop_pos = None;
+    ast.walk(&mut |path| {
+        infinite_loop_pos = match path.last() {
+            // While loops end when the condition is false. Note that a
+            // `loop` is represented as a while loop with a unit condition.
+            Some(ASTNode::Stmt(Stmt::While(x, pos))) => {
+                is_infinite_loop(&x.expr, &x.body, false, fns).then_some(*pos)
+            }
+            // Do loops end when the condition is false, or when the condition
+            // is true for do..until loops.
+            Some(ASTNode::Stmt(Stmt::Do(x, flags, pos))) => {
+                let exits_when = flags.contains(ASTFlags::NEGATED);
+                is_infinite_loop(&x.expr, &x.body, exits_when, fns).then_some(*pos)
+            }
+            _ => None,
+        };
+        infinite_loop_pos.is_none()
+    });
+    match infinite_loop_pos {
+        Some(pos) => Err(BetterError {
+            message: String::from(ERR_INFINITE_LOOP),
+            line: pos.line(),
+            col: pos.position(),
+            output: vec![],
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Returns true if a loop with the given condition and body can never end.
+/// The loop ends when the condition is equal to exits_when.
+fn is_infinite_loop(condition: &Expr, body: &StmtBlock, exits_when: bool, fns: &ScriptFns) -> bool {
+    if let Expr::BoolConstant(value, _) = condition {
+        if *value == exits_when {
+            return false;
+        }
+    }
+    !can_exit_loop(body)
+        && !any_node_in_expr(condition, |path| steps(path, fns))
+        && !any_node_in_block(body, |path| steps(path, fns))
+        && !condition_can_change(condition, body, fns)
+}
+
+/// Returns true if the loop body contains a break (for this loop, not a
+/// nested one), return, or throw statement.
+fn can_exit_loop(body: &StmtBlock) -> bool {
+    any_node_in_block(body, |path| match path.last() {
+        Some(ASTNode::Stmt(Stmt::Return(..))) => true,
+        Some(ASTNode::Stmt(Stmt::BreakLoop(_, flags, _))) if flags.contains(ASTFlags::BREAK) => {
+            !path[..path.len() - 1].iter().any(is_loop)
+        }
+        _ => false,
+    })
+}
+
+/// Returns true if any variable used in the condition might be changed by
+/// the loop body (or by the condition itself).
+fn condition_can_change(condition: &Expr, body: &StmtBlock, fns: &ScriptFns) -> bool {
+    let mut condition_vars = HashSet::new();
+    let condition_has_side_effects = any_node_in_expr(condition, |path| match path.last() {
+        Some(ASTNode::Expr(Expr::Variable(x, ..))) => {
+            condition_vars.insert(x.3.to_string());
+            false
+        }
+        Some(node) => has_side_effects(node, fns),
+        None => false,
+    });
+    if condition_has_side_effects {
+        return true;
+    }
+    any_node_in_block(body, |path| match path.last() {
+        Some(ASTNode::Stmt(Stmt::Assignment(x))) => {
+            root_var_name(&x.1.lhs).is_some_and(|name| condition_vars.contains(name))
+        }
+        Some(ASTNode::Expr(Expr::Dot(x, ..))) => {
+            root_var_name(&x.lhs).is_some_and(|name| condition_vars.contains(name))
+        }
+        Some(node) => has_side_effects(node, fns),
+        None => false,
+    })
+}
+
+/// Returns true if the given node might change variables other than the ones
+/// it directly assigns to. This includes method calls (which can mutate the
+/// object they are called on), function calls which capture the parent
+/// scope, and unknown functions (e.g. closures, which can mutate the variables
+/// they capture).
+fn has_side_effects(node: &ASTNode, fns: &ScriptFns) -> bool {
+    match node {
+        ASTNode::Expr(Expr::Dot(x, ..)) => matches!(x.rhs, Expr::MethodCall(..)),
+        ASTNode::Expr(Expr::FnCall(call, ..)) | ASTNode::Stmt(Stmt::FnCall(call, ..)) => {
+            call.capture_parent_scope || is_unknown_fn(call, fns)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the name of the variable at the root of the given expression (e.g.
+/// "a" for `a`, `a.b`, or `a[0]`).
+fn root_var_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Variable(x, ..) => Some(x.3.as_str()),
+        Expr::Dot(x, ..) | Expr::Index(x, ..) => root_var_name(&x.lhs),
+        _ => None,
+    }
+}
+
+/// Returns true if the given name is a built-in function which causes the
+/// simulation to step forward.
+fn is_stepping_builtin(name: &str) -> bool {
+    BUILTIN_FUNCTIONS
+        .get(name)
+        .is_some_and(|builtin| builtin.cost.steps != Steps::Zero)
+}
+
+/// Returns true if the given call is not an operator, a built-in function, or
+/// a function defined in the script. This includes calls through function
+/// pointers and closures (e.g. `f.call()`, `call(f)`, or `f()` where f is a
+/// variable), which can't be determined ahead of time.
+fn is_unknown_fn(call: &FnCallExpr, fns: &ScriptFns) -> bool {
+    let name = call.name.as_str();
+    call.op_token.is_none() && !BUILTIN_FUNCTIONS.contains_key(name) && !fns.defined.contains(name)
+}
+
+/// Returns true if the last node in the path is a function call which might
+/// step the simulation forward.
+fn steps(path: &[ASTNode], fns: &ScriptFns) -> bool {
+    match path.last() {
+        Some(ASTNode::Expr(Expr::FnCall(call, ..)))
+        | Some(ASTNode::Expr(Expr::MethodCall(call, ..)))
+        | Some(ASTNode::Stmt(Stmt::FnCall(call, ..))) => {
+            let name = call.name.as_str();
+            is_stepping_builtin(name) || fns.stepping.contains(name) || is_unknown_fn(call, fns)
+        }
+        _ => false,
+    }
+}
+
+fn check_recursion(ast: &AST, fns: &ScriptFns) -> Result<(), BetterError> {
+    for fn_def in ast.iter_fn_def() {
+        if fns.stepping.contains(fn_def.name.as_str()) {
+            continue;
+        }
+        let is_self_call = |call: &FnCallExpr| {
+            call.name == fn_def.name
+                && call.args.len() == fn_def.params.len()
+                && call.namespace.is_empty()
+        };
+        if let Some(pos) = find_unconditional_call(fn_def.body.statements(), &is_self_call) {
+            return Err(BetterError {
+                message: String::from(ERR_INFINITE_RECURSION),
+                line: pos.line(),
+                col: pos.position(),
+                output: vec![],
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the position of the first function call matching is_match which is
+/// always reached when running the given statements. Calls inside the body of
+/// an if statement, loop, etc. are not always reached, so they are ignored.
+fn find_unconditional_call(
+    stmts: &[Stmt],
+    is_match: &impl Fn(&FnCallExpr) -> bool,
+) -> Option<Position> {
+    for stmt in stmts {
+        let found = match stmt {
+            Stmt::FnCall(call, pos) if is_match(call) => Some(*pos),
+            Stmt::FnCall(call, _) => call
+                .args
+                .iter()
+                .find_map(|arg| find_unconditional_call_in_expr(arg, is_match)),
+            Stmt::Var(x, ..) => find_unconditional_call_in_expr(&x.1, is_match),
+            Stmt::Assignment(x) => find_unconditional_call_in_expr(&x.1.rhs, is_match)
+                .or_else(|| find_unconditional_call_in_expr(&x.1.lhs, is_match)),
+            Stmt::Expr(expr) => find_unconditional_call_in_expr(expr, is_match),
+            Stmt::Block(block) => find_unconditional_call(block.statements(), is_match),
+            // Only the condition is always evaluated.
+            Stmt::If(x, _) | Stmt::While(x, _) => {
+                find_unconditional_call_in_expr(&x.expr, is_match)
+            }
+            Stmt::For(x, _) => find_unconditional_call_in_expr(&x.2.expr, is_match),
+            Stmt::Switch(x, _) => find_unconditional_call_in_expr(&x.0, is_match),
+            // The body of a do loop always runs at least once.
+            Stmt::Do(x, ..) => find_unconditional_call(x.body.statements(), is_match)
+                .or_else(|| find_unconditional_call_in_expr(&x.expr, is_match)),
+            Stmt::Return(Some(expr), ..) => find_unconditional_call_in_expr(expr, is_match),
+            _ => None,
+        };
+        if found.is_some() {
+            return found;
+        }
+        // Any statements after a return or break are not always reached.
+        let can_exit = any_node_in_stmt(stmt, |path| {
+            matches!(
+                path.last(),
+                Some(ASTNode::Stmt(Stmt::Return(..))) | Some(ASTNode::Stmt(Stmt::BreakLoop(..)))
+            )
+        });
+        if can_exit {
+            return None;
+        }
+    }
+    None
+}
+
+fn find_unconditional_call_in_expr(
+    expr: &Expr,
+    is_match: &impl Fn(&FnCallExpr) -> bool,
+) -> Option<Position> {
+    match expr {
+        Expr::FnCall(call, pos) if is_match(call) => Some(*pos),
+        Expr::FnCall(call, _) | Expr::MethodCall(call, _) => call
+            .args
+            .iter()
+            .find_map(|arg| find_unconditional_call_in_expr(arg, is_match)),
+        Expr::Dot(x, ..) | Expr::Index(x, ..) => find_unconditional_call_in_expr(&x.lhs, is_match)
+            .or_else(|| find_unconditional_call_in_expr(&x.rhs, is_match)),
+        // Only the left side of these operators is always evaluated.
+        Expr::And(x, _) | Expr::Or(x, _) | Expr::Coalesce(x, _) => {
+            find_unconditional_call_in_expr(&x.lhs, is_match)
+        }
+        Expr::Stmt(block) => find_unconditional_call(block.statements(), is_match),
+        _ => None,
+    }
+}
+
+/// Returns true if pred returns true for any node in the given statement
+/// (including the statement itself). pred is called with the path from the
+/// statement to the node.
+fn any_node_in_stmt(stmt: &Stmt, mut pred: impl FnMut(&[ASTNode]) -> bool) -> bool {
+    !stmt.walk(&mut vec![], &mut |path| !pred(path))
+}
+
+fn any_node_in_block(block: &StmtBlock, mut pred: impl FnMut(&[ASTNode]) -> bool) -> bool {
+    block
+        .iter()
+        .any(|stmt| !stmt.walk(&mut vec![], &mut |path| !pred(path)))
+}
+
+fn any_node_in_expr(expr: &Expr, mut pred: impl FnMut(&[ASTNode]) -> bool) -> bool {
+    !expr.walk(&mut vec![], &mut |path| !pred(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rhai::Engine;
+
+    fn check(script: &str) -> Result<(), BetterError> {
+        let ast = Engine::new().compile(script).unwrap();
+        check_infinite_loops(&ast)
+    }
+
+    #[test]
+    fn infinite_loops() {
+        let err = check("let x = 0;\nloop {\n  x += 1;\n}").unwrap_err();
+        assert_eq!(err.message, ERR_INFINITE_LOOP);
+        assert_eq!(err.line, Some(2));
+
+        let err = check("while true {\n  let y = 1;\n}").unwrap_err();
+        assert_eq!(err.line, Some(1));
+
+        // The condition never changes.
+        let err = check("let x = 0;\nlet y = 0;\nwhile x < 5 {\n  y += 1;\n}").unwrap_err();
+        assert_eq!(err.line, Some(3));
+
+        let err = check("let x = 0;\ndo {\n  x += 1;\n} while true;").unwrap_err();
+        assert_eq!(err.line, Some(2));
+
+        // A break inside a nested loop doesn't count.
+        let err = check("loop {\n  loop {\n    break;\n  }\n}").unwrap_err();
+        assert_eq!(err.line, Some(1));
+
+        // Loops inside functions are checked too.
+        let err = check("fn f() {\n  loop {}\n}\nf();").unwrap_err();
+        assert_eq!(err.line, Some(2));
+    }
+
+    #[test]
+    fn loops_that_can_end() {
+        // Loops which step the simulation forward.
+        assert!(check("loop { move_forward(1); }").is_ok());
+        assert!(check("while true { say(\"hi\"); }").is_ok());
+        assert!(check(
+            "fn advance() { turn_left(); }\nfn advance_twice() { advance(); advance(); }\nloop { advance_twice(); }"
+        )
+        .is_ok());
+        assert!(
+            check("let x = 0;\nwhile get_pos(x) { }\nfn get_pos(x) { move_forward(x); true }")
+                .is_ok()
+        );
+
+        // Loops which break or return.
+        assert!(check("let x = 0;\nloop {\n  x += 1;\n  if x > 5 { break; }\n}").is_ok());
+        assert!(check("fn f() {\n  loop { return 1; }\n}\nf();").is_ok());
+        assert!(check("loop { throw \"oops\"; }").is_ok());
+
+        // Loops where the condition changes.
+        assert!(check("let x = 0;\nwhile x < 5 { x += 1; }").is_ok());
+        assert!(check("let a = [1, 2];\nwhile a.len() > 0 { a.pop(); }").is_ok());
+        assert!(check("let a = [1, 2];\nwhile a[0] < 5 { a[0] += 1; }").is_ok());
+        assert!(check("let x = 0;\ndo { x += 1; } until x > 5;").is_ok());
+        // Closures can change the variables they capture, so calls through
+        // function pointers might change the condition.
+        assert!(check("let x = 0;\nlet f = || { x += 1; };\nwhile x < 3 { f.call(); }").is_ok());
+        assert!(check("let x = 0;\nlet f = || { x += 1; };\nwhile x < 3 { f(); }").is_ok());
+        assert!(check("let x = 0;\nlet f = || { x += 1; };\nwhile x < 3 { call(f); }").is_ok());
+
+        // Loops which never run or only run once.
+        assert!(check("while false { }").is_ok());
+        assert!(check("do { } while false;").is_ok());
+        assert!(check("do { } until true;").is_ok());
+        assert!(check("for i in 0..10 { }").is_ok());
+    }
+
+    #[test]
+    fn infinite_recursion() {
+        let err = check("fn f(x) {\n  let y = x + 1;\n  f(y);\n}\nf(0);").unwrap_err();
+        assert_eq!(err.message, ERR_INFINITE_RECURSION);
+        assert_eq!(err.line, Some(3));
+
+        let err = check("fn f(x) {\n  return f(x) + 1;\n}").unwrap_err();
+        assert_eq!(err.line, Some(2));
+
+        let err = check("fn f() {\n  if f() { 1 } else { 2 }\n}").unwrap_err();
+        assert_eq!(err.line, Some(2));
+
+        // Recursion which can end.
+        assert!(check("fn f(x) {\n  if x > 0 { f(x - 1); }\n}\nf(5);").is_ok());
+        assert!(check("fn f(x) {\n  if x > 0 { return 1; }\n  f(x + 1)\n}").is_ok());
+        assert!(check("fn f(x) {\n  x > 0 && f(x - 1)\n}").is_ok());
+        // Calling a different overload isn't recursion.
+        assert!(check("fn f() { 1 }\nfn f(x) { f() }").is_ok());
+        // Recursion which steps the simulation forward is allowed.
+        assert!(check("fn advance() {\n  move_forward(1);\n  advance();\n}\nadvance();").is_ok());
+    }
+}
@@ -1,9 +1,12 @@
 use js_sys::{Array, Object};
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
+use crate::builtins::Builtin;
 use crate::constants::{ERR_NO_BUTTON, ERR_NO_DATA_POINT};
 use crate::levels::Outcome;
 use crate::script_runner;
+use crate::session;
 use crate::simulation::{
     BigEnemyAnimState, EnemyAnimState, GateVariant, ObstacleKind, Orientation,
     OrientationWithDiagonals, PlayerAnimState, TermData,
@@ -15,6 +18,7 @@ pub struct RhaiError {
     pub message: String,
     pub line: usize,
     pub col: usize,
+    pub output: Array, // Array<OutputLine>
 }
 
 /// The state and active line numbers associated with each step in
@@ -24,6 +28,61 @@ pub struct RhaiError {
 pub struct StateWithLines {
     pub state: State,
     pub lines: Array, // Array<number>
+    pub vars: Array,  // Array<Var>
+}
+
+/// A snapshot of a variable in the user's script. See
+/// script_runner::VarSnapshot.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Var {
+    pub name: String,
+    pub type_name: String,
+    pub value: String,
+}
+
+/// A single call to print or debug. See script_runner::OutputLine.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct OutputLine {
+    pub step: usize,
+    pub line: usize,
+    pub kind: String, // "print" | "debug"
+    pub text: String,
+}
+
+pub fn to_js_output(output: &[script_runner::OutputLine]) -> Array {
+    let output_array = Array::new_with_length(output.len() as u32);
+    for (i, output_line) in output.iter().enumerate() {
+        output_array.set(
+            i as u32,
+            JsValue::from(OutputLine {
+                step: output_line.step,
+                line: output_line.line,
+                kind: match output_line.kind {
+                    script_runner::OutputKind::Print => String::from("print"),
+                    script_runner::OutputKind::Debug => String::from("debug"),
+                },
+                text: output_line.text.clone(),
+            }),
+        );
+    }
+    output_array
+}
+
+fn to_js_vars(vars: &[script_runner::VarSnapshot]) -> Array {
+    let vars_array = Array::new_with_length(vars.len() as u32);
+    for (i, var) in vars.iter().enumerate() {
+        vars_array.set(
+            i as u32,
+            JsValue::from(Var {
+                name: var.name.clone(),
+                type_name: var.type_name.clone(),
+                value: var.value.clone(),
+            }),
+        );
+    }
+    vars_array
 }
 
 #[wasm_bindgen]
@@ -52,14 +111,41 @@ pub struct ScriptStats {
     pub energy_used: i32,
     // Amount of time (i.e. number of steps) taken to execute the script.
     pub time_taken: i32,
+    // Number of operations performed by the Rhai engine.
+    pub operations: i32,
+    pub statements: i32,
+    pub ast_nodes: i32,
+    pub builtin_calls: Object, // Record<string, number>
+    pub user_functions: i32,
+    pub max_loop_depth: i32,
+    pub cyclomatic_complexity: i32,
 }
 
 impl From<&script_runner::ScriptStats> for ScriptStats {
     fn from(stats: &script_runner::ScriptStats) -> Self {
+        let builtin_calls = Object::new();
+        for (name, count) in stats.builtin_calls.iter() {
+            #[allow(unused_unsafe)]
+            unsafe {
+                js_sys::Reflect::set(
+                    &builtin_calls,
+                    &JsValue::from(name.to_string()),
+                    &JsValue::from(*count as i32),
+                )
+                .unwrap();
+            }
+        }
         Self {
             code_len: stats.code_len as i32,
             energy_used: stats.energy_used as i32,
             time_taken: stats.time_taken as i32,
+            operations: stats.operations as i32,
+            statements: stats.statements as i32,
+            ast_nodes: stats.ast_nodes as i32,
+            builtin_calls,
+            user_functions: stats.user_functions as i32,
+            max_loop_depth: stats.max_loop_depth as i32,
+            cyclomatic_complexity: stats.cyclomatic_complexity as i32,
         }
     }
 }
@@ -68,17 +154,26 @@ impl From<&script_runner::ScriptStats> for ScriptStats {
 #[derive(Clone, PartialEq, Debug)]
 pub struct RunResult {
     pub states: Array,            // Array<FuzzyStateWithLines>
+    pub output: Array,            // Array<OutputLine>
     pub outcome: String,          // "success" | "continue" | "other failure message"
     pub err_type: Option<String>, // "err_no_button" | "err_no_data_point"
     pub stats: ScriptStats,
     pub passes_challenge: bool,
+    pub initial_state_index: usize,
 }
 
 /// Converts script_runner::ScriptResult to a format that is wasm_bindgen
 /// compatible and can ultimately be used by the JavaScript code.
 pub fn to_js_run_result(result: &script_runner::ScriptResult) -> RunResult {
     let states_array = Array::new_with_length(result.states.len() as u32);
-    for (i, (state, lines)) in result.states.iter().zip(result.trace.iter()).enumerate() {
+    let mut cache = SharedLayerCache::default();
+    for (i, ((state, lines), vars)) in result
+        .states
+        .iter()
+        .zip(result.trace.iter())
+        .zip(result.vars.iter())
+        .enumerate()
+    {
         let lines_array = Array::new_with_length(lines.len() as u32);
         for (j, &line_number) in lines.iter().enumerate() {
             lines_array.set(j as u32, line_number.into());
@@ -86,8 +181,9 @@ pub fn to_js_run_result(result: &script_runner::ScriptResult) -> RunResult {
         states_array.set(
             i as u32,
             JsValue::from(StateWithLines {
-                state: State::from(state.clone()),
+                state: State::from_with_cache(state, &mut cache),
                 lines: lines_array,
+                vars: to_js_vars(vars),
             }),
         );
     }
@@ -105,24 +201,110 @@ pub fn to_js_run_result(result: &script_runner::ScriptResult) -> RunResult {
     };
     RunResult {
         states: states_array,
-        outcome: match &result.outcome {
-            Outcome::Success => String::from("success"),
-            Outcome::Failure(msg) => msg.clone(),
-            Outcome::Continue => String::from("continue"),
-            Outcome::NoObjective => String::from("no_objective"),
-        },
+        output: to_js_output(&result.output),
+        outcome: to_js_outcome(&result.outcome),
         stats: ScriptStats::from(&result.stats),
         passes_challenge: result.passes_challenge,
+        initial_state_index: result.initial_state_index,
         err_type,
     }
 }
 
+fn to_js_outcome(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Success => String::from("success"),
+        Outcome::Failure(msg) => msg.clone(),
+        Outcome::Continue => String::from("continue"),
+        Outcome::NoObjective => String::from("no_objective"),
+    }
+}
+
+/// A single step of a session. See session::SessionStep.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct SessionStep {
+    pub step: usize,
+    pub state: State,
+    pub lines: Array,    // Array<number>
+    pub vars: Array,     // Array<Var>
+    pub outcome: String, // "success" | "continue" | "no_objective" | "other failure message"
+    pub done: bool,
+    pub result: Option<RunResult>,
+    pub pause: Option<Pause>,
+    pub breakpoint_errors: Array, // Array<RhaiError>
+}
+
+/// Describes where a session is paused. See script_runner::Pause.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pause {
+    pub line: usize,
+    pub call_stack: Array, // Array<StackFrame>
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct StackFrame {
+    pub fn_name: String,
+    pub line: usize,
+}
+
+fn to_js_pause(pause: &script_runner::Pause) -> Pause {
+    let call_stack = Array::new_with_length(pause.call_stack.len() as u32);
+    for (i, frame) in pause.call_stack.iter().enumerate() {
+        call_stack.set(
+            i as u32,
+            JsValue::from(StackFrame {
+                fn_name: frame.fn_name.clone(),
+                line: frame.line,
+            }),
+        );
+    }
+    Pause {
+        line: pause.line,
+        call_stack,
+    }
+}
+
+pub fn to_js_session_step(step: &session::SessionStep) -> SessionStep {
+    let lines = Array::new_with_length(step.lines.len() as u32);
+    for (i, &line_number) in step.lines.iter().enumerate() {
+        lines.set(i as u32, line_number.into());
+    }
+    let breakpoint_errors = Array::new_with_length(step.breakpoint_errors.len() as u32);
+    for (i, err) in step.breakpoint_errors.iter().enumerate() {
+        breakpoint_errors.set(
+            i as u32,
+            JsValue::from(RhaiError {
+                message: err.message.clone(),
+                line: err.line.unwrap_or(0),
+                col: err.col.unwrap_or(0),
+                output: to_js_output(&err.output),
+            }),
+        );
+    }
+    SessionStep {
+        step: step.step,
+        state: State::from(step.state.clone()),
+        lines,
+        vars: to_js_vars(&step.vars),
+        outcome: to_js_outcome(&step.outcome),
+        done: step.done,
+        result: step.result.as_ref().map(to_js_run_result),
+        pause: step.pause.as_ref().map(to_js_pause),
+        breakpoint_errors,
+    }
+}
+
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone, PartialEq, Debug)]
 pub struct LevelData {
     pub name: String,
     pub short_name: String,
     pub objective: String,
+    /// The width and height of the grid for the level.
+    pub width: u32,
+    pub height: u32,
     pub initial_state: State,
     pub initial_code: String,
     pub disabled_funcs: Array, // Array<String>
@@ -149,6 +331,8 @@ impl LevelData {
             name: level.name().to_string(),
             short_name: level.short_name().to_string(),
             objective: level.objective().to_string(),
+            width: level.width(),
+            height: level.height(),
             initial_code: level.initial_code().to_string(),
             initial_state: State::from(level.filtered_initial_state()),
             disabled_funcs,
@@ -174,6 +358,37 @@ pub fn to_level_data_obj(levels: levels::LEVELS) -> Object {
     obj
 }
 
+/// Documentation for a built-in function. Used for autocomplete in the editor.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct BuiltinDoc {
+    pub name: String,
+    pub arg_types: Array, // Array<String>
+    pub doc: String,
+    /// See Builtin::cost.
+    pub energy_per_space: u32,
+}
+
+pub fn to_builtin_docs_array(builtins: &[Builtin]) -> Array {
+    let docs = Array::new_with_length(builtins.len() as u32);
+    for (i, builtin) in builtins.iter().enumerate() {
+        let arg_types = Array::new();
+        for arg_type in builtin.arg_types {
+            arg_types.push(&JsValue::from(arg_type.to_string()));
+        }
+        docs.set(
+            i as u32,
+            JsValue::from(BuiltinDoc {
+                name: builtin.name.to_string(),
+                arg_types,
+                doc: builtin.doc.to_string(),
+                energy_per_space: builtin.cost.energy_per_space,
+            }),
+        );
+    }
+    docs
+}
+
 #[wasm_bindgen]
 #[derive(Clone, PartialEq, Debug)]
 pub struct AsteroidWarning {
@@ -265,16 +480,17 @@ fn get_js_big_enemy_anim_data(anim_state: &BigEnemyAnimState) -> Option<JsValue>
 #[derive(Clone, PartialEq, Debug)]
 pub struct State {
     pub player: Player,
-    pub energy_cells: Array,   // Array<EnergyCell>
-    pub goals: Array,          // Array<Goal>
-    pub enemies: Array,        // Array<Enemy>
-    pub obstacles: Array,      // Array<Obstacle>
-    pub password_gates: Array, // Array<PasswordGate>
-    pub data_points: Array,    // Array<DataPoint>
-    pub telepads: Array,       // Array<Telepad>
-    pub buttons: Array,        // Array<Button>
-    pub gates: Array,          // Array<Gate>
-    pub big_enemies: Array,    // Array<BigEnemy>
+    pub energy_cells: Array,    // Array<EnergyCell>
+    pub goals: Array,           // Array<Goal>
+    pub enemies: Array,         // Array<Enemy>
+    pub obstacles: Array,       // Array<Obstacle>
+    pub password_gates: Array,  // Array<PasswordGate>
+    pub data_points: Array,     // Array<DataPoint>
+    pub input_terminals: Array, // Array<InputTerminal>
+    pub telepads: Array,        // Array<Telepad>
+    pub buttons: Array,         // Array<Button>
+    pub gates: Array,           // Array<Gate>
+    pub big_enemies: Array,     // Array<BigEnemy>
 }
 
 impl State {
@@ -294,6 +510,7 @@ impl State {
     //         obstacles: Array::new(),
     //         password_gates: Array::new(),
     //         data_points: Array::new(),
+    //         input_terminals: Array::new(),
     //         telepads: Array::new(),
     //         buttons: Array::new(),
     //         gates: Array::new(),
@@ -302,6 +519,12 @@ impl State {
     // }
 
     pub fn from(state: simulation::State) -> Self {
+        Self::from_with_cache(&state, &mut SharedLayerCache::default())
+    }
+
+    /// Like from, but re-uses the JavaScript arrays for any layers which are
+    /// shared with a previously converted state.
+    fn from_with_cache(state: &simulation::State, cache: &mut SharedLayerCache) -> Self {
         let energy_cells = Array::new_with_length(state.energy_cells.len() as u32);
         for (i, energy_cell) in state.energy_cells.iter().enumerate() {
             energy_cells.set(
@@ -359,23 +582,7 @@ impl State {
             );
         }
 
-        let obstacles = Array::new_with_length(state.obstacles.len() as u32);
-        for (i, obstacle) in state.obstacles.iter().enumerate() {
-            obstacles.set(
-                i as u32,
-                JsValue::from(Obstacle {
-                    pos: Pos {
-                        x: obstacle.pos.x as i32,
-                        y: obstacle.pos.y as i32,
-                    },
-                    kind: match obstacle.kind {
-                        ObstacleKind::Rock => "rock".to_string(),
-                        ObstacleKind::Server => "server".to_string(),
-                        ObstacleKind::Asteroid => "asteroid".to_string(),
-                    },
-                }),
-            );
-        }
+        let obstacles = cache.obstacles(&state.obstacles);
 
         let password_gates = Array::new_with_length(state.password_gates.len() as u32);
         for (i, pw_gate) in state.password_gates.iter().enumerate() {
@@ -399,18 +606,24 @@ impl State {
             );
         }
 
-        let data_points = Array::new_with_length(state.data_points.len() as u32);
-        for (i, data_point) in state.data_points.iter().enumerate() {
-            data_points.set(
+        let data_points = cache.data_points(&state.data_points);
+
+        let input_terminals = Array::new_with_length(state.input_terminals.len() as u32);
+        for (i, terminal) in state.input_terminals.iter().enumerate() {
+            input_terminals.set(
                 i as u32,
-                JsValue::from(DataPoint {
+                JsValue::from(InputTerminal {
                     pos: Pos {
-                        x: data_point.pos.x as i32,
-                        y: data_point.pos.y as i32,
+                        x: terminal.pos.x,
+                        y: terminal.pos.y,
+                    },
+                    last_input: match &terminal.last_input {
+                        Some(data) => term_data_to_js(data),
+                        None => JsValue::UNDEFINED,
                     },
-                    data: term_data_to_js(&data_point.data),
-                    reading: data_point.reading,
-                    additional_info: data_point.additional_info.clone(),
+                    accepted: terminal.accepted,
+                    writing: terminal.writing,
+                    additional_info: terminal.additional_info.clone(),
                 }),
             );
         }
@@ -515,13 +728,14 @@ impl State {
         }
 
         State {
-            player: Player::from(state.player),
+            player: Player::from(state.player.clone()),
             energy_cells,
             goals,
             enemies,
             obstacles,
             password_gates,
             data_points,
+            input_terminals,
             telepads,
             buttons,
             gates,
@@ -530,6 +744,82 @@ impl State {
     }
 }
 
+/// Caches the JavaScript arrays for the layers of State which are shared
+/// between steps (see simulation::State). If a layer is the same (i.e. the
+/// same Rc) as in the last state that was converted, the cached array is
+/// returned instead of converting the layer again.
+#[derive(Default)]
+struct SharedLayerCache {
+    obstacles: Option<(Rc<Vec<simulation::Obstacle>>, Array)>,
+    data_points: Option<(Rc<Vec<simulation::DataPoint>>, Array)>,
+}
+
+impl SharedLayerCache {
+    fn obstacles(&mut self, obstacles: &Rc<Vec<simulation::Obstacle>>) -> Array {
+        get_or_convert(&mut self.obstacles, obstacles, obstacles_to_js)
+    }
+
+    fn data_points(&mut self, data_points: &Rc<Vec<simulation::DataPoint>>) -> Array {
+        get_or_convert(&mut self.data_points, data_points, data_points_to_js)
+    }
+}
+
+fn get_or_convert<T>(
+    cached: &mut Option<(Rc<Vec<T>>, Array)>,
+    layer: &Rc<Vec<T>>,
+    convert: fn(&[T]) -> Array,
+) -> Array {
+    if let Some((cached_layer, array)) = cached {
+        if Rc::ptr_eq(cached_layer, layer) {
+            return array.clone();
+        }
+    }
+    let array = convert(layer);
+    *cached = Some((layer.clone(), array.clone()));
+    array
+}
+
+fn obstacles_to_js(obstacles: &[simulation::Obstacle]) -> Array {
+    let array = Array::new_with_length(obstacles.len() as u32);
+    for (i, obstacle) in obstacles.iter().enumerate() {
+        array.set(
+            i as u32,
+            JsValue::from(Obstacle {
+                pos: Pos {
+                    x: obstacle.pos.x as i32,
+                    y: obstacle.pos.y as i32,
+                },
+                kind: match obstacle.kind {
+                    ObstacleKind::Rock => "rock".to_string(),
+                    ObstacleKind::Server => "server".to_string(),
+                    ObstacleKind::Asteroid => "asteroid".to_string(),
+                },
+            }),
+        );
+    }
+    array
+}
+
+fn data_points_to_js(data_points: &[simulation::DataPoint]) -> Array {
+    let array = Array::new_with_length(data_points.len() as u32);
+    for (i, data_point) in data_points.iter().enumerate() {
+        array.set(
+            i as u32,
+            JsValue::from(DataPoint {
+                pos: Pos {
+                    x: data_point.pos.x as i32,
+                    y: data_point.pos.y as i32,
+                },
+                data: term_data_to_js(&data_point.data),
+                data_type: data_point.data.type_name().to_string(),
+                reading: data_point.reading,
+                additional_info: data_point.additional_info.clone(),
+            }),
+        );
+    }
+    array
+}
+
 fn term_data_to_js(data: &TermData) -> JsValue {
     match data {
         TermData::String(str) => JsValue::from_str(str),
@@ -540,6 +830,19 @@ fn term_data_to_js(data: &TermData) -> JsValue {
             }
             JsValue::from(js_arr)
         }
+        TermData::Int(n) => JsValue::from_f64(*n as f64),
+        TermData::Bool(b) => JsValue::from_bool(*b),
+        TermData::Map(map) => {
+            let js_obj = Object::new();
+            for (key, value) in map.iter() {
+                #[allow(unused_unsafe)]
+                unsafe {
+                    js_sys::Reflect::set(&js_obj, &JsValue::from_str(key), &term_data_to_js(value))
+                        .unwrap();
+                }
+            }
+            JsValue::from(js_obj)
+        }
     }
 }
 
@@ -637,11 +940,22 @@ pub struct PasswordGate {
 #[derive(Clone, PartialEq, Debug)]
 pub struct DataPoint {
     pub pos: Pos,
-    pub data: JsValue, // string | string[]
+    pub data: JsValue,     // string | number | boolean | array | object
+    pub data_type: String, // "string" | "number" | "boolean" | "array" | "map"
     pub reading: bool,
     pub additional_info: String,
 }
 
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputTerminal {
+    pub pos: Pos,
+    pub last_input: JsValue, // undefined if no data has been written yet
+    pub accepted: bool,
+    pub writing: bool,
+    pub additional_info: String,
+}
+
 #[wasm_bindgen(getter_with_clone)]
 #[derive(Clone, PartialEq, Debug)]
 pub struct Telepad {
@@ -133,6 +133,20 @@ impl Level for AsteroidStrikePartTwo {
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"let safe_direction = read_data();
+move_forward(3);
+if safe_direction == "top" {
+  turn_left();
+}
+if safe_direction == "bottom" {
+  turn_right();
+}
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
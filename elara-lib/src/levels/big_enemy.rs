@@ -1,6 +1,6 @@
 use super::{make_all_initial_states_for_telepads, std_check_win, Level, Outcome};
 use crate::{
-    actors::{BigEnemyActor, Bounds},
+    actors::BigEnemyActor,
     script_runner::ScriptStats,
     simulation::{
         Actor, BigEnemy, Button, ButtonConnection, DataPoint, EnergyCell, Gate, GateVariant, Goal,
@@ -117,7 +117,7 @@ fn face_direction(direction) {
         make_all_initial_states_for_telepads(base_states)
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
-        vec![Box::new(BigEnemyActor::new(0, Bounds::default()))]
+        vec![Box::new(BigEnemyActor::new(0, self.bounds()))]
     }
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
@@ -133,6 +133,63 @@ fn face_direction(direction) {
         }
         true
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"fn face_direction(direction) {
+  while get_orientation() != direction {
+    turn_left();
+  }
+}
+
+turn_left();
+move_forward(4);
+face_direction("right");
+move_forward(1);
+let password = read_data();
+move_forward(10);
+face_direction("up");
+move_forward(1);
+say(password);
+move_forward(3);
+press_button();
+move_backward(3);
+turn_left();
+move_forward(6);
+turn_right();
+move_forward(4);
+turn_right();
+move_forward(1);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"turn_left();
+move_forward(3);
+turn_right();
+move_forward(1);
+turn_left();
+move_forward(1);
+turn_right();
+move_forward(5);
+let password = read_data();
+move_backward(5);
+turn_right();
+move_forward(11);
+turn_left();
+say(password);
+move_forward(3);
+press_button();
+move_backward(3);
+turn_left();
+move_forward(6);
+turn_right();
+move_forward(4);
+turn_right();
+move_forward(1);
+"#,
+        )
+    }
 }
 
 // TODO(albrow): Add more test cases.
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{
     Actor, Button, ButtonConnection, Gate, GateVariant, Goal, Obstacle, Orientation, Player, State,
@@ -41,7 +43,7 @@ move_forward(4);
             GateVariant::NESW,
             "This gate can be unlocked by pressing the nearby button.".into(),
         )];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(6, 6),
             Obstacle::new(6, 7),
             Obstacle::new(7, 6),
@@ -53,7 +55,7 @@ move_forward(4);
             Obstacle::new(10, 5),
             Obstacle::new(10, 6),
             Obstacle::new(11, 2),
-        ];
+        ]);
         state.goals = vec![Goal::new(7, 7)];
         vec![state]
     }
@@ -63,6 +65,19 @@ move_forward(4);
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"turn_right();
+move_forward(3);
+press_button();
+turn_left();
+turn_left();
+move_forward(3);
+turn_right();
+move_forward(4);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -48,6 +48,13 @@ impl Level for ButtonsPartOne {
             Outcome::Continue
         }
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(2);
+press_button();
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
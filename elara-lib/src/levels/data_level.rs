@@ -0,0 +1,1223 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use super::{
+    input_terminals_check_win, make_all_initial_states_for_telepads, no_objective_check_win,
+    std_check_win, Level, Outcome,
+};
+use crate::actors::{BigEnemyActor, EvilRoverActor};
+use crate::constants::{ERR_OUT_OF_ENERGY, HEIGHT, MAX_ENERGY, MAX_TELEPADS, WIDTH};
+use crate::script_query::ScriptQuery;
+use crate::script_runner::{EngineLimits, LanguageFeature, ScriptStats};
+use crate::simulation::{
+    Actor, BigEnemy, Button, ButtonConnection, DataPoint, Enemy, EnergyCell, Gate, GateVariant,
+    Goal, InputTerminal, Obstacle, ObstacleKind, Orientation, OrientationWithDiagonals,
+    PasswordGate, Player, PlayerAnimState, State, Telepad, TermData,
+};
+
+/// A declarative definition of a level. This is the format used for level
+/// files (currently JSON). It can express everything that the built-in
+/// levels need, i.e. the grid entities, the player start, any possible
+/// initial-state variants, disabled functions and language features, engine
+/// limits, the objective type, and the challenge criteria.
+///
+/// Positions are written as `[x, y]`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelDef {
+    pub name: String,
+    pub short_name: String,
+    pub objective: String,
+    pub initial_code: String,
+    #[serde(default)]
+    pub disabled_functions: Vec<String>,
+    /// Language features which can't be used in this level.
+    #[serde(default)]
+    pub disabled_features: Vec<LanguageFeature>,
+    /// Limits on the resources scripts can use. Any limits which are not
+    /// given use the default values.
+    #[serde(default)]
+    pub engine_limits: EngineLimits,
+    pub objective_type: ObjectiveType,
+    /// Determines which data is correct for each input terminal, in the same
+    /// order as the input terminals in the initial state.
+    #[serde(default)]
+    pub input_checks: Vec<InputCheck>,
+    #[serde(default)]
+    pub challenge: Option<ChallengeDef>,
+    /// The width of the grid. Defaults to WIDTH.
+    #[serde(default = "default_width")]
+    pub width: u32,
+    /// The height of the grid. Defaults to HEIGHT.
+    #[serde(default = "default_height")]
+    pub height: u32,
+    /// The part of the initial state which is shared by all variants.
+    pub initial_state: StateDef,
+    /// Possible variations of the initial state. Each variant is merged with
+    /// initial_state to create one possible initial state: entities in the
+    /// variant are added to the ones in initial_state, and the player (if
+    /// present) replaces the one in initial_state. If there are no variants,
+    /// initial_state is the only possible initial state.
+    #[serde(default)]
+    pub variants: Vec<StateDef>,
+    /// If true, the possible initial states are expanded to include all
+    /// possible orientations for each telepad. See
+    /// make_all_initial_states_for_telepads.
+    #[serde(default)]
+    pub all_telepad_orientations: bool,
+}
+
+fn default_width() -> u32 {
+    WIDTH
+}
+
+fn default_height() -> u32 {
+    HEIGHT
+}
+
+/// Determines how Level::check_win works for a DataLevel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObjectiveType {
+    /// Move the rover to any goal (see std_check_win).
+    ReachGoal,
+    /// Press the button with the given index.
+    PressButton { button: usize },
+    /// Say the given message.
+    SayMessage { message: String },
+    /// Write the correct data to every input terminal (see
+    /// input_terminals_check_win).
+    WriteInputs,
+    /// No objective (e.g. sandbox levels).
+    NoObjective,
+}
+
+/// Determines how Level::check_input works for an input terminal in a
+/// DataLevel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputCheck {
+    /// The data must be equal to the given value.
+    Equals { value: TermData },
+    /// The data must be equal to the data held by the data point with the
+    /// given index.
+    CopyDataPoint { data_point: usize },
+    /// The data must be equal to the sum of the array of numbers held by the
+    /// data point with the given index.
+    SumDataPoint { data_point: usize },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChallengeDef {
+    /// The description of the challenge which is shown to the user.
+    pub description: String,
+    /// All criteria must be met in order to pass the challenge.
+    pub criteria: Vec<ChallengeCriterion>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChallengeCriterion {
+    /// Take at most the given number of steps.
+    MaxSteps { value: u32 },
+    /// Use at most the given amount of energy.
+    MaxEnergyUsed { value: u32 },
+    /// Write a script which is at most the given length (not counting comments
+    /// and unnecessary whitespace).
+    MaxCodeLen { value: usize },
+    /// Use at most the given number of Rhai operations.
+    MaxOperations { value: u64 },
+    /// Don't nest loops more than the given number of levels deep.
+    MaxLoopDepth { value: usize },
+    /// Don't use any of the given keywords or call any of the given functions
+    /// (see ScriptQuery::uses_keyword for the supported keywords).
+    ForbiddenWords { words: Vec<String> },
+    /// Don't use any telepads.
+    NoTeleporting,
+    /// Don't pick up any energy cells.
+    NoEnergyCellsCollected,
+    /// Say each of the given messages at some point.
+    SayAllMessages { messages: Vec<String> },
+}
+
+/// A declarative definition of (part of) a State. All fields are optional.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateDef {
+    pub player: Option<PlayerDef>,
+    pub goals: Vec<(u32, u32)>,
+    pub obstacles: Vec<ObstacleDef>,
+    pub energy_cells: Vec<(u32, u32)>,
+    pub buttons: Vec<ButtonDef>,
+    pub gates: Vec<GateDef>,
+    pub password_gates: Vec<PasswordGateDef>,
+    pub data_points: Vec<DataPointDef>,
+    pub input_terminals: Vec<InputTerminalDef>,
+    pub telepads: Vec<TelepadDef>,
+    pub enemies: Vec<EnemyDef>,
+    pub big_enemies: Vec<BigEnemyDef>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerDef {
+    pub pos: (u32, u32),
+    #[serde(default = "default_energy")]
+    pub energy: u32,
+    #[serde(default = "default_facing")]
+    pub facing: Orientation,
+}
+
+fn default_energy() -> u32 {
+    MAX_ENERGY
+}
+
+fn default_facing() -> Orientation {
+    Orientation::Right
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObstacleDef {
+    pub pos: (u32, u32),
+    /// Defaults to Rock.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ObstacleKind>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ButtonDef {
+    pub pos: (u32, u32),
+    /// Defaults to None (i.e. not connected to anything).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<ButtonConnection>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub additional_info: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GateDef {
+    pub pos: (u32, u32),
+    #[serde(default)]
+    pub open: bool,
+    pub variant: GateVariant,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub additional_info: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordGateDef {
+    pub pos: (u32, u32),
+    pub password: String,
+    #[serde(default)]
+    pub open: bool,
+    pub variant: GateVariant,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub additional_info: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataPointDef {
+    pub pos: (u32, u32),
+    pub data: TermData,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub additional_info: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputTerminalDef {
+    pub pos: (u32, u32),
+    /// Defaults to None (i.e. not connected to anything).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<ButtonConnection>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub additional_info: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelepadDef {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+    pub end_facing: Orientation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnemyDef {
+    pub pos: (u32, u32),
+    pub facing: Orientation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BigEnemyDef {
+    /// The position of the top left corner of the enemy.
+    pub pos: (u32, u32),
+    pub facing: OrientationWithDiagonals,
+}
+
+impl StateDef {
+    /// Returns a new StateDef which is the result of applying the given
+    /// variant to this one.
+    fn merge(&self, variant: &StateDef) -> StateDef {
+        fn concat<T: Clone>(a: &[T], b: &[T]) -> Vec<T> {
+            [a, b].concat()
+        }
+        StateDef {
+            player: variant.player.clone().or_else(|| self.player.clone()),
+            goals: concat(&self.goals, &variant.goals),
+            obstacles: concat(&self.obstacles, &variant.obstacles),
+            energy_cells: concat(&self.energy_cells, &variant.energy_cells),
+            buttons: concat(&self.buttons, &variant.buttons),
+            gates: concat(&self.gates, &variant.gates),
+            password_gates: concat(&self.password_gates, &variant.password_gates),
+            data_points: concat(&self.data_points, &variant.data_points),
+            input_terminals: concat(&self.input_terminals, &variant.input_terminals),
+            telepads: concat(&self.telepads, &variant.telepads),
+            enemies: concat(&self.enemies, &variant.enemies),
+            big_enemies: concat(&self.big_enemies, &variant.big_enemies),
+        }
+    }
+
+    /// Converts the definition to a State. Returns an error if the player is
+    /// missing.
+    fn to_state(&self) -> Result<State, String> {
+        let player = match &self.player {
+            Some(player) => player,
+            None => return Err(String::from("the player is missing from the initial state")),
+        };
+        let mut state = State::new();
+        state.player = Player::new(player.pos.0, player.pos.1, player.energy, player.facing);
+        state.goals = self.goals.iter().map(|&(x, y)| Goal::new(x, y)).collect();
+        state.obstacles = Rc::new(
+            self.obstacles
+                .iter()
+                .map(|o| {
+                    Obstacle::new_with_kind(
+                        o.pos.0,
+                        o.pos.1,
+                        o.kind.clone().unwrap_or(ObstacleKind::Rock),
+                    )
+                })
+                .collect(),
+        );
+        state.energy_cells = self
+            .energy_cells
+            .iter()
+            .map(|&(x, y)| EnergyCell::new(x, y))
+            .collect();
+        state.buttons = self
+            .buttons
+            .iter()
+            .map(|b| {
+                Button::new_with_info(
+                    b.pos.0,
+                    b.pos.1,
+                    b.connection.clone().unwrap_or(ButtonConnection::None),
+                    b.additional_info.clone(),
+                )
+            })
+            .collect();
+        state.gates = self
+            .gates
+            .iter()
+            .map(|g| {
+                Gate::new_with_info(
+                    g.pos.0,
+                    g.pos.1,
+                    g.open,
+                    g.variant.clone(),
+                    g.additional_info.clone(),
+                )
+            })
+            .collect();
+        state.password_gates = self
+            .password_gates
+            .iter()
+            .map(|g| {
+                PasswordGate::new_with_info(
+                    g.pos.0,
+                    g.pos.1,
+                    g.password.clone(),
+                    g.open,
+                    g.variant.clone(),
+                    g.additional_info.clone(),
+                )
+            })
+            .collect();
+        state.data_points = Rc::new(
+            self.data_points
+                .iter()
+                .map(|d| {
+                    DataPoint::new_with_info(
+                        d.pos.0,
+                        d.pos.1,
+                        d.data.clone(),
+                        d.additional_info.clone(),
+                    )
+                })
+                .collect(),
+        );
+        state.input_terminals = self
+            .input_terminals
+            .iter()
+            .map(|t| {
+                InputTerminal::new_with_info(
+                    t.pos.0,
+                    t.pos.1,
+                    t.connection.clone().unwrap_or(ButtonConnection::None),
+                    t.additional_info.clone(),
+                )
+            })
+            .collect();
+        state.telepads = self
+            .telepads
+            .iter()
+            .map(|t| Telepad::new(t.start, t.end, t.end_facing))
+            .collect();
+        state.enemies = self
+            .enemies
+            .iter()
+            .map(|e| Enemy::new(e.pos.0, e.pos.1, e.facing))
+            .collect();
+        state.big_enemies = self
+            .big_enemies
+            .iter()
+            .map(|e| BigEnemy::new(e.pos.0, e.pos.1, e.facing))
+            .collect();
+        Ok(state)
+    }
+}
+
+impl From<&State> for StateDef {
+    /// Creates a definition which describes the given state. Only the parts of
+    /// the state which can be configured for an initial state are included
+    /// (e.g. animation states are not).
+    fn from(state: &State) -> StateDef {
+        fn pos(pos: &crate::simulation::Pos) -> (u32, u32) {
+            (pos.x as u32, pos.y as u32)
+        }
+        StateDef {
+            player: Some(PlayerDef {
+                pos: pos(&state.player.pos),
+                energy: state.player.energy,
+                facing: state.player.facing,
+            }),
+            goals: state.goals.iter().map(|g| pos(&g.pos)).collect(),
+            obstacles: state
+                .obstacles
+                .iter()
+                .map(|o| ObstacleDef {
+                    pos: pos(&o.pos),
+                    kind: match o.kind {
+                        ObstacleKind::Rock => None,
+                        _ => Some(o.kind.clone()),
+                    },
+                })
+                .collect(),
+            energy_cells: state.energy_cells.iter().map(|e| pos(&e.pos)).collect(),
+            buttons: state
+                .buttons
+                .iter()
+                .map(|b| ButtonDef {
+                    pos: pos(&b.pos),
+                    connection: match b.connection {
+                        ButtonConnection::None => None,
+                        _ => Some(b.connection.clone()),
+                    },
+                    additional_info: b.additional_info.clone(),
+                })
+                .collect(),
+            gates: state
+                .gates
+                .iter()
+                .map(|g| GateDef {
+                    pos: pos(&g.pos),
+                    open: g.open,
+                    variant: g.variant.clone(),
+                    additional_info: g.additional_info.clone(),
+                })
+                .collect(),
+            password_gates: state
+                .password_gates
+                .iter()
+                .map(|g| PasswordGateDef {
+                    pos: pos(&g.pos),
+                    password: g.password.clone(),
+                    open: g.open,
+                    variant: g.variant.clone(),
+                    additional_info: g.additional_info.clone(),
+                })
+                .collect(),
+            data_points: state
+                .data_points
+                .iter()
+                .map(|d| DataPointDef {
+                    pos: pos(&d.pos),
+                    data: d.data.clone(),
+                    additional_info: d.additional_info.clone(),
+                })
+                .collect(),
+            input_terminals: state
+                .input_terminals
+                .iter()
+                .map(|t| InputTerminalDef {
+                    pos: pos(&t.pos),
+                    connection: match t.connection {
+                        ButtonConnection::None => None,
+                        _ => Some(t.connection.clone()),
+                    },
+                    additional_info: t.additional_info.clone(),
+                })
+                .collect(),
+            telepads: state
+                .telepads
+                .iter()
+                .map(|t| TelepadDef {
+                    start: pos(&t.start_pos),
+                    end: pos(&t.end_pos),
+                    end_facing: t.end_facing,
+                })
+                .collect(),
+            enemies: state
+                .enemies
+                .iter()
+                .map(|e| EnemyDef {
+                    pos: pos(&e.pos),
+                    facing: e.facing,
+                })
+                .collect(),
+            big_enemies: state
+                .big_enemies
+                .iter()
+                .map(|e| BigEnemyDef {
+                    pos: pos(&e.pos),
+                    facing: e.facing,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A level which is loaded from a LevelDef at runtime instead of being
+/// hard-coded in Rust.
+///
+/// Note that the Level trait returns &'static strs, so the strings in a
+/// DataLevel are leaked when it is created. This is fine since levels are
+/// expected to live for the entire lifetime of the program, but it means that
+/// DataLevels should not be created over and over again.
+pub struct DataLevel {
+    name: &'static str,
+    short_name: &'static str,
+    objective: &'static str,
+    initial_code: &'static str,
+    disabled_functions: &'static Vec<&'static str>,
+    disabled_features: Vec<LanguageFeature>,
+    engine_limits: EngineLimits,
+    objective_type: ObjectiveType,
+    input_checks: Vec<InputCheck>,
+    challenge: Option<(&'static str, Vec<ChallengeCriterion>)>,
+    width: u32,
+    height: u32,
+    initial_states: Vec<State>,
+}
+
+fn leak_str(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+impl DataLevel {
+    /// Creates a new DataLevel from the given definition. Returns an error if
+    /// the definition is invalid.
+    pub fn new(def: &LevelDef) -> Result<DataLevel, String> {
+        if def.short_name.is_empty() {
+            return Err(String::from("short_name cannot be empty"));
+        }
+        if def.width == 0 || def.height == 0 {
+            return Err(String::from("width and height must be greater than 0"));
+        }
+
+        let mut initial_states = if def.variants.is_empty() {
+            vec![def.initial_state.to_state()?]
+        } else {
+            def.variants
+                .iter()
+                .map(|variant| def.initial_state.merge(variant).to_state())
+                .collect::<Result<Vec<State>, String>>()?
+        };
+        if def.all_telepad_orientations {
+            if initial_states
+                .iter()
+                .any(|state| state.telepads.len() > MAX_TELEPADS)
+            {
+                return Err(format!(
+                    "all_telepad_orientations only supports up to {} telepads",
+                    MAX_TELEPADS
+                ));
+            }
+            initial_states = make_all_initial_states_for_telepads(initial_states);
+        }
+
+        if let ObjectiveType::PressButton { button } = def.objective_type {
+            if initial_states
+                .iter()
+                .any(|state| button >= state.buttons.len())
+            {
+                return Err(format!(
+                    "objective refers to button {} which does not exist",
+                    button
+                ));
+            }
+        }
+        if initial_states
+            .iter()
+            .any(|state| state.input_terminals.len() != def.input_checks.len())
+        {
+            return Err(String::from(
+                "there must be exactly one input check for each input terminal",
+            ));
+        }
+        for check in def.input_checks.iter() {
+            if let InputCheck::CopyDataPoint { data_point }
+            | InputCheck::SumDataPoint { data_point } = check
+            {
+                if initial_states
+                    .iter()
+                    .any(|state| *data_point >= state.data_points.len())
+                {
+                    return Err(format!(
+                        "input check refers to data point {} which does not exist",
+                        data_point
+                    ));
+                }
+            }
+        }
+        if let Some(challenge) = &def.challenge {
+            if challenge.criteria.is_empty() {
+                return Err(String::from("challenge must have at least one criterion"));
+            }
+        }
+
+        let disabled_functions: Vec<&'static str> =
+            def.disabled_functions.iter().map(|f| leak_str(f)).collect();
+        Ok(DataLevel {
+            name: leak_str(&def.name),
+            short_name: leak_str(&def.short_name),
+            objective: leak_str(&def.objective),
+            initial_code: leak_str(&def.initial_code),
+            disabled_functions: Box::leak(Box::new(disabled_functions)),
+            disabled_features: def.disabled_features.clone(),
+            engine_limits: def.engine_limits.clone(),
+            objective_type: def.objective_type.clone(),
+            input_checks: def.input_checks.clone(),
+            challenge: def
+                .challenge
+                .as_ref()
+                .map(|c| (leak_str(&c.description), c.criteria.clone())),
+            width: def.width,
+            height: def.height,
+            initial_states,
+        })
+    }
+
+    /// Parses a LevelDef from the given JSON and creates a DataLevel from it.
+    pub fn from_json(json: &str) -> Result<DataLevel, String> {
+        let def: LevelDef = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        DataLevel::new(&def)
+    }
+}
+
+impl Level for DataLevel {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn short_name(&self) -> &'static str {
+        self.short_name
+    }
+    fn objective(&self) -> &'static str {
+        self.objective
+    }
+    fn initial_code(&self) -> &'static str {
+        self.initial_code
+    }
+    fn initial_states(&self) -> Vec<State> {
+        self.initial_states.clone()
+    }
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn actors(&self) -> Vec<Box<dyn Actor>> {
+        // Every enemy is controlled by the default actor for its type. Note that
+        // all possible initial states are expected to have the same enemies.
+        let state = &self.initial_states[0];
+        let mut actors: Vec<Box<dyn Actor>> = vec![];
+        for i in 0..state.enemies.len() {
+            actors.push(Box::new(EvilRoverActor::new(i, self.bounds())));
+        }
+        for i in 0..state.big_enemies.len() {
+            actors.push(Box::new(BigEnemyActor::new(i, self.bounds())));
+        }
+        actors
+    }
+    fn check_win(&self, state: &State) -> Outcome {
+        match &self.objective_type {
+            ObjectiveType::ReachGoal => std_check_win(state),
+            ObjectiveType::PressButton { button } => {
+                if state.player.energy == 0 {
+                    Outcome::Failure(ERR_OUT_OF_ENERGY.to_string())
+                } else if state.buttons[*button].currently_pressed {
+                    Outcome::Success
+                } else {
+                    Outcome::Continue
+                }
+            }
+            ObjectiveType::SayMessage { message } => {
+                if state.player.energy == 0 {
+                    Outcome::Failure(ERR_OUT_OF_ENERGY.to_string())
+                } else if state.player.message == *message {
+                    Outcome::Success
+                } else {
+                    Outcome::Continue
+                }
+            }
+            ObjectiveType::WriteInputs => input_terminals_check_win(state),
+            ObjectiveType::NoObjective => no_objective_check_win(state),
+        }
+    }
+    fn check_input(&self, state: &State, terminal_index: usize, data: &TermData) -> bool {
+        match self.input_checks.get(terminal_index) {
+            Some(InputCheck::Equals { value }) => data == value,
+            Some(InputCheck::CopyDataPoint { data_point }) => {
+                *data == state.data_points[*data_point].data
+            }
+            Some(InputCheck::SumDataPoint { data_point }) => {
+                match &state.data_points[*data_point].data {
+                    TermData::Array(items) => {
+                        let mut sum = 0;
+                        for item in items {
+                            match item {
+                                TermData::Int(n) => sum += n,
+                                _ => return false,
+                            }
+                        }
+                        *data == TermData::Int(sum)
+                    }
+                    _ => false,
+                }
+            }
+            None => false,
+        }
+    }
+    fn disabled_functions(&self) -> &'static Vec<&'static str> {
+        self.disabled_functions
+    }
+    fn disabled_features(&self) -> Vec<LanguageFeature> {
+        self.disabled_features.clone()
+    }
+    fn engine_limits(&self) -> EngineLimits {
+        self.engine_limits.clone()
+    }
+    fn challenge(&self) -> Option<&'static str> {
+        self.challenge.as_ref().map(|(description, _)| *description)
+    }
+    fn check_challenge(&self, states: &Vec<State>, script: &str, stats: &ScriptStats) -> bool {
+        match &self.challenge {
+            Some((_, criteria)) => criteria
+                .iter()
+                .all(|criterion| check_criterion(criterion, states, script, stats)),
+            None => false,
+        }
+    }
+}
+
+fn check_criterion(
+    criterion: &ChallengeCriterion,
+    states: &[State],
+    script: &str,
+    stats: &ScriptStats,
+) -> bool {
+    match criterion {
+        ChallengeCriterion::MaxSteps { value } => stats.time_taken <= *value,
+        ChallengeCriterion::MaxEnergyUsed { value } => stats.energy_used <= *value,
+        ChallengeCriterion::MaxCodeLen { value } => stats.code_len <= *value,
+        ChallengeCriterion::MaxOperations { value } => stats.operations <= *value,
+        ChallengeCriterion::MaxLoopDepth { value } => stats.max_loop_depth <= *value,
+        ChallengeCriterion::ForbiddenWords { words } => match ScriptQuery::new(script) {
+            Some(query) => !words
+                .iter()
+                .any(|word| query.uses_keyword(word) || query.calls(word)),
+            // Otherwise, challenge is considered not passed.
+            None => false,
+        },
+        ChallengeCriterion::NoTeleporting => !states
+            .iter()
+            .any(|state| matches!(state.player.anim_state, PlayerAnimState::Teleporting(_))),
+        ChallengeCriterion::NoEnergyCellsCollected => match states.last() {
+            Some(state) => !state.energy_cells.iter().any(|cell| cell.collected),
+            None => false,
+        },
+        ChallengeCriterion::SayAllMessages { messages } => {
+            let mut remaining: HashSet<&String> = messages.iter().collect();
+            for state in states {
+                remaining.remove(&state.player.message);
+            }
+            remaining.is_empty()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::LEVELS;
+    use crate::simulation::Pos;
+
+    /// Asserts that the given DataLevel is equivalent to the built-in level
+    /// with the same short name, both in terms of the level data and the
+    /// results of running the given scripts.
+    fn assert_equivalent(data_level: &'static DataLevel, scripts: &[&str]) {
+        let level = LEVELS.get(data_level.short_name()).unwrap().as_ref();
+        assert_eq!(data_level.name(), level.name());
+        assert_eq!(data_level.objective(), level.objective());
+        assert_eq!(data_level.width(), level.width());
+        assert_eq!(data_level.height(), level.height());
+        assert_eq!(data_level.initial_code(), level.initial_code());
+        assert_eq!(data_level.initial_states(), level.initial_states());
+        assert_eq!(data_level.disabled_functions(), level.disabled_functions());
+        assert_eq!(data_level.challenge(), level.challenge());
+        assert_eq!(data_level.actors().len(), level.actors().len());
+
+        let mut game = crate::Game::new();
+        for script in scripts {
+            let expected = game
+                .run_player_script_with_all_funcs_unlocked(level, script.to_string())
+                .unwrap();
+            let actual = game
+                .run_player_script_with_all_funcs_unlocked(data_level, script.to_string())
+                .unwrap();
+            assert_eq!(actual.outcome, expected.outcome, "script: {}", script);
+            assert_eq!(
+                actual.passes_challenge, expected.passes_challenge,
+                "script: {}",
+                script
+            );
+        }
+    }
+
+    fn load(json: &str) -> &'static DataLevel {
+        Box::leak(Box::new(DataLevel::from_json(json).unwrap()))
+    }
+
+    #[test]
+    fn movement() {
+        let level = load(include_str!("../../data/levels/movement.json"));
+        assert_equivalent(
+            level,
+            &[
+                level.initial_code(),
+                "move_forward(3); turn_right(); move_forward(3);",
+            ],
+        );
+    }
+
+    #[test]
+    fn asteroid_strike() {
+        let level = load(include_str!("../../data/levels/asteroid_strike.json"));
+        assert_equivalent(
+            level,
+            &[
+                level.initial_code(),
+                r#"move_forward(2);
+                let safe_direction = read_data();
+                if safe_direction == "left" {
+                    turn_left();
+                    move_forward(3);
+                }
+                if safe_direction == "right" {
+                    turn_right();
+                    move_forward(3);
+                }"#,
+                r#"move_forward(2);
+                turn_left();
+                move_forward(3);"#,
+            ],
+        );
+        assert_eq!(level.asteroid_warnings().len(), 2);
+    }
+
+    #[test]
+    fn buttons_part_one() {
+        let level = load(include_str!("../../data/levels/buttons_part_one.json"));
+        assert_equivalent(
+            level,
+            &[
+                level.initial_code(),
+                "move_forward(2); press_button();",
+                "press_button();",
+            ],
+        );
+    }
+
+    #[test]
+    fn enemies_with_telepad() {
+        let level = load(include_str!("../../data/levels/enemies_with_telepad.json"));
+        assert_equivalent(
+            level,
+            &[
+                level.initial_code(),
+                "move_forward(4);",
+                "move_forward(2); turn_left(); move_forward(1); turn_left(); move_forward(4);",
+            ],
+        );
+    }
+
+    #[test]
+    fn loops_part_two() {
+        let level = load(include_str!("../../data/levels/loops_part_two.json"));
+        assert_equivalent(
+            level,
+            &[
+                level.initial_code(),
+                "loop { move_forward(2); turn_right(); move_forward(2); turn_left(); }",
+                "move_forward(2); turn_right(); move_forward(2); turn_left();
+                move_forward(2); turn_right(); move_forward(2); turn_left();",
+            ],
+        );
+    }
+
+    #[test]
+    fn all_built_in_states_are_expressible() {
+        for level in LEVELS.values() {
+            for state in level.initial_states() {
+                let def = StateDef::from(&state);
+                assert_eq!(
+                    def.to_state().unwrap(),
+                    state,
+                    "level: {}",
+                    level.short_name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn custom_grid_size() {
+        let level = load(
+            r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "reach_goal" },
+            "width": 20,
+            "height": 10,
+            "initial_state": {
+                "player": { "pos": [0, 0] },
+                "goals": [[19, 9]]
+            }
+        }"#,
+        );
+        assert_eq!(level.width(), 20);
+        assert_eq!(level.height(), 10);
+        assert!(crate::levels::validation::validate_level(level).is_empty());
+
+        // The rover should be able to move past the default grid size, but not
+        // past the edge of the grid for this level.
+        let mut game = crate::Game::new();
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from("move_forward(25); turn_right(); move_forward(9);"),
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+        assert_eq!(result.states.last().unwrap().player.pos, Pos::new(19, 9));
+    }
+
+    #[test]
+    fn disabled_features() {
+        let level = load(
+            r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "reach_goal" },
+            "disabled_features": ["loop", "while_loop", "function_definitions", "arrays"],
+            "initial_state": {
+                "player": { "pos": [0, 0] },
+                "goals": [[3, 0]]
+            }
+        }"#,
+        );
+        let mut game = crate::Game::new();
+        let cases = [
+            (
+                "loop {\n  move_forward(1);\n}",
+                "Error: The loop keyword is disabled for this level.",
+                1,
+            ),
+            (
+                "let x = 0;\nwhile x < 3 {\n  move_forward(1);\n  x += 1;\n}",
+                "Error: While loops are disabled for this level.",
+                2,
+            ),
+            (
+                "fn forward() {\n  move_forward(3);\n}\nforward();",
+                "Error: Defining your own functions is disabled for this level.",
+                1,
+            ),
+            (
+                "let a = [1, 2];",
+                "Error: Arrays are disabled for this level.",
+                1,
+            ),
+            (
+                "let a = \"abc\";\nmove_forward(a[0]);",
+                "Error: Arrays are disabled for this level.",
+                2,
+            ),
+        ];
+        for (script, message, line) in cases {
+            let err = game
+                .run_player_script_with_all_funcs_unlocked(level, script.to_string())
+                .unwrap_err();
+            assert_eq!(err.message, message, "script: {}", script);
+            assert_eq!(err.line, Some(line), "script: {}", script);
+        }
+
+        // Features which are not disabled still work.
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from("for i in 0..3 {\n  move_forward(1);\n}"),
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+    }
+
+    #[test]
+    fn structured_data() {
+        let level = load(
+            r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "no_objective" },
+            "initial_state": {
+                "player": { "pos": [0, 0] },
+                "data_points": [
+                    { "pos": [1, 0], "data": { "spaces": 2, "safe": true, "path": ["a", "b"] } }
+                ]
+            }
+        }"#,
+        );
+        let mut game = crate::Game::new();
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from(
+                    r#"let data = read_data();
+                    if data.safe {
+                        say(`${data.spaces + 1} ${data.path[1]}`);
+                    }"#,
+                ),
+            )
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "3 b");
+    }
+
+    #[test]
+    fn input_terminals() {
+        let level = load(include_str!("../../data/levels/input_terminals.json"));
+        assert_eq!(level.initial_states().len(), 2);
+        assert_eq!(level.initial_states()[0].input_terminals.len(), 1);
+        assert!(crate::levels::validation::validate_level(level).is_empty());
+
+        let mut game = crate::Game::new();
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from(
+                    r#"let sum = 0;
+                    for n in read_data() {
+                        sum += n;
+                    }
+                    write_data(sum);"#,
+                ),
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+
+        // Hard-coding the answer only works for one of the variants.
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(level, String::from("write_data(16);"))
+            .unwrap();
+        assert_ne!(result.outcome, Outcome::Success);
+
+        // The other kinds of input checks.
+        let level = load(
+            r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "write_inputs" },
+            "input_checks": [
+                { "type": "equals", "value": "hello" },
+                { "type": "copy_data_point", "data_point": 0 }
+            ],
+            "initial_state": {
+                "player": { "pos": [1, 1] },
+                "data_points": [{ "pos": [1, 0], "data": [1, "two"] }],
+                "input_terminals": [{ "pos": [0, 1] }, { "pos": [2, 1] }]
+            }
+        }"#,
+        );
+        let state = &level.initial_states()[0];
+        assert!(level.check_input(state, 0, &TermData::from("hello")));
+        assert!(!level.check_input(state, 0, &TermData::from("world")));
+        assert!(level.check_input(state, 1, &state.data_points[0].data));
+        assert!(!level.check_input(state, 1, &TermData::Int(1)));
+        assert!(!level.check_input(state, 2, &TermData::Int(1)));
+
+        let json = r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "write_inputs" },
+            "initial_state": {
+                "player": { "pos": [0, 0] },
+                "input_terminals": [{ "pos": [1, 0] }]
+            }
+        }"#;
+        assert_eq!(
+            DataLevel::from_json(json).err(),
+            Some(String::from(
+                "there must be exactly one input check for each input terminal"
+            ))
+        );
+    }
+
+    #[test]
+    fn engine_limits() {
+        let level = load(
+            r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "no_objective" },
+            "engine_limits": { "max_call_levels": 4, "max_string_size": 10 },
+            "initial_state": { "player": { "pos": [0, 0] } }
+        }"#,
+        );
+        assert_eq!(
+            level.engine_limits(),
+            EngineLimits {
+                max_call_levels: 4,
+                max_string_size: 10,
+                ..EngineLimits::default()
+            }
+        );
+
+        let mut game = crate::Game::new();
+        let err = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from(
+                    "fn countdown(n) {\n  if n > 0 {\n    countdown(n - 1);\n  }\n}\ncountdown(10);",
+                ),
+            )
+            .unwrap_err();
+        assert!(
+            err.message
+                .starts_with("Error: Too many nested function calls. Functions can only call other functions up to 4 levels deep."),
+            "{}",
+            err.message
+        );
+
+        let err = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from("let s = \"abcdef\";\ns += \"ghijkl\";"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Error: String is too long. Strings can have at most 10 characters in this level."
+        );
+    }
+
+    #[test]
+    fn invalid_defs() {
+        let json = r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "press_button", "button": 1 },
+            "initial_state": {
+                "player": { "pos": [0, 0] },
+                "buttons": [{ "pos": [1, 0] }]
+            }
+        }"#;
+        assert_eq!(
+            DataLevel::from_json(json).err(),
+            Some(String::from(
+                "objective refers to button 1 which does not exist"
+            ))
+        );
+
+        let json = r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "reach_goal" },
+            "initial_state": { "goals": [[1, 0]] }
+        }"#;
+        assert_eq!(
+            DataLevel::from_json(json).err(),
+            Some(String::from("the player is missing from the initial state"))
+        );
+
+        let json = r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "reach_goal" },
+            "width": 0,
+            "initial_state": { "player": { "pos": [0, 0] } }
+        }"#;
+        assert_eq!(
+            DataLevel::from_json(json).err(),
+            Some(String::from("width and height must be greater than 0"))
+        );
+
+        // Unknown fields should result in an error.
+        let json = r#"{
+            "name": "Test",
+            "short_name": "test",
+            "objective": "",
+            "initial_code": "",
+            "objective_type": { "type": "reach_goal" },
+            "initial_state": { "player": { "pos": [0, 0] }, "goal": [[1, 0]] }
+        }"#;
+        assert!(DataLevel::from_json(json).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{Level, Outcome};
 use crate::{
     constants::ERR_OUT_OF_ENERGY,
@@ -42,7 +44,7 @@ impl Level for DataPointsPartOne {
     fn initial_states(&self) -> Vec<State> {
         let mut state = State::new();
         state.player = Player::new(0, 3, 10, Orientation::Right);
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 2),
             Obstacle::new(1, 2),
             Obstacle::new(2, 2),
@@ -57,13 +59,13 @@ impl Level for DataPointsPartOne {
             Obstacle::new(4, 4),
             Obstacle::new(5, 4),
             Obstacle::new(6, 4),
-        ];
-        state.data_points = vec![DataPoint::new_with_info(
+        ]);
+        state.data_points = Rc::new(vec![DataPoint::new_with_info(
             6,
             3,
             HUMMUS_RECIPE.into(),
             "This data point contains the secret hummus recipe as a string.".into(),
-        )];
+        )]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -80,6 +82,13 @@ impl Level for DataPointsPartOne {
             Outcome::Continue
         }
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(5);
+say(read_data());
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,5 @@
 use super::{std_check_win, Level, Outcome};
-use crate::actors::{Bounds, EvilRoverActor};
+use crate::actors::EvilRoverActor;
 use crate::script_runner::ScriptStats;
 use crate::simulation::{Actor, DataPoint, Enemy, EnergyCell, ObstacleKind, Orientation};
 use crate::simulation::{Goal, Obstacle, Player, State};
@@ -125,8 +125,8 @@ impl Level for EnemiesAndAsteroids {
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
         vec![
-            Box::new(EvilRoverActor::new(0, Bounds::default())),
-            Box::new(EvilRoverActor::new(1, Bounds::default())),
+            Box::new(EvilRoverActor::new(0, self.bounds())),
+            Box::new(EvilRoverActor::new(1, self.bounds())),
         ]
     }
     fn check_win(&self, state: &State) -> Outcome {
@@ -138,6 +138,59 @@ impl Level for EnemiesAndAsteroids {
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.time_taken <= 22
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"let safe_direction = read_data();
+
+if safe_direction == "right" {
+  move_forward(6);
+  turn_right();
+  move_forward(6);
+  turn_right();
+  move_forward(6);
+  turn_right();
+  move_forward(4);
+}
+if safe_direction == "left" {
+  move_forward(6);
+  turn_left();
+  move_forward(5);
+  turn_left();
+  move_forward(6);
+  turn_left();
+  move_forward(3);
+}
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"let safe_direction = read_data();
+
+if safe_direction == "right" {
+  say("waiting");
+  move_forward(4);
+  turn_left();
+  move_forward(5);
+  turn_left();
+  move_forward(4);
+  turn_left();
+  move_forward(3);
+}
+if safe_direction == "left" {
+  say("waiting");
+  say("waiting");
+  move_forward(4);
+  turn_right();
+  move_forward(2);
+  turn_right();
+  move_forward(4);
+  turn_right();
+  move_forward(2);
+}
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
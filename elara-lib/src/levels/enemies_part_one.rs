@@ -1,5 +1,7 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
-use crate::actors::{Bounds, EvilRoverActor};
+use crate::actors::EvilRoverActor;
 use crate::simulation::{Actor, Enemy, Goal, Obstacle, Orientation, Player, State};
 
 #[derive(Copy, Clone)]
@@ -29,7 +31,7 @@ move_forward(3);
         state.energy_cells = vec![];
         state.goals = vec![Goal::new(8, 5)];
         state.enemies = vec![Enemy::new(8, 6, Orientation::Up)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(3, 3),
             Obstacle::new(3, 4),
             Obstacle::new(4, 3),
@@ -40,11 +42,11 @@ move_forward(3);
             Obstacle::new(6, 4),
             Obstacle::new(7, 3),
             Obstacle::new(7, 4),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
-        vec![Box::new(EvilRoverActor::new(0, Bounds::default()))]
+        vec![Box::new(EvilRoverActor::new(0, self.bounds()))]
     }
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
@@ -60,6 +62,32 @@ move_forward(3);
     ) -> bool {
         stats.time_taken <= 18
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"turn_right();
+move_forward(6);
+turn_left();
+move_forward(3);
+turn_left();
+move_forward(6);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"turn_right();
+move_backward(2);
+turn_left();
+move_forward(4);
+say("waiting");
+move_backward(1);
+turn_right();
+move_backward(1);
+say("waiting");
+move_forward(3);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
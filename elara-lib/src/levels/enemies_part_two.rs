@@ -1,5 +1,7 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
-use crate::actors::{Bounds, EvilRoverActor};
+use crate::actors::EvilRoverActor;
 use crate::simulation::{Actor, Enemy, EnergyCell, Goal, Obstacle, Orientation, Player, State};
 
 #[derive(Copy, Clone)]
@@ -28,7 +30,7 @@ impl Level for EnemiesPartTwo {
             Enemy::new(0, 7, Orientation::Up),
             Enemy::new(6, 0, Orientation::Left),
         ];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 4),
             Obstacle::new(1, 2),
             Obstacle::new(1, 3),
@@ -65,13 +67,13 @@ impl Level for EnemiesPartTwo {
             Obstacle::new(9, 5),
             Obstacle::new(9, 6),
             Obstacle::new(9, 7),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
         vec![
-            Box::new(EvilRoverActor::new(0, Bounds::default())),
-            Box::new(EvilRoverActor::new(1, Bounds::default())),
+            Box::new(EvilRoverActor::new(0, self.bounds())),
+            Box::new(EvilRoverActor::new(1, self.bounds())),
         ]
     }
     fn check_win(&self, state: &State) -> Outcome {
@@ -88,6 +90,33 @@ impl Level for EnemiesPartTwo {
     ) -> bool {
         stats.energy_used <= 7
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(2);
+turn_right();
+move_forward(2);
+turn_left();
+move_forward(2);
+turn_right();
+move_forward(2);
+turn_left();
+move_forward(2);
+turn_left();
+move_forward(5);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"say("waiting");
+move_forward(4);
+turn_left();
+move_forward(1);
+turn_right();
+move_forward(2);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
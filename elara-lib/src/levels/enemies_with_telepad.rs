@@ -1,6 +1,5 @@
 use super::{make_all_initial_states_for_telepads, std_check_win, Level, Outcome};
-use crate::actors::{Bounds, EvilRoverActor};
-use crate::constants::{HEIGHT, WIDTH};
+use crate::actors::EvilRoverActor;
 use crate::simulation::{
     Actor, Enemy, EnergyCell, Goal, Obstacle, Orientation, Player, State, Telepad,
 };
@@ -68,15 +67,7 @@ impl Level for EnemiesWithTelepad {
         make_all_initial_states_for_telepads(vec![base_state])
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
-        vec![Box::new(EvilRoverActor::new(
-            0,
-            Bounds {
-                min_x: 0,
-                max_x: (WIDTH - 1) as i32,
-                min_y: 0,
-                max_y: (HEIGHT - 1) as i32,
-            },
-        ))]
+        vec![Box::new(EvilRoverActor::new(0, self.bounds()))]
     }
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
@@ -92,6 +83,39 @@ impl Level for EnemiesWithTelepad {
     ) -> bool {
         !states.is_empty() && !states.last().unwrap().energy_cells[0].collected
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(2);
+turn_right();
+move_forward(1);
+if get_orientation() == "left" {
+  turn_right();
+}
+if get_orientation() == "right" {
+  turn_left();
+}
+if get_orientation() == "down" {
+  turn_left();
+  turn_left();
+}
+move_forward(3);
+turn_right();
+move_forward(7);
+turn_right();
+move_forward(3);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"turn_right();
+move_forward(4);
+move_backward(4);
+turn_left();
+move_forward(4);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::{
     script_runner::ScriptStats,
@@ -34,7 +36,7 @@ move_forward(4);
             collected: false,
         }];
         state.goals = vec![Goal::new(4, 4)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             // Obstacles enclose the player, goal, and energy cells with a few different
             // branching paths.
             Obstacle::new(1, 1),
@@ -58,7 +60,7 @@ move_forward(4);
             Obstacle::new(1, 5),
             Obstacle::new(1, 6),
             Obstacle::new(1, 7),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -73,6 +75,26 @@ move_forward(4);
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.time_taken <= 12
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(5);
+turn_left();
+turn_left();
+move_forward(1);
+turn_right();
+move_forward(4);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"move_forward(5);
+move_backward(1);
+turn_left();
+move_forward(4);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{
     Actor, DataPoint, GateVariant, Goal, Obstacle, Orientation, PasswordGate, Player, Pos, State,
@@ -34,7 +36,7 @@ let password = read_data();
         state.goals = vec![Goal {
             pos: Pos { x: 5, y: 5 },
         }];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(4, 0),
             Obstacle::new(4, 2),
             Obstacle::new(4, 3),
@@ -42,7 +44,7 @@ let password = read_data();
             Obstacle::new(6, 0),
             Obstacle::new(6, 1),
             Obstacle::new(6, 2),
-        ];
+        ]);
         state.password_gates = vec![PasswordGate::new_with_info(
             5,
             3,
@@ -51,12 +53,12 @@ let password = read_data();
             GateVariant::NESW,
             "The password for this gate is stored in the nearby data point.".into(),
         )];
-        state.data_points = vec![DataPoint::new_with_info(
+        state.data_points = Rc::new(vec![DataPoint::new_with_info(
             4,
             1,
             "turing".into(),
             "This data point contains the password you need.".into(),
-        )];
+        )]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -65,6 +67,16 @@ let password = read_data();
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(1);
+let password = read_data();
+move_forward(1);
+say(password);
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
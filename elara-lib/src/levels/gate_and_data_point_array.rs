@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{
     Actor, DataPoint, GateVariant, Goal, Obstacle, Orientation, PasswordGate, Player, State,
@@ -31,7 +33,7 @@ let array = read_data();
         let mut state = State::new();
         state.player = Player::new(10, 6, 10, Orientation::Left);
         state.goals = vec![Goal::new(5, 6)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(7, 5),
             Obstacle::new(7, 7),
             Obstacle::new(8, 5),
@@ -42,7 +44,7 @@ let array = read_data();
             Obstacle::new(10, 7),
             Obstacle::new(11, 5),
             Obstacle::new(11, 7),
-        ];
+        ]);
         state.password_gates = vec![PasswordGate::new_with_info(
             7,
             6,
@@ -51,7 +53,7 @@ let array = read_data();
             GateVariant::NESW,
             "The nearby data point holds an array. The password for this gate is stored at index `2` of the array.".into(),
         )];
-        state.data_points = vec![DataPoint::new_with_info(
+        state.data_points = Rc::new(vec![DataPoint::new_with_info(
             11,
             6,
             vec![
@@ -62,7 +64,7 @@ let array = read_data();
             ]
             .into(),
             "This data point holds an array. The password is stored at index `2` of the array.".into(),
-        )];
+        )]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -71,6 +73,15 @@ let array = read_data();
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"let array = read_data();
+move_forward(2);
+say(array[2]);
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{
     Actor, DataPoint, GateVariant, Goal, Obstacle, Orientation, PasswordGate, Player, State,
@@ -31,14 +33,13 @@ say(password);
         let mut state = State::new();
         state.player = Player::new(1, 0, 20, Orientation::Down);
         state.goals = vec![Goal::new(4, 1)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(2, 0),
             Obstacle::new(2, 2),
             Obstacle::new(2, 3),
             Obstacle::new(0, 3),
             Obstacle::new(1, 3),
-            Obstacle::new(2, 3),
-        ];
+        ]);
         state.password_gates = vec![PasswordGate::new(
             2,
             1,
@@ -46,11 +47,11 @@ say(password);
             false,
             GateVariant::NWSE,
         )];
-        state.data_points = vec![
+        state.data_points = Rc::new(vec![
             DataPoint::new(0, 0, "carver".into()),
             DataPoint::new(0, 1, "curie".into()),
             DataPoint::new(0, 2, "vaughan".into()),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -59,6 +60,19 @@ say(password);
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(2);
+let password = read_data();
+turn_left();
+turn_left();
+move_forward(1);
+say(password);
+turn_right();
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,8 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::{
-    actors::{Bounds, EvilRoverActor},
-    constants::{HEIGHT, WIDTH},
+    actors::EvilRoverActor,
     simulation::{
         Actor, DataPoint, Enemy, GateVariant, Goal, Obstacle, Orientation, PasswordGate, Player,
         Pos, State,
@@ -35,7 +36,7 @@ impl Level for GateAndDataPointPartTwo {
         state.goals = vec![Goal {
             pos: Pos { x: 9, y: 4 },
         }];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(8, 0),
             Obstacle::new(8, 1),
             Obstacle::new(8, 2),
@@ -48,7 +49,7 @@ impl Level for GateAndDataPointPartTwo {
             Obstacle::new(10, 2),
             Obstacle::new(11, 0),
             Obstacle::new(11, 2),
-        ];
+        ]);
         state.password_gates = vec![PasswordGate::new_with_info(
             9,
             2,
@@ -57,29 +58,32 @@ impl Level for GateAndDataPointPartTwo {
             GateVariant::NWSE,
             "The password for this gate is stored in the nearby data point.".into(),
         )];
-        state.data_points = vec![DataPoint::new_with_info(
+        state.data_points = Rc::new(vec![DataPoint::new_with_info(
             10,
             0,
             "hopper".into(),
             "This data point contains the password you need.".into(),
-        )];
+        )]);
         state.enemies = vec![Enemy::new(5, 0, Orientation::Right)];
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
-        vec![Box::new(EvilRoverActor::new(
-            0,
-            Bounds {
-                min_x: 0,
-                max_x: (WIDTH - 1) as i32,
-                min_y: 0,
-                max_y: (HEIGHT - 1) as i32,
-            },
-        ))]
+        vec![Box::new(EvilRoverActor::new(0, self.bounds()))]
     }
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(1);
+let password = read_data();
+move_forward(1);
+say(password);
+turn_left();
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::{
     script_runner::ScriptStats,
@@ -30,7 +32,7 @@ move_forward(2);
         let mut state = State::new();
         state.player = Player::new(0, 3, 10, Orientation::Right);
         state.goals = vec![Goal::new(7, 3)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(3, 0),
             Obstacle::new(3, 1),
             Obstacle::new(3, 2),
@@ -38,7 +40,7 @@ move_forward(2);
             Obstacle::new(3, 5),
             Obstacle::new(3, 6),
             Obstacle::new(3, 7),
-        ];
+        ]);
         state.password_gates = vec![PasswordGate::new_with_info(
             3,
             3,
@@ -61,6 +63,23 @@ move_forward(2);
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.code_len <= 40
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(2);
+say("lovelace");
+move_forward(5);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"loop {
+  move_forward(2);
+  say("lovelace");
+}
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
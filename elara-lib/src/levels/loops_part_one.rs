@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{Actor, EnergyCell, Goal, Obstacle, Orientation, Player, State};
 
@@ -29,7 +31,7 @@ impl Level for LoopsPartOne {
         state.player = Player::new(0, 7, 5, Orientation::Right);
         state.energy_cells = vec![EnergyCell::new(3, 5)];
         state.goals = vec![Goal::new(8, 0)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 6),
             Obstacle::new(0, 5),
             Obstacle::new(1, 5),
@@ -58,7 +60,7 @@ impl Level for LoopsPartOne {
             Obstacle::new(8, 1),
             Obstacle::new(9, 1),
             Obstacle::new(9, 0),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -67,6 +69,17 @@ impl Level for LoopsPartOne {
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"loop {
+  move_forward(1);
+  turn_left();
+  move_forward(1);
+  turn_right();
+}
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
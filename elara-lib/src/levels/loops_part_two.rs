@@ -1,9 +1,9 @@
-use rhai::Engine;
+use std::rc::Rc;
 
 use super::{std_check_win, Level, Outcome};
 use crate::{
-    actors::{Bounds, EvilRoverActor},
-    constants::{HEIGHT, WIDTH},
+    actors::EvilRoverActor,
+    script_query::ScriptQuery,
     script_runner::ScriptStats,
     simulation::{Actor, Enemy, EnergyCell, Goal, Obstacle, Orientation, Player, Pos, State},
 };
@@ -35,7 +35,7 @@ impl Level for LoopsPartTwo {
         state.goals = vec![Goal {
             pos: Pos { x: 10, y: 5 },
         }];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 1),
             Obstacle::new(1, 1),
             Obstacle::new(1, 2),
@@ -67,20 +67,12 @@ impl Level for LoopsPartTwo {
             Obstacle::new(11, 3),
             Obstacle::new(11, 4),
             Obstacle::new(11, 5),
-        ];
+        ]);
         state.enemies = vec![Enemy::new(12, 2, Orientation::Left)];
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
-        vec![Box::new(EvilRoverActor::new(
-            0,
-            Bounds {
-                min_x: 0,
-                max_x: (WIDTH - 1) as i32,
-                min_y: 0,
-                max_y: (HEIGHT - 1) as i32,
-            },
-        ))]
+        vec![Box::new(EvilRoverActor::new(0, self.bounds()))]
     }
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
@@ -92,14 +84,37 @@ impl Level for LoopsPartTwo {
         if stats.code_len > 84 {
             return false;
         }
-        // Script cannot contain the key words "loop", "for", "while", or "do".
-        // Strip the comments first, then use a regex to check for keywords.
-        if let Ok(script) = Engine::new().compact_script(script) {
-            let loop_regex = regex::Regex::new(r"\b(loop|for|while|do)\b").unwrap();
-            return !loop_regex.is_match(script.as_str());
+        // Script cannot use any kind of loop.
+        match ScriptQuery::new(script) {
+            Some(query) => !query.uses_loop(),
+            // Otherwise, challenge is considered not passed.
+            None => false,
         }
-        // Otherwise, challenge is considered not passed.
-        false
+    }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"loop {
+  move_forward(2);
+  turn_right();
+  move_forward(1);
+  turn_left();
+}
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"fn main() {
+  move_forward(2);
+  turn_right();
+  move_forward(1);
+  turn_left();
+  main();
+}
+
+main();
+"#,
+        )
     }
 }
 
@@ -3,6 +3,7 @@ mod asteroid_strike_part_two;
 mod big_enemy;
 mod button_and_gate;
 mod buttons_part_one;
+pub mod data_level;
 mod data_point_demo;
 mod data_points_part_one;
 mod enemies_and_asteroids;
@@ -27,16 +28,19 @@ mod server_room;
 mod telepad_part_one;
 mod telepad_part_two;
 mod telepads_and_while_loop;
+pub mod validation;
 mod variables_intro;
 
 use crate::actors::{Bounds, BIG_ENEMY_SIZE};
-use crate::constants::{ERR_DESTROYED_BY_ENEMY, ERR_OUT_OF_ENERGY, HEIGHT, WIDTH};
-use crate::script_runner::ScriptStats;
+use crate::constants::{ERR_DESTROYED_BY_ENEMY, ERR_OUT_OF_ENERGY, HEIGHT, MAX_TELEPADS, WIDTH};
+use crate::script_runner::{EngineLimits, LanguageFeature, ScriptStats};
 use crate::simulation::State;
-use crate::simulation::{Actor, AsteroidWarning, ObstacleKind, Orientation, Pos};
+use crate::simulation::{Actor, AsteroidWarning, ObstacleKind, Orientation, Pos, TermData};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum Outcome {
     // Continue running the code, but this is considered a failure if there is no code left to run.
     Continue,
@@ -60,23 +64,55 @@ pub trait Level {
     fn initial_states(&self) -> Vec<State>;
     fn actors(&self) -> Vec<Box<dyn Actor>>;
     fn check_win(&self, state: &State) -> Outcome;
+    /// Returns true if the given data is correct for the input terminal with
+    /// the given index. state is the state before the data was written. Levels
+    /// with input terminals should override this.
+    fn check_input(&self, _state: &State, _terminal_index: usize, _data: &TermData) -> bool {
+        false
+    }
+    /// The width of the grid for this level.
+    fn width(&self) -> u32 {
+        WIDTH
+    }
+    /// The height of the grid for this level.
+    fn height(&self) -> u32 {
+        HEIGHT
+    }
+    /// The area that the rover and enemies are allowed to move in. Defaults to
+    /// the entire grid.
     fn bounds(&self) -> Bounds {
-        Bounds {
-            min_x: 0,
-            max_x: (WIDTH - 1) as i32,
-            min_y: 0,
-            max_y: (HEIGHT - 1) as i32,
-        }
+        Bounds::for_grid(self.width(), self.height())
     }
     fn disabled_functions(&self) -> &'static Vec<&'static str> {
         &EMPTY_VEC
     }
+    /// Language features which can't be used in this level (e.g. to focus on
+    /// one concept at a time).
+    fn disabled_features(&self) -> Vec<LanguageFeature> {
+        vec![]
+    }
+    /// Limits on the resources scripts are allowed to use in this level.
+    fn engine_limits(&self) -> EngineLimits {
+        EngineLimits::default()
+    }
     fn challenge(&self) -> Option<&'static str> {
         None
     }
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, _stats: &ScriptStats) -> bool {
         false
     }
+    /// Returns one or more scripts which beat the level in all possible initial
+    /// states. Every level with an objective should have at least one. These
+    /// are used to automatically test that the level can be beaten (e.g. after
+    /// changes to actors or std_check_win).
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![]
+    }
+    /// Returns a script which beats the level and passes the challenge in all
+    /// possible initial states. Every level with a challenge should have one.
+    fn challenge_solution(&self) -> Option<&'static str> {
+        None
+    }
 
     /// Returns asteroid warnings based on the given possible initial states
     /// for this level.
@@ -95,7 +131,7 @@ pub trait Level {
 
         // Then remove any asteroids with a position equal to a warning's position.
         let mut filtered_state = self.initial_states()[0].clone();
-        filtered_state.obstacles.retain(|x| {
+        Rc::make_mut(&mut filtered_state.obstacles).retain(|x| {
             !warnings
                 .iter()
                 .any(|warning| warning.pos == x.pos && x.kind == ObstacleKind::Asteroid)
@@ -262,17 +298,43 @@ pub fn no_objective_check_win(state: &State) -> Outcome {
     }
 }
 
+/// An implementation of Level::check_win for levels where the objective is
+/// to write the correct data to every input terminal (see
+/// Level::check_input).
+pub fn input_terminals_check_win(state: &State) -> Outcome {
+    if is_destroyed_by_enemy(state) {
+        Outcome::Failure(ERR_DESTROYED_BY_ENEMY.to_string())
+    } else if !state.input_terminals.is_empty() && state.input_terminals.iter().all(|t| t.accepted)
+    {
+        Outcome::Success
+    } else if state.player.energy == 0 {
+        Outcome::Failure(ERR_OUT_OF_ENERGY.to_string())
+    } else {
+        Outcome::Continue
+    }
+}
+
 /// Expects an array of initial states for a level where each state
 /// has only one possible orientation for each telepad. Expands the
 /// possible states to include all possible orientations for each
 /// telepad. Returns the new, expanded states.
+///
+/// States with more than MAX_TELEPADS telepads are returned as-is.
 pub fn make_all_initial_states_for_telepads(states: Vec<State>) -> Vec<State> {
     let mut new_states = vec![];
     for state in states.iter() {
         if state.telepads.is_empty() {
             new_states.push(state.clone());
             continue;
-        } else if state.telepads.len() > 3 {
+        } else if state.telepads.len() > MAX_TELEPADS {
+            // The total number of initial states grows with 4^n where n is the
+            // number of telepads. When the user runs a script, the simulation is
+            // run once for each possible initial state. Therefore, we need to limit
+            // the number of initial states in order to keep the total script run time
+            // low. We don't expand the state in this case and leave it to the level
+            // validator to report the issue (see LevelIssue::TooManyTelepads).
+            new_states.push(state.clone());
+            continue;
         }
         for orientation in [
             Orientation::Up,
@@ -315,15 +377,6 @@ pub fn make_all_initial_states_for_telepads(states: Vec<State>) -> Vec<State> {
                     nested_state.telepads[2].end_facing = orientation.flip();
                     new_states.push(nested_state);
                 }
-            } else {
-                // The total number of initial states grows with 4^n where n is the
-                // number of telepads. When the user runs a script, the simulation is
-                // run once for each possible initial state. Therefore, we need to limit
-                // the number of initial states in order to keep the total script run time
-                // low.
-                panic!(
-                    "Error computing initial states for telepads: max number of telepads exceeded."
-                );
             }
         }
     }
@@ -334,6 +387,7 @@ pub fn make_all_initial_states_for_telepads(states: Vec<State>) -> Vec<State> {
 mod tests {
     use super::*;
     use crate::{
+        script_runner::new_level_engine,
         simulation::{Obstacle, Orientation, Pos, Telepad},
         state_maker::StateMaker,
     };
@@ -384,6 +438,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn make_all_initial_states_for_too_many_telepads() {
+        // States with too many telepads are returned as-is instead of being
+        // expanded.
+        let state = StateMaker::new()
+            .with_telepads(vec![
+                Telepad::new((1, 0), (1, 5), Orientation::Up),
+                Telepad::new((2, 0), (2, 5), Orientation::Up),
+                Telepad::new((3, 0), (3, 5), Orientation::Up),
+                Telepad::new((4, 0), (4, 5), Orientation::Up),
+            ])
+            .build();
+        let initial_states = make_all_initial_states_for_telepads(vec![state.clone()]);
+        assert_eq!(initial_states, vec![state]);
+    }
+
     #[test]
     fn test_make_all_initial_states_for_telepads() {
         let state = StateMaker::new()
@@ -498,4 +568,184 @@ mod tests {
         assert_eq!(full_initial_states.len(), expected.len());
         assert_eq!(full_initial_states, expected);
     }
+
+    /// Returns true if the level has an objective, i.e. it doesn't use
+    /// no_objective_check_win or something similar.
+    fn has_objective(level: &dyn Level) -> bool {
+        level.check_win(&level.initial_states()[0]) != Outcome::NoObjective
+    }
+
+    /// Functions which are unlocked at the start of the game (see
+    /// DEFAULT_UNLOCKED_FUNCTIONS in web/contexts/save_data.tsx).
+    const DEFAULT_UNLOCKED_FUNCTIONS: &[&str] =
+        &["move_forward", "move_backward", "turn_left", "turn_right"];
+
+    /// The levels in the order they are played, along with any functions
+    /// which are unlocked by the time the player reaches them (see RAW_SCENES
+    /// in web/contexts/scenes.tsx).
+    const CAMPAIGN: &[(&str, &[&str])] = &[
+        ("movement", &[]),
+        ("movement_part_two", &["say"]),
+        ("energy_part_one", &[]),
+        ("loops_part_one", &[]),
+        ("loops_part_two", &[]),
+        ("buttons_part_one", &["press_button"]),
+        ("button_and_gate", &[]),
+        ("data_points_part_one", &["get_orientation", "read_data"]),
+        ("gates", &[]),
+        ("variables_intro", &[]),
+        ("gate_and_data_point", &[]),
+        ("gate_and_data_point_part_two", &[]),
+        ("asteroid_strike", &[]),
+        ("asteroid_strike_part_two", &[]),
+        ("partly_disabled_movement", &[]),
+        ("reimplement_turn_right", &[]),
+        ("telepad_part_one", &[]),
+        ("telepad_part_two", &[]),
+        ("telepads_and_while_loop", &[]),
+        ("enemies_part_one", &[]),
+        ("enemies_part_two", &[]),
+        ("enemies_with_telepad", &[]),
+        ("enemies_and_asteroids", &[]),
+        ("big_enemy", &[]),
+        ("server_room", &[]),
+    ];
+
+    /// Returns the functions available to the player in the given level, i.e.
+    /// the functions unlocked by the time they reach it minus the level's
+    /// disabled functions. Levels which are not part of the campaign (e.g.
+    /// sandbox) get every function unlocked by the end of the game.
+    fn avail_funcs(level: &'static dyn Level) -> Vec<String> {
+        let mut unlocked: Vec<String> = DEFAULT_UNLOCKED_FUNCTIONS
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        for (short_name, new_funcs) in CAMPAIGN {
+            unlocked.extend(new_funcs.iter().map(|f| f.to_string()));
+            if *short_name == level.short_name() {
+                break;
+            }
+        }
+        crate::get_avail_funcs(level, &unlocked)
+    }
+
+    #[test]
+    fn campaign_levels_exist() {
+        for (short_name, _) in CAMPAIGN {
+            assert!(
+                LEVELS.contains_key(short_name),
+                "no level with short name {}",
+                short_name
+            );
+        }
+    }
+
+    #[test]
+    fn locked_functions_are_unavailable() {
+        let mut game = crate::Game::new();
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let err = game
+            .run_player_script_internal(level, &avail_funcs(level), "say(\"hi\");".to_string())
+            .unwrap_err();
+        assert!(
+            err.message.contains("say"),
+            "unexpected error: {}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn initial_code_compiles() {
+        for level in LEVELS.values() {
+            if let Err(err) = new_level_engine(level.as_ref()).compile(level.initial_code()) {
+                panic!(
+                    "initial code for level {} does not compile: {}",
+                    level.short_name(),
+                    err
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reference_solutions() {
+        let mut game = crate::Game::new();
+        for level in LEVELS.values() {
+            let solutions = level.reference_solutions();
+            if has_objective(level.as_ref()) {
+                assert!(
+                    !solutions.is_empty(),
+                    "level {} has no reference solutions",
+                    level.short_name()
+                );
+            }
+            for script in solutions {
+                let result = game
+                    .run_player_script_internal(
+                        level.as_ref(),
+                        &avail_funcs(level.as_ref()),
+                        script.to_string(),
+                    )
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "reference solution for level {} returned an error: {}",
+                            level.short_name(),
+                            err.message
+                        )
+                    });
+                assert_eq!(
+                    result.outcome,
+                    Outcome::Success,
+                    "reference solution for level {} failed in initial state {}",
+                    level.short_name(),
+                    result.initial_state_index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn challenge_solutions() {
+        let mut game = crate::Game::new();
+        for level in LEVELS.values() {
+            let script = match (level.challenge(), level.challenge_solution()) {
+                (Some(_), Some(script)) => script,
+                (None, None) => continue,
+                (Some(_), None) => panic!(
+                    "level {} has a challenge but no challenge solution",
+                    level.short_name()
+                ),
+                (None, Some(_)) => panic!(
+                    "level {} has a challenge solution but no challenge",
+                    level.short_name()
+                ),
+            };
+            let result = game
+                .run_player_script_internal(
+                    level.as_ref(),
+                    &avail_funcs(level.as_ref()),
+                    script.to_string(),
+                )
+                .unwrap_or_else(|err| {
+                    panic!(
+                        "challenge solution for level {} returned an error: {}",
+                        level.short_name(),
+                        err.message
+                    )
+                });
+            assert_eq!(
+                result.outcome,
+                Outcome::Success,
+                "challenge solution for level {} failed in initial state {}",
+                level.short_name(),
+                result.initial_state_index
+            );
+            assert!(
+                result.passes_challenge,
+                "challenge solution for level {} does not pass the challenge in initial state {}",
+                level.short_name(),
+                result.initial_state_index
+            );
+        }
+    }
 }
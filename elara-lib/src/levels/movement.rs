@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{Actor, Goal, Obstacle, Orientation, Player, State};
 
@@ -28,7 +30,7 @@ move_forward(1);
         let mut state = State::new();
         state.player = Player::new(0, 0, 20, Orientation::Right);
         state.goals = vec![Goal::new(3, 3)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             // Obstacles enclose the player and goal in a 4x4 square.
             Obstacle::new(4, 0),
             Obstacle::new(4, 1),
@@ -39,7 +41,7 @@ move_forward(1);
             Obstacle::new(1, 4),
             Obstacle::new(2, 4),
             Obstacle::new(3, 4),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -48,6 +50,14 @@ move_forward(1);
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(3);
+turn_right();
+move_forward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
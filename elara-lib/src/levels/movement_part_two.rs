@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::{
     script_runner::ScriptStats,
@@ -28,7 +30,7 @@ impl Level for MovementPartTwo {
         let mut state = State::new();
         state.player = Player::new(8, 7, 20, Orientation::Right);
         state.goals = vec![Goal::new(8, 4)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(7, 3),
             Obstacle::new(7, 4),
             Obstacle::new(7, 5),
@@ -43,7 +45,7 @@ impl Level for MovementPartTwo {
             Obstacle::new(10, 5),
             Obstacle::new(10, 6),
             Obstacle::new(11, 3),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -58,6 +60,25 @@ impl Level for MovementPartTwo {
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.code_len <= 40
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(3);
+turn_left();
+move_forward(3);
+turn_left();
+move_forward(3);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"loop {
+  move_forward(3);
+  turn_left();
+}
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{Actor, Goal, Obstacle, Orientation, Player, State};
 
@@ -32,7 +34,7 @@ impl Level for PartlyDisabledMovement {
         let mut state = State::new();
         state.player = Player::new(0, 7, 10, Orientation::Right);
         state.goals = vec![Goal::new(3, 4)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 3),
             Obstacle::new(1, 3),
             Obstacle::new(2, 3),
@@ -42,7 +44,7 @@ impl Level for PartlyDisabledMovement {
             Obstacle::new(4, 5),
             Obstacle::new(4, 6),
             Obstacle::new(4, 7),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -51,6 +53,16 @@ impl Level for PartlyDisabledMovement {
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"turn_left();
+turn_left();
+move_backward(3);
+turn_left();
+move_backward(3);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::{
     script_runner::ScriptStats,
@@ -49,7 +51,7 @@ move_backward(1);
         let mut state = State::new();
         state.player = Player::new(0, 0, 10, Orientation::Left);
         state.goals = vec![Goal::new(2, 2)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(0, 1),
             Obstacle::new(0, 2),
             Obstacle::new(0, 3),
@@ -71,7 +73,7 @@ move_backward(1);
             Obstacle::new(5, 3),
             Obstacle::new(5, 4),
             Obstacle::new(5, 5),
-        ];
+        ]);
         state.energy_cells = vec![EnergyCell::new(2, 4)];
         vec![state]
     }
@@ -87,6 +89,38 @@ move_backward(1);
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.code_len <= 55
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"fn three_lefts() {
+  turn_left();
+  turn_left();
+  turn_left();
+}
+
+move_backward(4);
+three_lefts();
+move_backward(4);
+three_lefts();
+move_backward(3);
+three_lefts();
+move_backward(2);
+three_lefts();
+move_backward(1);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"fn l() { turn_left(); }
+loop {
+  move_backward(4);
+  l();
+  l();
+  l();
+}
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{no_objective_check_win, Level, Outcome};
 use crate::{
     constants::MAX_ENERGY,
@@ -29,7 +31,7 @@ impl Level for SandboxWithDataPoint {
     fn initial_states(&self) -> Vec<State> {
         let mut state = State::new();
         state.player = Player::new(0, 0, 50, Orientation::Right);
-        state.data_points = vec![DataPoint::new(1, 0, "bananas".into())];
+        state.data_points = Rc::new(vec![DataPoint::new(1, 0, "bananas".into())]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::rc::Rc;
 
 use super::{Level, Outcome};
 use crate::{
@@ -108,7 +109,7 @@ impl Level for ServerRoom {
                 .into(),
         )];
         state.energy_cells = vec![EnergyCell::new(2, 7), EnergyCell::new(6, 4)];
-        state.data_points = vec![
+        state.data_points = Rc::new(vec![
             DataPoint::new_with_info(
                 0,
                 7,
@@ -151,8 +152,8 @@ impl Level for ServerRoom {
                 "This data point holds a message from the original team that built Moonbase Alpha."
                     .into(),
             ),
-        ];
-        state.obstacles = vec![
+        ]);
+        state.obstacles = Rc::new(vec![
             Obstacle::new_with_kind(0, 1, ObstacleKind::Server),
             Obstacle::new_with_kind(1, 1, ObstacleKind::Server),
             Obstacle::new_with_kind(2, 1, ObstacleKind::Server),
@@ -186,7 +187,7 @@ impl Level for ServerRoom {
             Obstacle::new_with_kind(9, 5, ObstacleKind::Server),
             Obstacle::new_with_kind(10, 5, ObstacleKind::Server),
             Obstacle::new_with_kind(11, 5, ObstacleKind::Server),
-        ];
+        ]);
         vec![state]
     }
     fn actors(&self) -> Vec<Box<dyn Actor>> {
@@ -221,6 +222,45 @@ impl Level for ServerRoom {
         }
         remaining_messages.is_empty()
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"move_forward(6);
+press_button();
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"turn_left();
+move_forward(5);
+say(read_data());
+turn_right();
+move_forward(1);
+say(read_data());
+turn_right();
+move_forward(3);
+say(read_data());
+move_forward(2);
+turn_left();
+move_forward(2);
+turn_right();
+move_forward(2);
+say(read_data());
+move_forward(2);
+say(read_data());
+move_backward(4);
+turn_left();
+move_forward(2);
+turn_left();
+move_forward(3);
+say(read_data());
+move_backward(3);
+turn_right();
+move_forward(1);
+press_button();
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,5 @@
-use rhai::Engine;
-
 use super::{std_check_win, Level, Outcome};
+use crate::script_query::ScriptQuery;
 use crate::simulation::{Actor, Goal, Obstacle, Orientation, Player, State, Telepad};
 use crate::state_maker::StateMaker;
 
@@ -101,13 +100,60 @@ if get_orientation() == "up" {
         if stats.code_len > 165 {
             return false;
         }
-        // Script cannot contain the key word "fn".
-        if let Ok(script) = Engine::new().compact_script(script) {
-            let fn_regex = regex::Regex::new(r"\b(fn)\b").unwrap();
-            return !fn_regex.is_match(script.as_str());
+        // Script cannot define any functions.
+        match ScriptQuery::new(script) {
+            Some(query) => !query.defines_fn(),
+            // Otherwise, challenge is considered not passed.
+            None => false,
         }
-        // Otherwise, challenge is considered not passed.
-        false
+    }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"// This code uses the get_orientation function to figure out which
+// way the rover is facing. You DON'T need to change this part.
+move_forward(3);
+
+// If the rover is facing up, turn to the right and then move
+// forward. You DON'T need to change this part.
+if get_orientation() == "up" {
+  turn_right();
+  move_forward(3);
+}
+
+// Add more if statements to handle the other possible orientations.
+// ADD YOUR CODE BELOW
+if get_orientation() == "down" {
+  turn_left();
+  move_forward(3);
+}
+if get_orientation() == "left" {
+  turn_right();
+  turn_right();
+  move_forward(3);
+}
+if get_orientation() == "right" {
+  move_forward(3);
+}
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"move_forward(3);
+let facing = get_orientation();
+if facing == "up" {
+  turn_right();
+}
+if facing == "down" {
+  turn_left();
+}
+if facing == "left" {
+  turn_left();
+  turn_left();
+}
+move_forward(3);
+"#,
+        )
     }
 }
 
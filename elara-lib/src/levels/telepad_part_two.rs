@@ -66,6 +66,25 @@ impl Level for TelepadPartTwo {
     fn check_win(&self, state: &State) -> Outcome {
         std_check_win(state)
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"fn face_right() {
+  loop {
+    if get_orientation() == "right" {
+      break;
+    }
+    turn_right();
+  }
+}
+
+move_forward(2);
+face_right();
+move_forward(2);
+face_right();
+move_backward(2);
+"#,
+        ]
+    }
 }
 
 #[cfg(test)]
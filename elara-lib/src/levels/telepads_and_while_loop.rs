@@ -89,6 +89,53 @@ move_forward(3);
     fn check_challenge(&self, _states: &Vec<State>, _script: &str, stats: &ScriptStats) -> bool {
         stats.time_taken <= 18
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"fn face_up() {
+  while get_orientation() != "up" {
+    turn_left();
+  }
+}
+
+move_forward(2);
+face_up();
+move_forward(3);
+face_up();
+move_forward(4);
+face_up();
+move_forward(5);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"// This function will cause G.R.O.V.E.R. to move up in as few
+// steps as possible, regardless of which direction he is currently
+// facing.
+fn move_up(spaces) {
+  let facing = get_orientation();
+  // If facing down, move backward.
+  if facing == "down" {
+    move_backward(spaces);
+    return;
+  }
+  // Otherwise turn to face up, then move forward.
+  if facing == "left" {
+    turn_right();
+  }
+  if facing == "right" {
+    turn_left();
+  }
+  move_forward(spaces);
+}
+
+move_up(2);
+move_up(3);
+move_up(4);
+move_up(5);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,476 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use super::Level;
+use crate::actors::{Bounds, BIG_ENEMY_SIZE};
+use crate::constants::MAX_TELEPADS;
+use crate::simulation::{ButtonConnection, Pos, State};
+
+/// Refers to a single entity in a State (e.g. for the purposes of reporting
+/// an issue with it). The usize is the index of the entity in the
+/// corresponding Vec in State.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Entity {
+    Player,
+    Goal(usize),
+    Obstacle(usize),
+    EnergyCell(usize),
+    Button(usize),
+    Gate(usize),
+    DataPoint(usize),
+    InputTerminal(usize),
+    PasswordGate(usize),
+    TelepadStart(usize),
+    TelepadEnd(usize),
+    Enemy(usize),
+    BigEnemy(usize),
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entity::Player => write!(f, "player"),
+            Entity::Goal(i) => write!(f, "goal {}", i),
+            Entity::Obstacle(i) => write!(f, "obstacle {}", i),
+            Entity::EnergyCell(i) => write!(f, "energy cell {}", i),
+            Entity::Button(i) => write!(f, "button {}", i),
+            Entity::Gate(i) => write!(f, "gate {}", i),
+            Entity::DataPoint(i) => write!(f, "data point {}", i),
+            Entity::InputTerminal(i) => write!(f, "input terminal {}", i),
+            Entity::PasswordGate(i) => write!(f, "password gate {}", i),
+            Entity::TelepadStart(i) => write!(f, "start of telepad {}", i),
+            Entity::TelepadEnd(i) => write!(f, "end of telepad {}", i),
+            Entity::Enemy(i) => write!(f, "enemy {}", i),
+            Entity::BigEnemy(i) => write!(f, "big enemy {}", i),
+        }
+    }
+}
+
+/// A problem with the initial states of a level. Issues which refer to a
+/// specific initial state include the index of that state in
+/// Level::initial_states.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LevelIssue {
+    /// Level::initial_states returned an empty Vec.
+    NoInitialStates,
+    /// The state has more telepads than we support.
+    TooManyTelepads { state_index: usize, count: usize },
+    /// The entity is outside of Level::bounds.
+    OutOfBounds {
+        state_index: usize,
+        entity: Entity,
+        pos: Pos,
+    },
+    /// Two entities occupy the same position.
+    Overlap {
+        state_index: usize,
+        first: Entity,
+        second: Entity,
+        pos: Pos,
+    },
+    /// A button or input terminal is connected to a gate which does not
+    /// exist.
+    InvalidGateConnection {
+        state_index: usize,
+        source: Entity,
+        gate_index: usize,
+    },
+    /// The goal can't be reached from the player's starting position (and
+    /// neither can any of the other goals in the same state).
+    UnreachableGoal {
+        state_index: usize,
+        goal_index: usize,
+        pos: Pos,
+    },
+}
+
+impl fmt::Display for LevelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelIssue::NoInitialStates => write!(f, "level has no initial states"),
+            LevelIssue::TooManyTelepads { state_index, count } => write!(
+                f,
+                "state {}: {} telepads (max is {})",
+                state_index, count, MAX_TELEPADS
+            ),
+            LevelIssue::OutOfBounds {
+                state_index,
+                entity,
+                pos,
+            } => write!(
+                f,
+                "state {}: {} at ({}, {}) is out of bounds",
+                state_index, entity, pos.x, pos.y
+            ),
+            LevelIssue::Overlap {
+                state_index,
+                first,
+                second,
+                pos,
+            } => write!(
+                f,
+                "state {}: {} and {} overlap at ({}, {})",
+                state_index, first, second, pos.x, pos.y
+            ),
+            LevelIssue::InvalidGateConnection {
+                state_index,
+                source,
+                gate_index,
+            } => write!(
+                f,
+                "state {}: {} is connected to gate {} which does not exist",
+                state_index, source, gate_index
+            ),
+            LevelIssue::UnreachableGoal {
+                state_index,
+                goal_index,
+                pos,
+            } => write!(
+                f,
+                "state {}: goal {} at ({}, {}) can't be reached",
+                state_index, goal_index, pos.x, pos.y
+            ),
+        }
+    }
+}
+
+/// Checks that the possible initial states for the given level are coherent
+/// and returns any issues that were found. An empty Vec means the level is
+/// valid.
+pub fn validate_level(level: &dyn Level) -> Vec<LevelIssue> {
+    let initial_states = level.initial_states();
+    if initial_states.is_empty() {
+        return vec![LevelIssue::NoInitialStates];
+    }
+
+    let bounds = level.bounds();
+    let mut issues = vec![];
+    for (state_index, state) in initial_states.iter().enumerate() {
+        issues.extend(validate_state(state_index, state, &bounds));
+    }
+    issues
+}
+
+/// Checks a single initial state. state_index is only used for reporting.
+fn validate_state(state_index: usize, state: &State, bounds: &Bounds) -> Vec<LevelIssue> {
+    let mut issues = vec![];
+
+    if state.telepads.len() > MAX_TELEPADS {
+        issues.push(LevelIssue::TooManyTelepads {
+            state_index,
+            count: state.telepads.len(),
+        });
+    }
+
+    // Check for entities which are outside the bounds or overlap with one
+    // another.
+    let mut occupied: HashMap<Pos, Entity> = HashMap::new();
+    for (entity, pos) in entity_positions(state) {
+        if !in_bounds(bounds, &pos) {
+            issues.push(LevelIssue::OutOfBounds {
+                state_index,
+                entity,
+                pos: pos.clone(),
+            });
+        }
+        match occupied.get(&pos) {
+            Some(first) => issues.push(LevelIssue::Overlap {
+                state_index,
+                first: *first,
+                second: entity,
+                pos,
+            }),
+            None => {
+                occupied.insert(pos, entity);
+            }
+        }
+    }
+
+    let connections = state
+        .buttons
+        .iter()
+        .enumerate()
+        .map(|(i, button)| (Entity::Button(i), &button.connection))
+        .chain(
+            state
+                .input_terminals
+                .iter()
+                .enumerate()
+                .map(|(i, terminal)| (Entity::InputTerminal(i), &terminal.connection)),
+        );
+    for (source, connection) in connections {
+        if let ButtonConnection::Gate(gate_index) = *connection {
+            if gate_index >= state.gates.len() {
+                issues.push(LevelIssue::InvalidGateConnection {
+                    state_index,
+                    source,
+                    gate_index,
+                });
+            }
+        }
+    }
+
+    // Reaching any of the goals is enough to win (see std_check_win), so
+    // some goals are allowed to be unreachable (e.g. if the path to them is
+    // blocked in some initial states but not others). It's only an issue if
+    // none of them can be reached.
+    let reachable = reachable_positions(state, bounds);
+    if !state.goals.iter().any(|goal| reachable.contains(&goal.pos)) {
+        for (goal_index, goal) in state.goals.iter().enumerate() {
+            issues.push(LevelIssue::UnreachableGoal {
+                state_index,
+                goal_index,
+                pos: goal.pos.clone(),
+            });
+        }
+    }
+
+    issues
+}
+
+/// Returns every position occupied by an entity in the given state. Big
+/// enemies occupy multiple positions.
+fn entity_positions(state: &State) -> Vec<(Entity, Pos)> {
+    let mut positions = vec![(Entity::Player, state.player.pos.clone())];
+    for (i, goal) in state.goals.iter().enumerate() {
+        positions.push((Entity::Goal(i), goal.pos.clone()));
+    }
+    for (i, obstacle) in state.obstacles.iter().enumerate() {
+        positions.push((Entity::Obstacle(i), obstacle.pos.clone()));
+    }
+    for (i, energy_cell) in state.energy_cells.iter().enumerate() {
+        positions.push((Entity::EnergyCell(i), energy_cell.pos.clone()));
+    }
+    for (i, button) in state.buttons.iter().enumerate() {
+        positions.push((Entity::Button(i), button.pos.clone()));
+    }
+    for (i, gate) in state.gates.iter().enumerate() {
+        positions.push((Entity::Gate(i), gate.pos.clone()));
+    }
+    for (i, data_point) in state.data_points.iter().enumerate() {
+        positions.push((Entity::DataPoint(i), data_point.pos.clone()));
+    }
+    for (i, terminal) in state.input_terminals.iter().enumerate() {
+        positions.push((Entity::InputTerminal(i), terminal.pos.clone()));
+    }
+    for (i, password_gate) in state.password_gates.iter().enumerate() {
+        positions.push((Entity::PasswordGate(i), password_gate.pos.clone()));
+    }
+    for (i, telepad) in state.telepads.iter().enumerate() {
+        positions.push((Entity::TelepadStart(i), telepad.start_pos.clone()));
+        positions.push((Entity::TelepadEnd(i), telepad.end_pos.clone()));
+    }
+    for (i, enemy) in state.enemies.iter().enumerate() {
+        positions.push((Entity::Enemy(i), enemy.pos.clone()));
+    }
+    for (i, big_enemy) in state.big_enemies.iter().enumerate() {
+        for x in big_enemy.pos.x..big_enemy.pos.x + BIG_ENEMY_SIZE {
+            for y in big_enemy.pos.y..big_enemy.pos.y + BIG_ENEMY_SIZE {
+                positions.push((Entity::BigEnemy(i), Pos::new(x, y)));
+            }
+        }
+    }
+    positions
+}
+
+fn in_bounds(bounds: &Bounds, pos: &Pos) -> bool {
+    pos.x >= bounds.min_x && pos.x <= bounds.max_x && pos.y >= bounds.min_y && pos.y <= bounds.max_y
+}
+
+/// Returns true if the player could ever occupy the given position. Gates
+/// are considered passable since they can be opened, and enemies are ignored
+/// since they move around.
+fn is_passable(state: &State, bounds: &Bounds, pos: &Pos) -> bool {
+    in_bounds(bounds, pos)
+        && !state.obstacles.iter().any(|o| o.pos == *pos)
+        && !state.data_points.iter().any(|d| d.pos == *pos)
+        && !state.input_terminals.iter().any(|t| t.pos == *pos)
+        && !state.buttons.iter().any(|b| b.pos == *pos)
+}
+
+/// Does a breadth-first search starting from the player's position and
+/// returns all positions the player could reach. Moving onto the start of a
+/// telepad moves the player to the end of the telepad.
+fn reachable_positions(state: &State, bounds: &Bounds) -> HashSet<Pos> {
+    let mut reachable = HashSet::new();
+    let mut queue = VecDeque::new();
+    reachable.insert(state.player.pos.clone());
+    queue.push_back(state.player.pos.clone());
+
+    while let Some(pos) = queue.pop_front() {
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let mut next = Pos::new(pos.x + dx, pos.y + dy);
+            if !is_passable(state, bounds, &next) {
+                continue;
+            }
+            if let Some(telepad) = state.telepads.iter().find(|t| t.start_pos == next) {
+                next = telepad.end_pos.clone();
+            }
+            if reachable.insert(next.clone()) {
+                queue.push_back(next);
+            }
+        }
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::Bounds;
+    use crate::levels::LEVELS;
+    use crate::simulation::{
+        Button, ButtonConnection, Enemy, Gate, GateVariant, Goal, InputTerminal, Obstacle,
+        Orientation, Player, Telepad,
+    };
+    use crate::state_maker::StateMaker;
+
+    #[test]
+    fn all_levels_are_valid() {
+        for level in LEVELS.values() {
+            // The malfunctioning rover in loops_part_two intentionally starts
+            // just off screen, so that the player can see it moving around
+            // without it getting in their way.
+            let expected = if level.short_name() == "loops_part_two" {
+                vec![LevelIssue::OutOfBounds {
+                    state_index: 0,
+                    entity: Entity::Enemy(0),
+                    pos: Pos::new(12, 2),
+                }]
+            } else {
+                vec![]
+            };
+            let issues = validate_level(level.as_ref());
+            assert_eq!(
+                issues,
+                expected,
+                "level {} has issues:\n{}",
+                level.short_name(),
+                issues
+                    .iter()
+                    .map(|issue| issue.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn overlap_and_out_of_bounds() {
+        let state = StateMaker::new()
+            .with_player(Player::new(0, 0, 10, Orientation::Right))
+            .with_goals(vec![Goal::new(2, 0)])
+            .with_obstacles(vec![Obstacle::new(1, 1), Obstacle::new(2, 0)])
+            .with_enemies(vec![Enemy::new(5, 5, Orientation::Left)])
+            .build();
+        let issues = validate_state(0, &state, &Bounds::new(0, 3, 0, 3));
+        assert_eq!(
+            issues,
+            vec![
+                LevelIssue::Overlap {
+                    state_index: 0,
+                    first: Entity::Goal(0),
+                    second: Entity::Obstacle(1),
+                    pos: Pos::new(2, 0),
+                },
+                LevelIssue::OutOfBounds {
+                    state_index: 0,
+                    entity: Entity::Enemy(0),
+                    pos: Pos::new(5, 5),
+                },
+                LevelIssue::UnreachableGoal {
+                    state_index: 0,
+                    goal_index: 0,
+                    pos: Pos::new(2, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_gate_connection() {
+        let mut state = StateMaker::new()
+            .with_buttons(vec![
+                Button::new(1, 0, ButtonConnection::Gate(0)),
+                Button::new(2, 0, ButtonConnection::Gate(1)),
+            ])
+            .with_gates(vec![Gate::new(3, 0, false, GateVariant::NWSE)])
+            .build();
+        state.input_terminals = vec![InputTerminal::new(4, 0, ButtonConnection::Gate(2))];
+        let issues = validate_state(0, &state, &Bounds::default());
+        assert_eq!(
+            issues,
+            vec![
+                LevelIssue::InvalidGateConnection {
+                    state_index: 0,
+                    source: Entity::Button(1),
+                    gate_index: 1,
+                },
+                LevelIssue::InvalidGateConnection {
+                    state_index: 0,
+                    source: Entity::InputTerminal(0),
+                    gate_index: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "state 0: input terminal 0 is connected to gate 2 which does not exist"
+        );
+    }
+
+    #[test]
+    fn too_many_telepads() {
+        let state = StateMaker::new()
+            .with_telepads(vec![
+                Telepad::new((1, 0), (1, 5), Orientation::Up),
+                Telepad::new((2, 0), (2, 5), Orientation::Up),
+                Telepad::new((3, 0), (3, 5), Orientation::Up),
+                Telepad::new((4, 0), (4, 5), Orientation::Up),
+            ])
+            .build();
+        let issues = validate_state(0, &state, &Bounds::default());
+        assert_eq!(
+            issues,
+            vec![LevelIssue::TooManyTelepads {
+                state_index: 0,
+                count: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn unreachable_goals() {
+        // The goal is on the other side of a wall, but there is a telepad that
+        // leads past it.
+        let mut state = StateMaker::new()
+            .with_player(Player::new(0, 0, 10, Orientation::Right))
+            .with_goals(vec![Goal::new(3, 3)])
+            .with_obstacles(vec![
+                Obstacle::new(2, 0),
+                Obstacle::new(2, 1),
+                Obstacle::new(2, 2),
+                Obstacle::new(2, 3),
+            ])
+            .with_telepads(vec![Telepad::new((1, 0), (3, 0), Orientation::Down)])
+            .build();
+        let bounds = Bounds::new(0, 3, 0, 3);
+        assert_eq!(validate_state(0, &state, &bounds), vec![]);
+
+        // Without the telepad, the goal can't be reached.
+        state.telepads.clear();
+        assert_eq!(
+            validate_state(0, &state, &bounds),
+            vec![LevelIssue::UnreachableGoal {
+                state_index: 0,
+                goal_index: 0,
+                pos: Pos::new(3, 3),
+            }]
+        );
+
+        // It's fine for a goal to be unreachable as long as some other goal
+        // can be reached.
+        state.goals.push(Goal::new(0, 3));
+        assert_eq!(validate_state(0, &state, &bounds), vec![]);
+    }
+}
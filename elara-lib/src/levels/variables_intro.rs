@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use super::{std_check_win, Level, Outcome};
 use crate::simulation::{
     Actor, EnergyCell, GateVariant, Goal, Obstacle, Orientation, PasswordGate, Player, State,
@@ -35,7 +37,7 @@ let password = "supercalifragilisticexpialidocious";
         let mut state = State::new();
         state.player = Player::new(5, 7, 7, Orientation::Up);
         state.goals = vec![Goal::new(9, 3)];
-        state.obstacles = vec![
+        state.obstacles = Rc::new(vec![
             Obstacle::new(2, 2),
             Obstacle::new(2, 3),
             Obstacle::new(2, 4),
@@ -67,7 +69,7 @@ let password = "supercalifragilisticexpialidocious";
             Obstacle::new(10, 2),
             Obstacle::new(10, 3),
             Obstacle::new(10, 4),
-        ];
+        ]);
         state.energy_cells = vec![
             EnergyCell::new(3, 3),
             EnergyCell::new(8, 7),
@@ -118,6 +120,36 @@ let password = "supercalifragilisticexpialidocious";
     ) -> bool {
         stats.energy_used <= 12
     }
+    fn reference_solutions(&self) -> Vec<&'static str> {
+        vec![
+            r#"let password = "supercalifragilisticexpialidocious";
+turn_right();
+move_forward(3);
+move_backward(3);
+turn_left();
+say(password);
+move_forward(2);
+say(password);
+move_forward(2);
+turn_right();
+move_forward(4);
+"#,
+        ]
+    }
+    fn challenge_solution(&self) -> Option<&'static str> {
+        Some(
+            r#"let password = "supercalifragilisticexpialidocious";
+say(password);
+move_forward(2);
+say(password);
+move_forward(2);
+say(password);
+turn_left();
+move_forward(2);
+move_backward(6);
+"#,
+        )
+    }
 }
 
 #[cfg(test)]
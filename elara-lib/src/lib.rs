@@ -8,19 +8,26 @@ extern crate lazy_static;
 
 mod actors;
 mod better_errors;
+mod builtins;
+mod code_metrics;
 mod constants;
+mod infinite_loop_detector;
 mod js_types;
 mod levels;
+mod script_query;
 mod script_runner;
+mod semicolon_checker;
+mod serialization;
+mod session;
 mod simulation;
+mod spatial_index;
 mod state_maker;
 
-use actors::{Action, Bounds};
-use better_errors::BetterError;
-use constants::{HEIGHT, WIDTH};
-use levels::{Level, Outcome, LEVELS};
+use actors::{Bounds, PlayerAction};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use script_runner::{ScriptResult, ScriptRunner};
+use rand::SeedableRng;
+use script_runner::ScriptRunner;
 use simulation::Simulation;
 use std::cell::RefCell;
 use std::collections::HashSet;
@@ -28,6 +35,25 @@ use std::rc::Rc;
 use std::sync::mpsc;
 use wasm_bindgen::prelude::*;
 
+// Re-exports which make up the native (i.e. non-Wasm) Rust API. These are
+// used by the elara-cli binary.
+pub use better_errors::BetterError;
+pub use builtins::{Builtin, Cost, Steps, BUILTINS, BUILTIN_FUNCTIONS};
+pub use levels::data_level::{
+    BigEnemyDef, ButtonDef, ChallengeCriterion, ChallengeDef, DataLevel, DataPointDef, EnemyDef,
+    GateDef, InputCheck, InputTerminalDef, LevelDef, ObjectiveType, ObstacleDef, PasswordGateDef,
+    PlayerDef, StateDef, TelepadDef,
+};
+pub use levels::validation::{validate_level, Entity, LevelIssue};
+pub use levels::{input_terminals_check_win, Level, Outcome, LEVELS};
+pub use script_query::ScriptQuery;
+pub use script_runner::{
+    Breakpoint, OutputKind, OutputLine, Pause, ScriptResult, ScriptStats, StackFrame, VarSnapshot,
+};
+pub use serialization::{from_json, to_json, SerializationError, Versioned, SERIALIZATION_VERSION};
+pub use session::{ResumeMode, SessionStep};
+pub use simulation::{State, TermData};
+
 #[wasm_bindgen]
 /// Game is the main entry point for the game. It is responsible for
 /// managing state, running user scripts, and gluing all the pieces
@@ -35,8 +61,12 @@ use wasm_bindgen::prelude::*;
 pub struct Game {
     simulation: Rc<RefCell<Simulation>>,
     script_runner: ScriptRunner,
-    player_action_rx: Rc<RefCell<mpsc::Receiver<Action>>>,
-    // player_action_tx: Rc<RefCell<mpsc::Sender<Action>>>,
+    player_action_rx: Rc<RefCell<mpsc::Receiver<PlayerAction>>>,
+    // player_action_tx: Rc<RefCell<mpsc::Sender<PlayerAction>>>,
+    /// The current session for running a script one step at a time (if any).
+    session: Option<session::Session>,
+    /// Breakpoints for pausing sessions (see Game::resume_internal).
+    breakpoints: Vec<script_runner::Breakpoint>,
 }
 
 #[wasm_bindgen]
@@ -55,15 +85,15 @@ impl Game {
         let (tx, rx) = mpsc::channel();
         let player_action_tx = Rc::new(RefCell::new(tx));
         let player_action_rx = Rc::new(RefCell::new(rx));
+        // The result of each action is sent back through a separate channel.
+        let (result_tx, result_rx) = mpsc::channel();
+        let action_result_rx = Rc::new(RefCell::new(result_rx));
 
         // Set up the player actor and add it to the Simulation.
-        let bounds = Bounds {
-            min_x: 0,
-            max_x: (WIDTH - 1) as i32,
-            min_y: 0,
-            max_y: (HEIGHT - 1) as i32,
-        };
-        let player_actor = actors::PlayerChannelActor::new(player_action_rx.clone(), bounds);
+        // Note that the bounds are overwritten whenever a level is loaded.
+        let mut player_actor =
+            actors::PlayerChannelActor::new(player_action_rx.clone(), Bounds::default());
+        player_actor.set_result_tx(result_tx);
 
         // Simulation must be wrapped in Rc<RefCell> in order to be
         // used in the script_runner. This is due to a constraint
@@ -72,13 +102,19 @@ impl Game {
 
         // Set up the script runner, which holds references to the
         // player_tx channel and the simulation and glues them together.
-        let script_runner = ScriptRunner::new(simulation.clone(), player_action_tx.clone());
+        let script_runner = ScriptRunner::new(
+            simulation.clone(),
+            player_action_tx.clone(),
+            action_result_rx,
+        );
 
         Game {
             simulation,
             script_runner,
             player_action_rx,
             // player_action_tx,
+            session: None,
+            breakpoints: vec![],
         }
     }
 
@@ -90,23 +126,188 @@ impl Game {
     ) -> Result<js_types::RunResult, JsValue> {
         // Run the script and convert the results to the corresponding JS Types.
         let level = LEVELS.get(level_name).unwrap();
-        let unlocked_funcs = unlocked_funcs
-            .iter()
-            .map(|x| x.as_string().unwrap())
-            .collect();
+        let unlocked_funcs = to_rust_unlocked_funcs(unlocked_funcs);
+        let result = self.run_player_script_internal(level.as_ref(), &unlocked_funcs, script);
+        to_js_run_result_or_err(result)
+    }
+
+    /// Like run_player_script, but uses the given seed to determine the order
+    /// in which the possible initial states are run. The result is always the
+    /// same for the same script and seed.
+    pub fn run_player_script_with_seed(
+        &mut self,
+        level_name: &str,
+        unlocked_funcs: js_sys::Array,
+        script: String,
+        seed: u32,
+    ) -> Result<js_types::RunResult, JsValue> {
+        let level = LEVELS.get(level_name).unwrap();
+        let unlocked_funcs = to_rust_unlocked_funcs(unlocked_funcs);
+        let result = self.run_player_script_with_seed_internal(
+            level.as_ref(),
+            &unlocked_funcs,
+            script,
+            seed as u64,
+        );
+        to_js_run_result_or_err(result)
+    }
+
+    /// Like run_player_script, but only runs the script against the initial
+    /// state with the given index (e.g. the initial_state_index of a previous
+    /// result).
+    pub fn run_player_script_for_state(
+        &mut self,
+        level_name: &str,
+        unlocked_funcs: js_sys::Array,
+        script: String,
+        state_index: usize,
+    ) -> Result<js_types::RunResult, JsValue> {
+        let level = LEVELS.get(level_name).unwrap();
+        let unlocked_funcs = to_rust_unlocked_funcs(unlocked_funcs);
+        let result = self.run_player_script_for_state_internal(
+            level.as_ref(),
+            &unlocked_funcs,
+            script,
+            state_index,
+        );
+        to_js_run_result_or_err(result)
+    }
+
+    /// Starts a new session for running the given script one step at a time.
+    /// If state_index is undefined, a random initial state is used. Returns
+    /// step 0 (i.e. the initial state).
+    ///
+    /// Scripts can't be suspended in the middle of running, so sessions don't
+    /// execute incrementally. Whenever a session needs to go further than the
+    /// steps it has already computed, it replays the script from the start.
+    pub fn start(
+        &mut self,
+        level_name: &str,
+        unlocked_funcs: js_sys::Array,
+        script: String,
+        state_index: Option<usize>,
+    ) -> Result<js_types::SessionStep, JsValue> {
+        let level = LEVELS.get(level_name).unwrap();
+        let unlocked_funcs = to_rust_unlocked_funcs(unlocked_funcs);
+        let result = self.start_internal(level.as_ref(), &unlocked_funcs, script, state_index);
+        to_js_session_step_or_err(result)
+    }
+
+    /// Advances the current session by one step. If the step hasn't been
+    /// computed yet, the script is replayed from the start for twice as many
+    /// steps as before.
+    pub fn step(&mut self) -> Result<js_types::SessionStep, JsValue> {
+        let result = self.step_internal();
+        to_js_session_step_or_err(result)
+    }
+
+    /// Moves the current session to the given step. Like step, this replays
+    /// the script from the start if the step hasn't been computed yet.
+    pub fn continue_to(&mut self, step: usize) -> Result<js_types::SessionStep, JsValue> {
+        let result = self.continue_to_internal(step);
+        to_js_session_step_or_err(result)
+    }
+
+    /// Resumes the current session and pauses again according to mode, which
+    /// must be one of "step_into", "step_over", "step_out", or "continue". If
+    /// the session isn't paused (e.g. after step or continue_to), it resumes
+    /// from the current step.
+    ///
+    /// Every call replays the script from the start until it reaches the next
+    /// pause, so the cost of resuming grows with how far into the script the
+    /// next pause is.
+    pub fn resume(&mut self, mode: &str) -> Result<js_types::SessionStep, JsValue> {
+        let mode = match mode {
+            "step_into" => ResumeMode::StepInto,
+            "step_over" => ResumeMode::StepOver,
+            "step_out" => ResumeMode::StepOut,
+            "continue" => ResumeMode::Continue,
+            _ => return Err(JsValue::from(format!("Invalid resume mode: {}", mode))),
+        };
+        let result = self.resume_internal(mode);
+        to_js_session_step_or_err(result)
+    }
+
+    /// Sets a breakpoint at the given line. If condition is defined, the
+    /// session only pauses at the breakpoint if it evaluates to true. See
+    /// set_breakpoint_internal for what a condition can use.
+    pub fn set_breakpoint(
+        &mut self,
+        line: usize,
+        condition: Option<String>,
+    ) -> Result<(), JsValue> {
+        self.set_breakpoint_internal(line, condition)
+            .map_err(to_js_err)
+    }
+
+    /// Removes the breakpoint at the given line (if any).
+    pub fn remove_breakpoint(&mut self, line: usize) {
+        self.remove_breakpoint_internal(line);
+    }
+
+    /// Stops the current session (if any).
+    pub fn abort(&mut self) {
+        self.session = None;
+    }
+
+    /// Like run_player_script, but returns the result as a plain JS object
+    /// which is created by serializing a Versioned<ScriptResult>. This is
+    /// useful for saving runs or sending them elsewhere (e.g. as JSON).
+    pub fn run_player_script_serialized(
+        &mut self,
+        level_name: &str,
+        unlocked_funcs: js_sys::Array,
+        script: String,
+    ) -> Result<JsValue, JsValue> {
+        let level = LEVELS.get(level_name).unwrap();
+        let unlocked_funcs = to_rust_unlocked_funcs(unlocked_funcs);
         let result = self.run_player_script_internal(level.as_ref(), &unlocked_funcs, script);
         match result {
-            Ok(result) => Ok(js_types::to_js_run_result(&result)),
-            Err(err) => {
-                let message = err.message;
-                let line = err.line.unwrap_or(0);
-                let col = err.col.unwrap_or(0);
-                Err(JsValue::from(js_types::RhaiError { message, line, col }))
-            }
+            Ok(result) => serde_wasm_bindgen::to_value(&Versioned::new(result))
+                .map_err(|err| JsValue::from(err.to_string())),
+            Err(err) => Err(to_js_err(err)),
         }
     }
 }
 
+fn to_rust_unlocked_funcs(unlocked_funcs: js_sys::Array) -> Vec<String> {
+    unlocked_funcs
+        .iter()
+        .map(|x| x.as_string().unwrap())
+        .collect()
+}
+
+fn to_js_err(err: BetterError) -> JsValue {
+    let message = err.message;
+    let line = err.line.unwrap_or(0);
+    let col = err.col.unwrap_or(0);
+    let output = js_types::to_js_output(&err.output);
+    JsValue::from(js_types::RhaiError {
+        message,
+        line,
+        col,
+        output,
+    })
+}
+
+fn to_js_session_step_or_err(
+    result: Result<SessionStep, BetterError>,
+) -> Result<js_types::SessionStep, JsValue> {
+    match result {
+        Ok(step) => Ok(js_types::to_js_session_step(&step)),
+        Err(err) => Err(to_js_err(err)),
+    }
+}
+
+fn to_js_run_result_or_err(
+    result: Result<ScriptResult, BetterError>,
+) -> Result<js_types::RunResult, JsValue> {
+    match result {
+        Ok(result) => Ok(js_types::to_js_run_result(&result)),
+        Err(err) => Err(to_js_err(err)),
+    }
+}
+
 impl Default for Game {
     fn default() -> Self {
         Self::new()
@@ -127,23 +328,79 @@ fn get_avail_funcs(level: &'static dyn Level, unlocked_funcs: &Vec<String>) -> V
 
 impl Game {
     /// An internal implementation of run_player_script which works with Rust types
-    /// instead of JS types. This is not expected to be called from Wasm-land.
-    fn run_player_script_internal(
+    /// instead of JS types. This is not expected to be called from Wasm-land, but
+    /// it is used by native tools such as elara-cli.
+    pub fn run_player_script_internal(
         &mut self,
         level: &'static dyn Level,
         unlocked_funcs: &Vec<String>,
         script: String,
     ) -> Result<ScriptResult, BetterError> {
-        // Run the simulation multiple times, once for each possible initial
-        // state. Return the first result that fails (if any), otherwise return
-        // a random successful result.
-        let mut successes: Vec<ScriptResult> = vec![];
-
         // Shuffle the seeds to keep up the illusion that the game behavior is
         // random.
         let mut seeds: Vec<usize> = (0..(level.initial_states().len())).collect();
         let mut rng = rand::thread_rng();
         seeds.shuffle(&mut rng);
+        self.run_player_script_on_states(level, unlocked_funcs, script, seeds)
+    }
+
+    /// Like run_player_script_internal, but the order in which the possible
+    /// initial states are run is determined by the given RNG seed instead of
+    /// being random. Running the same script with the same seed always gives
+    /// the same result.
+    pub fn run_player_script_with_seed_internal(
+        &mut self,
+        level: &'static dyn Level,
+        unlocked_funcs: &Vec<String>,
+        script: String,
+        seed: u64,
+    ) -> Result<ScriptResult, BetterError> {
+        let mut seeds: Vec<usize> = (0..(level.initial_states().len())).collect();
+        let mut rng = StdRng::seed_from_u64(seed);
+        seeds.shuffle(&mut rng);
+        self.run_player_script_on_states(level, unlocked_funcs, script, seeds)
+    }
+
+    /// Like run_player_script_internal, but only runs the script against the
+    /// initial state with the given index. This can be used to re-run exactly
+    /// the scenario that a previous result came from (see
+    /// ScriptResult::initial_state_index).
+    pub fn run_player_script_for_state_internal(
+        &mut self,
+        level: &'static dyn Level,
+        unlocked_funcs: &Vec<String>,
+        script: String,
+        state_index: usize,
+    ) -> Result<ScriptResult, BetterError> {
+        let num_states = level.initial_states().len();
+        if state_index >= num_states {
+            return Err(BetterError {
+                message: format!(
+                    "Invalid initial state index {} (level {} has {} possible initial states)",
+                    state_index,
+                    level.short_name(),
+                    num_states
+                ),
+                line: None,
+                col: None,
+                output: vec![],
+            });
+        }
+        self.run_player_script_on_states(level, unlocked_funcs, script, vec![state_index])
+    }
+
+    /// Runs the script once for each of the given initial state indexes (in
+    /// order). Returns the first result that fails (if any), otherwise returns
+    /// the first result that doesn't pass the challenge (if any), otherwise
+    /// returns the first result.
+    fn run_player_script_on_states(
+        &mut self,
+        level: &'static dyn Level,
+        unlocked_funcs: &Vec<String>,
+        script: String,
+        seeds: Vec<usize>,
+    ) -> Result<ScriptResult, BetterError> {
+        let mut successes: Vec<ScriptResult> = vec![];
 
         let avail_funcs = get_avail_funcs(level, unlocked_funcs);
 
@@ -153,9 +410,7 @@ impl Game {
             // Drain the channel.
             while self.player_action_rx.clone().borrow().try_recv().is_ok() {}
             // Run the script.
-            let result =
-                self.script_runner
-                    .run(&avail_funcs, level.disabled_functions(), script.as_str());
+            let result = self.script_runner.run(&avail_funcs, level, script.as_str());
             match result {
                 Ok(result) => {
                     // Check if the result passes the main objective.
@@ -192,7 +447,7 @@ impl Game {
         }
 
         // Otherwise, return the first successful result. (This is effectively
-        // a random result since the seeds were shuffled above.)
+        // a random result if the seeds were shuffled.)
         Ok(successes.first().unwrap().clone())
     }
 
@@ -206,16 +461,10 @@ impl Game {
     ) -> Result<ScriptResult, BetterError> {
         let avail_funcs = get_avail_funcs(
             level,
-            &vec![
-                "move_forward".to_string(),
-                "move_backward".to_string(),
-                "turn_left".to_string(),
-                "turn_right".to_string(),
-                "say".to_string(),
-                "press_button".to_string(),
-                "get_orientation".to_string(),
-                "read_data".to_string(),
-            ],
+            &BUILTINS
+                .iter()
+                .map(|builtin| builtin.name.to_string())
+                .collect::<Vec<String>>(),
         );
         self.run_player_script_internal(level, &avail_funcs, script)
     }
@@ -235,7 +484,213 @@ pub fn get_level_data() -> js_sys::Object {
     js_types::to_level_data_obj(LEVELS)
 }
 
+#[wasm_bindgen]
+/// Returns the documentation for all built-in functions.
+pub fn get_builtin_docs() -> js_sys::Array {
+    js_types::to_builtin_docs_array(BUILTINS)
+}
+
 #[wasm_bindgen]
 pub fn new_pos() -> js_types::Pos {
     js_types::Pos { x: 0, y: 0 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A script which succeeds for one of the possible initial states of the
+    // asteroid_strike level and fails for the other.
+    const ASTEROID_STRIKE_SCRIPT: &str = "move_forward(2); turn_left(); move_forward(3);";
+
+    fn all_funcs() -> Vec<String> {
+        BUILTIN_FUNCTIONS.keys().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn run_player_script_with_seed() {
+        let level = LEVELS.get("asteroid_strike").unwrap().as_ref();
+        let mut game = Game::new();
+        for seed in 0..10 {
+            let first = game
+                .run_player_script_with_seed_internal(
+                    level,
+                    &all_funcs(),
+                    ASTEROID_STRIKE_SCRIPT.to_string(),
+                    seed,
+                )
+                .unwrap();
+            let second = game
+                .run_player_script_with_seed_internal(
+                    level,
+                    &all_funcs(),
+                    ASTEROID_STRIKE_SCRIPT.to_string(),
+                    seed,
+                )
+                .unwrap();
+            assert_eq!(first, second);
+            // A failure should always be returned if there is one, regardless of
+            // the seed.
+            assert_eq!(first.outcome, Outcome::Continue);
+            assert_eq!(first.initial_state_index, 1);
+        }
+    }
+
+    #[test]
+    fn run_player_script_for_state() {
+        let level = LEVELS.get("asteroid_strike").unwrap().as_ref();
+        let mut game = Game::new();
+        let avail_funcs = all_funcs();
+
+        let result = game
+            .run_player_script_for_state_internal(
+                level,
+                &avail_funcs,
+                ASTEROID_STRIKE_SCRIPT.to_string(),
+                0,
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+        assert_eq!(result.initial_state_index, 0);
+        assert_eq!(result.states[0], level.initial_states()[0]);
+
+        let result = game
+            .run_player_script_for_state_internal(
+                level,
+                &avail_funcs,
+                ASTEROID_STRIKE_SCRIPT.to_string(),
+                1,
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Continue);
+        assert_eq!(result.initial_state_index, 1);
+        assert_eq!(result.states[0], level.initial_states()[1]);
+
+        // Re-running the scenario from a result should give the same result.
+        let rerun = game
+            .run_player_script_for_state_internal(
+                level,
+                &avail_funcs,
+                ASTEROID_STRIKE_SCRIPT.to_string(),
+                result.initial_state_index,
+            )
+            .unwrap();
+        assert_eq!(rerun, result);
+
+        let err = game
+            .run_player_script_for_state_internal(
+                level,
+                &avail_funcs,
+                ASTEROID_STRIKE_SCRIPT.to_string(),
+                2,
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Invalid initial state index 2 (level asteroid_strike has 2 possible initial states)"
+        );
+    }
+
+    #[test]
+    fn states_share_static_layers() {
+        let level = LEVELS.get("data_point_demo").unwrap().as_ref();
+        let mut game = Game::new();
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                "move_forward(1);\nread_data();\nsay(\"done\");".to_string(),
+            )
+            .unwrap();
+        assert_eq!(result.states.len(), 4);
+
+        // Obstacles never change, so every state should share them.
+        for state in result.states.iter() {
+            assert!(Rc::ptr_eq(&state.obstacles, &result.states[0].obstacles));
+        }
+
+        // Data points are only copied when the reading state changes.
+        let data_points: Vec<_> = result.states.iter().map(|s| &s.data_points).collect();
+        assert!(Rc::ptr_eq(data_points[0], data_points[1]));
+        assert!(!Rc::ptr_eq(data_points[1], data_points[2]));
+        assert!(data_points[2][0].reading);
+        assert!(!Rc::ptr_eq(data_points[2], data_points[3]));
+        assert!(!data_points[3][0].reading);
+    }
+
+    #[test]
+    fn infinite_loops_are_reported_before_running() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let mut game = Game::new();
+        let err = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                "let x = 0;\nloop {\n  x += 1;\n}".to_string(),
+            )
+            .unwrap_err();
+        assert_eq!(err.message, constants::ERR_INFINITE_LOOP);
+        assert_eq!(err.line, Some(2));
+    }
+
+    #[test]
+    fn variable_snapshots() {
+        fn var(name: &str, type_name: &str, value: &str) -> VarSnapshot {
+            VarSnapshot {
+                name: name.to_string(),
+                type_name: type_name.to_string(),
+                value: value.to_string(),
+            }
+        }
+
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let mut game = Game::new();
+        let script = r#"fn turn(a) {
+  turn_right();
+}
+let x = 1;
+move_forward(x);
+x += 1;
+move_forward(x);
+let x = "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyz";
+say(x);
+turn(5);
+"#;
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(level, script.to_string())
+            .unwrap();
+        assert_eq!(result.vars.len(), result.trace.len());
+        assert_eq!(result.vars[0], vec![]);
+        assert_eq!(result.vars[1], vec![var("x", "i64", "1")]);
+        assert_eq!(result.vars[2], vec![var("x", "i64", "2")]);
+        assert_eq!(result.vars[3], vec![var("x", "i64", "2")]);
+        // Shadowed variables are replaced and long values are truncated.
+        assert_eq!(
+            result.vars[4],
+            vec![var(
+                "x",
+                "string",
+                "\"abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvw..."
+            )]
+        );
+        // Inside a function, only the function's own variables are in scope.
+        assert_eq!(result.vars[5], vec![var("a", "i64", "5")]);
+    }
+
+    #[test]
+    fn operation_count() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let mut game = Game::new();
+        let short = game
+            .run_player_script_with_all_funcs_unlocked(level, String::from("move_forward(3);"))
+            .unwrap();
+        let long = game
+            .run_player_script_with_all_funcs_unlocked(
+                level,
+                String::from("for i in 0..3 {\n  move_forward(1);\n}"),
+            )
+            .unwrap();
+        assert!(short.stats.operations > 0);
+        assert!(long.stats.operations > short.stats.operations);
+        assert_eq!(long.stats.max_loop_depth, 1);
+        assert_eq!(long.stats.builtin_calls.get("move_forward"), Some(&1));
+    }
+}
@@ -9,8 +9,9 @@ macro_rules! log {
 #[cfg(not(feature = "wasm"))]
 /// A macro to provide `println!(..)`-style syntax for `console.log` logging.
 macro_rules! log {
-    // On non-wasm platforms, just use println!
+    // On non-wasm platforms, just use eprintln! so that logs don't get mixed
+    // up with the output of native tools (e.g. elara-cli).
     ( $( $t:tt )* ) => {
-        println!( $( $t )* );
+        eprintln!( $( $t )* );
     }
 }
@@ -0,0 +1,224 @@
+use rhai::{ASTFlags, ASTNode, Engine, Expr, OptimizationLevel, Stmt, AST};
+
+/// A small query API over the compiled AST of a script. This is mainly used by
+/// Level::check_challenge to check how a script was written, e.g. whether it
+/// calls a certain function or uses a loop.
+///
+/// Unlike searching the script text, queries are not fooled by comments,
+/// strings, or variable names which happen to contain a keyword or function
+/// name (e.g. `my_read_data`).
+pub struct ScriptQuery {
+    ast: AST,
+}
+
+impl ScriptQuery {
+    /// Compiles the given script. Returns None if the script can't be
+    /// compiled.
+    pub fn new(script: &str) -> Option<ScriptQuery> {
+        // We use a raw engine without optimizations so that the AST reflects
+        // the code as it was written (e.g. constants are not folded and dead
+        // branches are not removed).
+        let mut engine = Engine::new_raw();
+        engine.set_optimization_level(OptimizationLevel::None);
+        engine.compile(script).ok().map(|ast| ScriptQuery { ast })
+    }
+
+    pub fn ast(&self) -> &AST {
+        &self.ast
+    }
+
+    /// Returns true if any node in the AST (including function bodies)
+    /// matches the given predicate.
+    fn any_node(&self, mut predicate: impl FnMut(&ASTNode) -> bool) -> bool {
+        // AST::walk returns false if the walk was stopped early, i.e. if we
+        // found a match.
+        !self.ast.walk(&mut |path| !predicate(path.last().unwrap()))
+    }
+
+    /// Returns the number of places where the function with the given name is
+    /// called. This counts calls in the code, not calls made while running it
+    /// (e.g. a call inside of a loop is only counted once). Function pointers
+    /// to the function (e.g. `Fn("read_data")`) also count as calls, since
+    /// they can be used to call it indirectly.
+    pub fn num_calls(&self, fn_name: &str) -> usize {
+        let mut count = 0;
+        self.ast.walk(&mut |path| {
+            if let Some(ASTNode::Expr(Expr::FnCall(x, _)))
+            | Some(ASTNode::Stmt(Stmt::FnCall(x, _))) = path.last()
+            {
+                if x.namespace.is_empty() && x.name == fn_name {
+                    count += 1;
+                }
+                if x.namespace.is_empty()
+                    && x.name == "Fn"
+                    && matches!(x.args.as_ref(), [Expr::StringConstant(name, _)] if name.as_str() == fn_name)
+                {
+                    count += 1;
+                }
+            }
+            true
+        });
+        count
+    }
+
+    /// Returns true if the function with the given name is called anywhere.
+    pub fn calls(&self, fn_name: &str) -> bool {
+        self.num_calls(fn_name) > 0
+    }
+
+    /// Returns true if the script uses any kind of loop.
+    pub fn uses_loop(&self) -> bool {
+        self.any_node(is_loop)
+    }
+
+    /// Returns true if the script defines any functions.
+    pub fn defines_fn(&self) -> bool {
+        self.ast.iter_fn_def().next().is_some()
+    }
+
+    /// Returns true if the script defines a function with the given name.
+    pub fn defines_fn_named(&self, fn_name: &str) -> bool {
+        self.ast.iter_fn_def().any(|f| f.name == fn_name)
+    }
+
+    /// Returns true if the given keyword is used anywhere. Supports loop,
+    /// while, do, for, fn, if, switch, break, continue, and return. Always
+    /// returns false for anything else.
+    pub fn uses_keyword(&self, keyword: &str) -> bool {
+        match keyword {
+            "fn" => self.defines_fn(),
+            "loop" => self.any_node(|node| {
+                matches!(node, ASTNode::Stmt(Stmt::While(x, _)) if matches!(x.expr, Expr::Unit(_)))
+            }),
+            // Note that "while" is also used in do-while loops (but not in
+            // do-until loops).
+            "while" => self.any_node(|node| match node {
+                ASTNode::Stmt(Stmt::While(x, _)) => !matches!(x.expr, Expr::Unit(_)),
+                ASTNode::Stmt(Stmt::Do(_, flags, _)) => !flags.contains(ASTFlags::NEGATED),
+                _ => false,
+            }),
+            "do" => self.any_node(|node| matches!(node, ASTNode::Stmt(Stmt::Do(..)))),
+            "for" => self.any_node(|node| matches!(node, ASTNode::Stmt(Stmt::For(..)))),
+            "if" => self.any_node(|node| matches!(node, ASTNode::Stmt(Stmt::If(..)))),
+            "switch" => self.any_node(|node| matches!(node, ASTNode::Stmt(Stmt::Switch(..)))),
+            "break" => self.any_node(|node| {
+                matches!(node, ASTNode::Stmt(Stmt::BreakLoop(_, flags, _)) if flags.contains(ASTFlags::BREAK))
+            }),
+            "continue" => self.any_node(|node| {
+                matches!(node, ASTNode::Stmt(Stmt::BreakLoop(_, flags, _)) if !flags.contains(ASTFlags::BREAK))
+            }),
+            "return" => self.any_node(|node| matches!(node, ASTNode::Stmt(Stmt::Return(..)))),
+            _ => false,
+        }
+    }
+
+    /// Returns the max number of loops nested inside of each other (0 if
+    /// there are no loops). Loops inside of a function body are not
+    /// considered to be nested inside of loops which call the function.
+    pub fn max_loop_depth(&self) -> usize {
+        self.max_depth(is_loop)
+    }
+
+    /// Returns the max number of control flow statements (if statements,
+    /// switch statements, and loops) nested inside of each other.
+    pub fn max_nesting_depth(&self) -> usize {
+        self.max_depth(|node| {
+            is_loop(node)
+                || matches!(
+                    node,
+                    ASTNode::Stmt(Stmt::If(..)) | ASTNode::Stmt(Stmt::Switch(..))
+                )
+        })
+    }
+
+    fn max_depth(&self, is_nested: impl Fn(&ASTNode) -> bool) -> usize {
+        let mut max_depth = 0;
+        self.ast.walk(&mut |path| {
+            let depth = path.iter().filter(|node| is_nested(node)).count();
+            max_depth = max_depth.max(depth);
+            true
+        });
+        max_depth
+    }
+}
+
+pub(crate) fn is_loop(node: &ASTNode) -> bool {
+    matches!(
+        node,
+        ASTNode::Stmt(Stmt::While(..)) | ASTNode::Stmt(Stmt::Do(..)) | ASTNode::Stmt(Stmt::For(..))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls() {
+        let query = ScriptQuery::new(
+            r#"// read_data();
+            let my_read_data = "read_data";
+            move_forward(1);
+            if my_read_data == "" {
+                move_forward(2);
+            }"#,
+        )
+        .unwrap();
+        // Comments, strings, and variable names don't count.
+        assert!(!query.calls("read_data"));
+        assert_eq!(query.num_calls("move_forward"), 2);
+        assert!(!query.uses_loop());
+        assert!(!query.defines_fn());
+        assert!(query.uses_keyword("if"));
+        assert_eq!(query.max_nesting_depth(), 1);
+
+        // Function pointers can be used to call a function indirectly, so
+        // they count too.
+        let query = ScriptQuery::new(
+            r#"let f = Fn("read_data");
+            f.call();
+            call(Fn("press_button"));"#,
+        )
+        .unwrap();
+        assert!(query.calls("read_data"));
+        assert!(query.calls("press_button"));
+        assert!(!query.calls("move_forward"));
+    }
+
+    #[test]
+    fn loops_and_functions() {
+        let query = ScriptQuery::new(
+            r"fn go_around() {
+                for i in 0..4 {
+                    if i == 2 {
+                        continue;
+                    }
+                    move_forward(1);
+                }
+            }
+            do {
+                go_around();
+            } until read_data() == 0;",
+        )
+        .unwrap();
+        assert!(query.uses_loop());
+        assert!(query.defines_fn());
+        assert!(query.defines_fn_named("go_around"));
+        assert!(!query.defines_fn_named("go"));
+        assert!(query.calls("go_around"));
+        assert!(query.calls("read_data"));
+        assert!(query.uses_keyword("for"));
+        assert!(query.uses_keyword("do"));
+        assert!(query.uses_keyword("continue"));
+        assert!(!query.uses_keyword("while"));
+        assert!(!query.uses_keyword("loop"));
+        assert!(!query.uses_keyword("break"));
+        assert_eq!(query.max_loop_depth(), 1);
+        assert_eq!(query.max_nesting_depth(), 2);
+    }
+
+    #[test]
+    fn invalid_script() {
+        assert!(ScriptQuery::new("move_forward(").is_none());
+    }
+}
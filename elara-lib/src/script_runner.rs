@@ -1,36 +1,57 @@
-use rhai::debugger::DebuggerCommand;
+use rhai::debugger::{DebuggerCommand, DebuggerEvent};
 use rhai::{
-    ASTNode, Dynamic, Engine, EvalAltResult, EvalContext, Expr, FnCallExpr, Position, Stmt,
+    ASTNode, Dynamic, Engine, EvalAltResult, EvalContext, Expr, FnCallExpr, OptimizationLevel,
+    Position, Scope, Stmt,
 };
-use std::cell::RefCell;
-use std::convert::TryInto;
-use std::io::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::rc::Rc;
 use std::sync::mpsc;
 use std::vec;
 
-use crate::actors::{Action, MoveDirection, TurnDirection};
-use crate::better_errors::{convert_err, BetterError};
-use crate::constants::{
-    BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL, ERR_NO_BUTTON, ERR_NO_DATA_POINT,
-    ERR_SIMULATION_END,
-};
-use crate::levels::Outcome;
-use crate::simulation::{
-    get_adjacent_button, get_adjacent_point, Orientation, Pos, Simulation, State,
-};
+use crate::actors::{ActionResult, PlayerAction};
+use crate::better_errors::{convert_err, convert_level_restriction_err, BetterError};
+use crate::builtins::{BuiltinEnv, BUILTINS, BUILTIN_FUNCTIONS};
+use crate::code_metrics::{compute_code_metrics, CodeMetrics};
+use crate::constants::{ERR_POSITION_INDEX, ERR_POSITION_OUT_OF_RANGE, ERR_SIMULATION_END};
+use crate::infinite_loop_detector::check_infinite_loops;
+use crate::levels::{Level, Outcome};
+use crate::semicolon_checker::check_semicolons;
+use crate::simulation::{Pos, Simulation, State};
 
 /// Responsible for running user scripts and coordinating communication
 /// between the Rhai Engine and the Simulation.
 pub struct ScriptRunner {
     simulation: Rc<RefCell<Simulation>>,
     /// Used to send actions from the script to the PlayerChannelActor.
-    player_action_tx: Rc<RefCell<mpsc::Sender<Action>>>,
+    player_action_tx: Rc<RefCell<mpsc::Sender<PlayerAction>>>,
+    /// Used to receive the result of each action from the PlayerChannelActor.
+    action_result_rx: Rc<RefCell<mpsc::Receiver<ActionResult>>>,
     /// Used for building up the trace of positions for each step in the simulation.
     pending_trace: Rc<RefCell<Vec<Vec<usize>>>>,
+    /// Used for building up the variables in scope for each step in the
+    /// simulation. Always has the same length as pending_trace.
+    pending_vars: Rc<RefCell<Vec<Vec<VarSnapshot>>>>,
+    /// The variables in scope for the latest call to a built-in function at
+    /// each depth of the call stack (see BuiltinEnv::call_vars).
+    call_vars: Rc<RefCell<Vec<Vec<VarSnapshot>>>>,
+    /// Used for building up the output from print and debug.
+    pending_output: Rc<RefCell<Vec<OutputLine>>>,
+    /// The line of the latest call to print. Rhai doesn't tell the print
+    /// callback where it was called from, so this is set by the debugger.
+    print_line: Rc<Cell<usize>>,
+    /// Where the next run should pause (if anywhere).
+    pause_target: Option<PauseTarget>,
+    /// Where the last run was paused (if it was).
+    pause: Rc<RefCell<Option<Pause>>>,
+    /// Errors from evaluating breakpoint conditions during the last run (at
+    /// most one per line).
+    condition_errors: Rc<RefCell<Vec<BetterError>>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ScriptResult {
     /// The state corresponding to each step in the simulation.
     pub states: Vec<State>,
@@ -43,49 +64,188 @@ pub struct ScriptResult {
     /// in each step corresponds to the *innermost* statement (i.e. the bottom
     /// of the call stack, typically the body of the function being called).
     pub trace: Vec<Vec<usize>>,
+    /// The variables in scope corresponding to each step in the simulation.
+    /// Like trace, this is captured when a function call causes the
+    /// simulation to step forward, and the variables for step 0 are always
+    /// empty.
+    #[serde(default)]
+    pub vars: Vec<Vec<VarSnapshot>>,
+    /// The output from any calls to print and debug, in the order they
+    /// happened.
+    #[serde(default)]
+    pub output: Vec<OutputLine>,
     pub outcome: Outcome,
     pub stats: ScriptStats,
     pub passes_challenge: bool,
+    /// The index of the initial state (out of all possible initial states for
+    /// the level) that this result came from. Can be used to re-run exactly
+    /// the same scenario (see Game::run_player_script_for_state_internal).
+    #[serde(default)]
+    pub initial_state_index: usize,
+}
+
+/// A snapshot of a single variable at some point while running a script.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VarSnapshot {
+    pub name: String,
+    pub type_name: String,
+    /// The value of the variable formatted as a string. Long values are
+    /// truncated to MAX_VAR_VALUE_LEN characters.
+    pub value: String,
+}
+
+/// A single call to print or debug while running a script.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputLine {
+    /// The step of the simulation at which the output happened (i.e. an index
+    /// into ScriptResult::states).
+    pub step: usize,
+    /// The line of code which produced the output (0 if unknown).
+    pub line: usize,
+    pub kind: OutputKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputKind {
+    Print,
+    Debug,
+}
+
+/// The max length of VarSnapshot::value (not including the "..." that is
+/// added to truncated values).
+const MAX_VAR_VALUE_LEN: usize = 50;
+
+/// A line breakpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub line: usize,
+    /// An optional Rhai expression. If present, the script only pauses at the
+    /// breakpoint if the expression evaluates to true in the current scope.
+    pub condition: Option<String>,
+}
+
+/// Determines where a script should pause (see ScriptRunner::set_pause_target).
+/// The script pauses right before running the first statement which matches
+/// all of the criteria.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PauseTarget {
+    /// If set, only statements after the statement with this index are
+    /// considered (see Pause::stmt_index).
+    pub after: Option<usize>,
+    /// If set, only statements which run once the simulation has reached this
+    /// step are considered.
+    pub after_step: Option<usize>,
+    /// If set, only statements with a call stack depth less than or equal to
+    /// this are considered.
+    pub max_depth: Option<usize>,
+    /// If set, only statements on a line with a breakpoint are considered.
+    pub breakpoints: Option<Vec<Breakpoint>>,
+}
+
+/// Describes where a script was paused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pause {
+    /// The index of the statement the script was paused at. Every statement
+    /// that runs is counted (including statements inside of functions and
+    /// statements that run more than once in a loop). Since scripts are
+    /// deterministic, this can be used to find the same place again when
+    /// re-running the script.
+    pub stmt_index: usize,
+    pub line: usize,
+    /// The depth of the call stack (0 for the top level of the script).
+    pub depth: usize,
+    /// The function calls that are currently running, outermost first.
+    pub call_stack: Vec<StackFrame>,
+    /// The variables in scope.
+    pub vars: Vec<VarSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub fn_name: String,
+    /// The line that the function was called from.
+    pub line: usize,
 }
 
 impl ScriptRunner {
     pub fn new(
         simulation: Rc<RefCell<Simulation>>,
-        player_action_tx: Rc<RefCell<mpsc::Sender<Action>>>,
+        player_action_tx: Rc<RefCell<mpsc::Sender<PlayerAction>>>,
+        action_result_rx: Rc<RefCell<mpsc::Receiver<ActionResult>>>,
     ) -> ScriptRunner {
         ScriptRunner {
             simulation,
             player_action_tx,
+            action_result_rx,
             // Start with empty line numbers for step 0. This ensures that
             // the trace aligns with simulation steps. Or in other words, at
             // step 0 there is not active line number.
             pending_trace: Rc::new(RefCell::new(vec![vec![]])),
+            pending_vars: Rc::new(RefCell::new(vec![vec![]])),
+            call_vars: Rc::new(RefCell::new(vec![])),
+            pending_output: Rc::new(RefCell::new(vec![])),
+            print_line: Rc::new(Cell::new(0)),
+            pause_target: None,
+            pause: Rc::new(RefCell::new(None)),
+            condition_errors: Rc::new(RefCell::new(vec![])),
         }
     }
 
+    /// Sets where subsequent runs should pause. If the pause target is
+    /// reached, the script stops running right away, as if the simulation had
+    /// ended. Use take_pause to check whether and where it was paused.
+    pub fn set_pause_target(&mut self, pause_target: Option<PauseTarget>) {
+        self.pause_target = pause_target;
+    }
+
+    /// Returns where the last run was paused (if it was).
+    pub fn take_pause(&mut self) -> Option<Pause> {
+        self.pause.borrow_mut().take()
+    }
+
+    /// Returns any errors from evaluating breakpoint conditions during the
+    /// last run. A breakpoint whose condition can't be evaluated is treated as
+    /// not hit, so these errors never affect the outcome of the script.
+    pub fn take_condition_errors(&mut self) -> Vec<BetterError> {
+        self.condition_errors.take()
+    }
+
     /// Runs the user script and returns the result or error.
     ///
     /// avail_funcs is the list of functions that are available to the user.
-    /// Some levels have restrictions on which functions are available.
+    /// Some levels have restrictions on which functions are available. The
+    /// level also determines the engine limits and which language features
+    /// are disabled.
     pub fn run(
         &mut self,
         avail_funcs: &[String],
-        disabled_funcs: &'static [&'static str],
+        level: &'static dyn Level,
         script: &str,
     ) -> Result<ScriptResult, BetterError> {
+        let disabled_funcs = level.disabled_functions();
+        let limits = level.engine_limits();
+        let disabled_features = level.disabled_features();
+
         // Create and configure the Rhai engine.
-        let mut engine = Engine::new();
-        set_engine_config(&mut engine);
-        set_engine_safeguards(&mut engine);
-        set_print_fn(&mut engine);
-        self.register_debugger(&mut engine, avail_funcs);
-        register_custom_types(&mut engine);
+        self.pause.borrow_mut().take();
+        self.condition_errors.borrow_mut().clear();
+        let mut engine = new_level_engine(level);
+        self.capture_output(&mut engine);
+        let operations = Rc::new(Cell::new(0));
+        count_operations(&mut engine, operations.clone());
+        self.register_debugger(&mut engine, avail_funcs, new_condition_engine(&limits));
         self.register_player_funcs(&mut engine, avail_funcs);
 
         // Try compiling the AST first and check for lexer/parser errors.
         let ast = match engine.compile(script) {
             Err(parse_err) => {
                 let alt_result = Box::new(EvalAltResult::ErrorParsing(*parse_err.0, parse_err.1));
+                if let Some(err) =
+                    convert_level_restriction_err(&disabled_features, &limits, &alt_result)
+                {
+                    return Err(err);
+                }
                 return Err(convert_err(
                     avail_funcs,
                     disabled_funcs,
@@ -96,6 +256,12 @@ impl ScriptRunner {
             Ok(ast) => ast,
         };
 
+        // Code metrics are computed from the AST as it was written. After
+        // that, we optimize the AST the same way Engine::compile normally
+        // would (see set_engine_config).
+        let code_metrics = compute_code_metrics(&ast);
+        let ast = engine.optimize_ast(&Scope::new(), ast, OptimizationLevel::Simple);
+
         // Next use a custom check for semicolons at the end of each line
         // (except for blocks or inside comments).
         match check_semicolons(script) {
@@ -110,10 +276,19 @@ impl ScriptRunner {
             }
         }
 
-        // Reset pending_trace. We always start with an empty list for step 0 (i.e. no
-        // active line numbers).
+        // Check for loops and recursion which can never end before running
+        // anything.
+        check_infinite_loops(&ast)?;
+
+        // Reset pending_trace and pending_vars. We always start with an empty list
+        // for step 0 (i.e. no active line numbers and no variables).
         self.pending_trace.borrow_mut().clear();
         self.pending_trace.borrow_mut().push(vec![]);
+        self.pending_vars.borrow_mut().clear();
+        self.pending_vars.borrow_mut().push(vec![]);
+        self.call_vars.borrow_mut().clear();
+        self.pending_output.borrow_mut().clear();
+        self.print_line.set(0);
 
         // Make engine non-mutable now that we are done configuring it.
         // This is a safety measure to prevent scripts from mutating the
@@ -123,42 +298,53 @@ impl ScriptRunner {
         // If the AST looks good, try running the script.
         if let Err(err) = engine.run_ast(&ast) {
             match *err {
+                _ if is_simulation_end(&err) => {
+                    // Special case for when the simulation ends before the script
+                    // finishes running (possibly inside of a function call). This is
+                    // not actually an error, so we continue.
+                }
                 EvalAltResult::ErrorRuntime(_, _) => {
-                    if err.to_string().contains(ERR_SIMULATION_END) {
-                        // Special case for when the simulation ends before the script
-                        // finishes running. This is not actually an error, so we continue.
-                    } else {
-                        // Other runtime errors should be considered a failure.
-                        // In this case we still return all the states and trace.
-                        let outcome = Outcome::Failure(err.to_string());
-                        let states = self.simulation.borrow().get_history();
-                        let trace = self.pending_trace.borrow().to_vec();
-                        let stats = compute_stats(&engine, script, &states);
-                        return Ok(ScriptResult {
-                            states,
-                            trace,
-                            outcome,
-                            stats,
-                            passes_challenge: false,
-                        });
-                    }
+                    // Other runtime errors should be considered a failure.
+                    // In this case we still return all the states and trace.
+                    let outcome = Outcome::Failure(err.to_string());
+                    let states = self.simulation.borrow().get_history();
+                    let trace = self.pending_trace.borrow().to_vec();
+                    let vars = self.pending_vars.borrow().to_vec();
+                    let output = self.pending_output.borrow().to_vec();
+                    let stats =
+                        compute_stats(&engine, script, code_metrics, &states, operations.get());
+                    return Ok(ScriptResult {
+                        states,
+                        trace,
+                        vars,
+                        output,
+                        outcome,
+                        stats,
+                        passes_challenge: false,
+                        initial_state_index: self.simulation.borrow().initial_state_index(),
+                    });
                 }
                 _ => {
-                    // For all other kinds of errors, we return the error.
-                    return Err(convert_err(
-                        avail_funcs,
-                        disabled_funcs,
-                        script.to_string(),
-                        err,
-                    ));
+                    // For all other kinds of errors, we return the error. There
+                    // is no ScriptResult in this case, so any output is
+                    // returned along with the error.
+                    let output = self.pending_output.borrow().to_vec();
+                    let err = match convert_level_restriction_err(&disabled_features, &limits, &err)
+                    {
+                        Some(err) => err,
+                        None => convert_err(avail_funcs, disabled_funcs, script.to_string(), err),
+                    };
+                    return Err(BetterError { output, ..err });
                 }
             }
         }
 
         let states = self.simulation.borrow().get_history();
         let positions = self.pending_trace.borrow().to_vec();
+        let vars = self.pending_vars.borrow().to_vec();
+        let output = self.pending_output.borrow().to_vec();
         let outcome = self.simulation.borrow().last_outcome();
-        let stats = compute_stats(&engine, script, &states);
+        let stats = compute_stats(&engine, script, code_metrics, &states, operations.get());
 
         // If the outcome is success, and the level has a challenge,
         // check if it was passed.
@@ -173,48 +359,78 @@ impl ScriptRunner {
         Ok(ScriptResult {
             states,
             trace: positions,
+            vars,
+            output,
             outcome,
             stats,
             passes_challenge,
+            initial_state_index: self.simulation.borrow().initial_state_index(),
         })
     }
 
-    fn register_debugger(&self, engine: &mut Engine, avail_funcs: &[String]) {
-        let pending_trace = self.pending_trace.clone();
+    fn register_debugger(
+        &self,
+        engine: &mut Engine,
+        avail_funcs: &[String],
+        condition_engine: Engine,
+    ) {
+        let call_vars = self.call_vars.clone();
         let simulation = self.simulation.clone();
         let avail_funcs = avail_funcs.to_owned();
+        let pause_target = self.pause_target.clone();
+        let pause = self.pause.clone();
+        let condition_errors = self.condition_errors.clone();
+        let print_line = self.print_line.clone();
+        // The number of statements reached so far. See Pause::stmt_index.
+        let stmt_count = Cell::new(0);
         // Note(albrow): register_debugger is not actually deprecated. The Rhai maintainers
         // have decided to use the "deprecated" attribute to indicate that the API is not
         // stable.
         #[allow(deprecated)]
         engine.register_debugger(
             |_engine, debugger| debugger,
-            move |context, _event, node, _source, pos| {
-                // log!("{:?}: {:?} at {}", _event, node, pos);
-                match node {
-                    ASTNode::Expr(Expr::FnCall(fn_call_expr, ..)) => {
-                        // log!(
-                        //     "Match on function call expression: {:?}",
-                        //     fn_call_expr.name.as_str()
-                        // );
-                        Self::handle_debugger_function_call(
-                            &avail_funcs,
-                            pending_trace.clone(),
-                            context,
-                            pos,
-                            fn_call_expr,
-                        )
+            move |context, event, node, _source, pos| {
+                // log!("{:?}: {:?} at {}", event, node, pos);
+                if simulation.borrow().reached_step_limit() {
+                    // Stop running the script as soon as the step limit is reached.
+                    // This needs to happen before any more functions are called,
+                    // since they would run without stepping the simulation forward.
+                    return Err(ERR_SIMULATION_END.into());
+                }
+                if let ASTNode::Stmt(stmt) = node {
+                    let is_step = matches!(event, DebuggerEvent::Start | DebuggerEvent::Step);
+                    let is_block = matches!(stmt, Stmt::Block(..) | Stmt::Noop(..));
+                    if is_step && !is_block {
+                        let stmt_index = stmt_count.get();
+                        stmt_count.set(stmt_index + 1);
+                        if let Some(target) = &pause_target {
+                            let step = simulation.borrow().history_len() - 1;
+                            if let Some(new_pause) = check_pause_target(
+                                target,
+                                &condition_engine,
+                                &condition_errors,
+                                &context,
+                                (stmt_index, step),
+                                pos,
+                            ) {
+                                // Stop running the script. This works the same way as
+                                // reaching the step limit.
+                                *pause.borrow_mut() = Some(new_pause);
+                                return Err(ERR_SIMULATION_END.into());
+                            }
+                        }
                     }
-                    ASTNode::Stmt(Stmt::FnCall(fn_call_expr, ..)) => {
-                        // log!(
-                        //     "Match on function call statement: {:?}",
-                        //     fn_call_expr.name.as_str()
-                        // );
+                }
+                match node {
+                    ASTNode::Expr(Expr::FnCall(fn_call_expr, ..))
+                    | ASTNode::Stmt(Stmt::FnCall(fn_call_expr, ..)) => {
+                        if fn_call_expr.name == "print" {
+                            print_line.set(pos.line().unwrap_or(0));
+                        }
                         Self::handle_debugger_function_call(
                             &avail_funcs,
-                            pending_trace.clone(),
+                            &call_vars,
                             context,
-                            pos,
                             fn_call_expr,
                         )
                     }
@@ -232,456 +448,83 @@ impl ScriptRunner {
         );
     }
 
-    // The whole purpose of handling function calls in the debugger is to
-    // track which line of code should currently be considered "active". In
-    // practice, this is not as straightforward as it sounds. For example,
-    // for function calls like move_right() we need to evaluate the argument
-    // to determine how many steps the rover should move and detect the current
-    // orientation to determine how many steps the rover should turn. This requires
-    // hooking into the current EvalContext and evaluating some custom code/custom
-    // AST at runtime.
+    /// Captures the output of print and debug so that it can be shown to the
+    /// user (see ScriptResult::output).
+    fn capture_output(&self, engine: &mut Engine) {
+        let simulation = self.simulation.clone();
+        let output = self.pending_output.clone();
+        let print_line = self.print_line.clone();
+        engine.on_print(move |s: &str| {
+            output.borrow_mut().push(OutputLine {
+                step: simulation.borrow().history_len() - 1,
+                line: print_line.get(),
+                kind: OutputKind::Print,
+                text: s.to_string(),
+            });
+        });
+
+        let simulation = self.simulation.clone();
+        let output = self.pending_output.clone();
+        engine.on_debug(move |s: &str, _source, pos: Position| {
+            output.borrow_mut().push(OutputLine {
+                step: simulation.borrow().history_len() - 1,
+                line: pos.line().unwrap_or(0),
+                kind: OutputKind::Debug,
+                text: s.to_string(),
+            });
+        });
+    }
+
+    // Built-in functions record the trace themselves each time they step the
+    // simulation forward (see BuiltinEnv::perform). However, native functions
+    // can't access the scope, so we need to snapshot the variables here, right
+    // before the function is called.
     fn handle_debugger_function_call(
         avail_funcs: &[String],
-        pending_trace: Rc<RefCell<Vec<Vec<usize>>>>,
+        call_vars: &RefCell<Vec<Vec<VarSnapshot>>>,
         context: EvalContext,
-        pos: Position,
         fn_call_expr: &FnCallExpr,
     ) -> Result<DebuggerCommand, Box<EvalAltResult>> {
-        if !(avail_funcs.contains(&fn_call_expr.name.as_str().to_string())) {
-            // If the function is not in the list of available functions, we
-            // ignore it. Some levels may ask users to implement functions that
-            // are later considered built-in.
+        let name = fn_call_expr.name.as_str();
+        if !(BUILTIN_FUNCTIONS.contains_key(name) && avail_funcs.iter().any(|f| f == name)) {
+            // Other function calls (including operators and user-defined
+            // functions) never step the simulation forward on their own.
             return Ok(DebuggerCommand::StepInto);
         }
 
-        // Compute the full list of line numbers including the current line
-        // number and any function calls from higher up in the call stack.
-        let mut trace_lines = context
-            .global_runtime_state()
-            .debugger()
-            .call_stack()
-            .iter()
-            .map(|stack| stack.pos.line().unwrap())
-            .collect::<Vec<usize>>();
-        let line = pos.line().unwrap();
-        trace_lines.push(line);
-
-        match fn_call_expr.name.as_str() {
-            "wait" => {
-                // The number of steps here depends on the argument. E.g. wait(3) means
-                // that this line should be considered "active" for 3 steps.
-                let duration = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                for _ in 0..duration {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "turn_right" | "turn_left" => {
-                pending_trace.borrow_mut().push(trace_lines.clone());
-                Ok(DebuggerCommand::StepInto)
-            }
-            "move_forward" | "move_backward" => {
-                // For move_forward and move_backward, the number of steps is just based
-                // on the argument.
-                let move_steps = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                for _ in 0..move_steps {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "move_right" => {
-                // For move_right and other directional move functions, the number of steps
-                // is based on: (a) the number of spaces to move, and (b) the current
-                // orientation of the rover.
-                let move_steps = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                let curr_orientation = eval_curr_orientation(&context).unwrap();
-                let rotation_steps = match curr_orientation {
-                    Orientation::Right => 0,
-                    Orientation::Up => 1,
-                    Orientation::Left => 2,
-                    Orientation::Down => 1,
-                };
-                let total_steps = move_steps + rotation_steps;
-                for _ in 0..total_steps {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "move_left" => {
-                let move_steps = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                let curr_orientation = eval_curr_orientation(&context).unwrap();
-                let rotation_steps = match curr_orientation {
-                    Orientation::Right => 2,
-                    Orientation::Up => 1,
-                    Orientation::Left => 0,
-                    Orientation::Down => 1,
-                };
-                let total_steps = move_steps + rotation_steps;
-                for _ in 0..total_steps {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "move_up" => {
-                let move_steps = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                let curr_orientation = eval_curr_orientation(&context).unwrap();
-                let rotation_steps = match curr_orientation {
-                    Orientation::Right => 1,
-                    Orientation::Up => 0,
-                    Orientation::Left => 1,
-                    Orientation::Down => 2,
-                };
-                let total_steps = move_steps + rotation_steps;
-                for _ in 0..total_steps {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "move_down" => {
-                let move_steps = eval_call_args_as_int(&context, fn_call_expr).unwrap_or(0);
-                let curr_orientation = eval_curr_orientation(&context).unwrap();
-                let rotation_steps = match curr_orientation {
-                    Orientation::Right => 1,
-                    Orientation::Up => 2,
-                    Orientation::Left => 1,
-                    Orientation::Down => 0,
-                };
-                let total_steps = move_steps + rotation_steps;
-                for _ in 0..total_steps {
-                    pending_trace.borrow_mut().push(trace_lines.clone());
-                }
-                Ok(DebuggerCommand::StepInto)
-            }
-            "say" => {
-                // The say function always has a duration of one step.
-                pending_trace.borrow_mut().push(trace_lines.clone());
-                Ok(DebuggerCommand::StepInto)
-            }
-            "read_data" => {
-                // The read_data function always has a duration of one step.
-                pending_trace.borrow_mut().push(trace_lines.clone());
-                Ok(DebuggerCommand::StepInto)
-            }
-            "press_button" => {
-                // The press_button function always has a duration of one step.
-                pending_trace.borrow_mut().push(trace_lines.clone());
-                Ok(DebuggerCommand::StepInto)
-            }
-            _ => Ok(DebuggerCommand::StepInto),
-        }
+        let depth = context.global_runtime_state().debugger().call_stack().len();
+        let mut call_vars = call_vars.borrow_mut();
+        call_vars.resize(depth + 1, vec![]);
+        call_vars[depth] = snapshot_vars(&context);
+        Ok(DebuggerCommand::StepInto)
     }
 
-    /// Register functions for each action that can exist in a user script.
-    /// Each function will simply send the corresponding action(s) through
-    /// the channel.
+    /// Register the built-in functions that are available to the user.
     ///
     /// avail_funcs is the list of functions that are available to the user.
     /// Some levels have restrictions on which functions are available.
     fn register_player_funcs(&self, engine: &mut Engine, avail_funcs: &[String]) {
-        // For each function, we clone and borrow some pointers (e.g. simulation and
+        // Each function gets its own clone of some pointers (e.g. simulation and
         // player_action_tx). This is a workaround due to the fact that the Rhai engine
         // does not allow for mutable non-static references in handlers. See
         // https://rhai.rs/book/patterns/control.html for more context.
-        //
-        //
-        // Note(albrow): If you add new functions here, don't forget to add them to
-        // BUILTIN_FUNCTIONS in constants.rs.
-        //
-        if avail_funcs.contains(&"wait".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("wait", move |duration: i64| {
-                for _ in 0..duration {
-                    tx.borrow().send(Action::Wait).unwrap();
-                    simulation.borrow_mut().step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"turn_right".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("turn_right", move || {
-                tx.borrow()
-                    .send(Action::Turn(TurnDirection::Right))
-                    .unwrap();
-                simulation.borrow_mut().step_forward();
-            });
-        }
-        if avail_funcs.contains(&"turn_left".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("turn_left", move || {
-                tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                simulation.borrow_mut().step_forward();
-            });
-        }
-        if avail_funcs.contains(&"move_forward".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_forward", move |spaces: i64| {
-                // For move_forward and move_backward, the number of steps is
-                // directly determined by the argument.
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Forward))
-                        .unwrap();
-                    simulation.borrow_mut().step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"move_backward".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_backward", move |spaces: i64| {
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Backward))
-                        .unwrap();
-                    simulation.borrow_mut().step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"move_right".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_right", move |spaces: i64| {
-                // move_right (and other directional move functions) work by first
-                // rotating the rover to correct orientation, and then moving
-                // forward the given number of spaces.
-                let mut sim = simulation.borrow_mut();
-                match sim.curr_state().player.facing {
-                    Orientation::Right => {}
-                    Orientation::Up => {
-                        tx.borrow()
-                            .send(Action::Turn(TurnDirection::Right))
-                            .unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Left => {
-                        for _ in 0..2 {
-                            tx.borrow()
-                                .send(Action::Turn(TurnDirection::Right))
-                                .unwrap();
-                            sim.step_forward();
-                        }
-                    }
-                    Orientation::Down => {
-                        tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                        sim.step_forward();
-                    }
-                }
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Forward))
-                        .unwrap();
-                    sim.step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"move_left".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_left", move |spaces: i64| {
-                let mut sim = simulation.borrow_mut();
-                match sim.curr_state().player.facing {
-                    Orientation::Right => {
-                        for _ in 0..2 {
-                            tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                            sim.step_forward();
-                        }
-                    }
-                    Orientation::Up => {
-                        tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Left => {}
-                    Orientation::Down => {
-                        tx.borrow()
-                            .send(Action::Turn(TurnDirection::Right))
-                            .unwrap();
-                        sim.step_forward();
-                    }
-                }
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Forward))
-                        .unwrap();
-                    sim.step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"move_up".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_up", move |spaces: i64| {
-                let mut sim = simulation.borrow_mut();
-                match sim.curr_state().player.facing {
-                    Orientation::Right => {
-                        tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Up => {}
-                    Orientation::Left => {
-                        tx.borrow()
-                            .send(Action::Turn(TurnDirection::Right))
-                            .unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Down => {
-                        for _ in 0..2 {
-                            tx.borrow()
-                                .send(Action::Turn(TurnDirection::Right))
-                                .unwrap();
-                            sim.step_forward();
-                        }
-                    }
-                }
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Forward))
-                        .unwrap();
-                    sim.step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"move_down".to_string()) {
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("move_down", move |spaces: i64| {
-                let mut sim = simulation.borrow_mut();
-                match sim.curr_state().player.facing {
-                    Orientation::Right => {
-                        tx.borrow()
-                            .send(Action::Turn(TurnDirection::Right))
-                            .unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Up => {
-                        for _ in 0..2 {
-                            tx.borrow()
-                                .send(Action::Turn(TurnDirection::Right))
-                                .unwrap();
-                            sim.step_forward();
-                        }
-                    }
-                    Orientation::Left => {
-                        tx.borrow().send(Action::Turn(TurnDirection::Left)).unwrap();
-                        sim.step_forward();
-                    }
-                    Orientation::Down => {}
-                }
-                for _ in 0..spaces {
-                    tx.borrow()
-                        .send(Action::Move(MoveDirection::Forward))
-                        .unwrap();
-                    sim.step_forward();
-                }
-            });
-        }
-        if avail_funcs.contains(&"get_position".to_string()) {
-            // get_position returns the current position of the player as an array
-            // of [x, y].
-            let simulation = self.simulation.clone();
-            engine.register_fn("get_position", move || -> Dynamic {
-                let pos = simulation.borrow().curr_state().player.pos;
-                rhai::Array::from(vec![
-                    Dynamic::from(pos.x as i64),
-                    Dynamic::from(pos.y as i64),
-                ])
-                .into()
-            });
-        }
-        if avail_funcs.contains(&"get_orientation".to_string()) {
-            // get_orientation returns the direction that the player is currently
-            // facing as a string.
-            let simulation = self.simulation.clone();
-            engine.register_fn("get_orientation", move || -> Dynamic {
-                let orientation = simulation.borrow().curr_state().player.facing;
-                let orientation_str = match orientation {
-                    Orientation::Up => "up",
-                    Orientation::Down => "down",
-                    Orientation::Left => "left",
-                    Orientation::Right => "right",
-                };
-                Dynamic::from(orientation_str)
-            });
-        }
-        if avail_funcs.contains(&"say".to_string()) {
-            // say causes the rover to say (i.e. display in a speech bubble)
-            // the given expression.
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("say", move |s: Dynamic| {
-                let message = s.to_string();
-                tx.borrow().send(Action::Say(message)).unwrap();
-                simulation.borrow_mut().step_forward();
-            });
-        }
-        if avail_funcs.contains(&"add".to_string()) {
-            // adds two numbers together. Used for teaching about function outputs.
-            // (Normally you would use the + operator instead.)
-            engine.register_fn("add", |a: i64, b: i64| -> i64 { a + b });
-        }
-        if avail_funcs.contains(&"read_data".to_string()) {
-            // read_data returns the data held by an adjacent data point.
-            // If there is no data point adjacent to the player, it returns
-            // an error.
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn(
-                "read_data",
-                move || -> Result<Dynamic, Box<EvalAltResult>> {
-                    tx.borrow().send(Action::ReadData).unwrap();
-                    simulation.borrow_mut().step_forward();
-
-                    let state = simulation.borrow().curr_state();
-                    let pos = &state.player.pos;
-                    if let Some(point_index) = get_adjacent_point(&state, pos) {
-                        let data = state.data_points[point_index].data.clone();
-                        Ok(data.into())
-                    } else {
-                        // TODO(albrow): Can we determine the line number for the error message?
-                        Err(ERR_NO_DATA_POINT.into())
-                    }
-                },
-            );
-        }
-        if avail_funcs.contains(&"press_button".to_string()) {
-            // press_button presses an adjacent button. If there is no button
-            // adjacent to the player, it returns an error.
-            let tx = self.player_action_tx.clone();
-            let simulation = self.simulation.clone();
-            engine.register_fn("press_button", move || -> Result<(), Box<EvalAltResult>> {
-                let state = simulation.borrow().curr_state();
-                let pos = &state.player.pos;
-                if get_adjacent_button(&state, pos).is_some() {
-                    tx.borrow().send(Action::PressButton).unwrap();
-                    simulation.borrow_mut().step_forward();
-                    Ok(())
-                } else {
-                    // TODO(albrow): Can we determine the line number for the error message?
-                    Err(ERR_NO_BUTTON.into())
-                }
-            });
+        let env = BuiltinEnv {
+            simulation: self.simulation.clone(),
+            player_action_tx: self.player_action_tx.clone(),
+            action_result_rx: self.action_result_rx.clone(),
+            trace: self.pending_trace.clone(),
+            vars: self.pending_vars.clone(),
+            call_vars: self.call_vars.clone(),
+        };
+        for builtin in BUILTINS {
+            if avail_funcs.iter().any(|f| f == builtin.name) {
+                builtin.register(engine, &env);
+            }
         }
-        // Our debugger hook *always* needs a way to get the current orientation, so
-        // we use this special function even it if the get_orientation function is not
-        // available for the user.
-        let simulation = self.simulation.clone();
-        engine.register_fn("__get_orientation__", move || -> Dynamic {
-            let orientation = simulation.borrow().curr_state().player.facing;
-            let orientation_str = match orientation {
-                Orientation::Up => "up",
-                Orientation::Down => "down",
-                Orientation::Left => "left",
-                Orientation::Right => "right",
-            };
-            Dynamic::from(orientation_str)
-        });
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ScriptStats {
     // Length of the script in bytes.
     pub code_len: usize,
@@ -689,328 +532,376 @@ pub struct ScriptStats {
     pub energy_used: u32,
     // Amount of time (i.e. number of steps) taken to execute the script.
     pub time_taken: u32,
+    // Number of operations performed by the Rhai engine while running the
+    // script (see EngineLimits::max_operations).
+    #[serde(default)]
+    pub operations: u64,
+    // The following are computed from the code itself. See CodeMetrics for
+    // details.
+    #[serde(default)]
+    pub statements: usize,
+    #[serde(default)]
+    pub ast_nodes: usize,
+    #[serde(default)]
+    pub builtin_calls: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub user_functions: usize,
+    #[serde(default)]
+    pub max_loop_depth: usize,
+    #[serde(default)]
+    pub cyclomatic_complexity: usize,
+}
+
+/// Limits on the resources a script is allowed to use. Scripts which go over
+/// any of these limits fail with an error. See https://rhai.rs/book/safety/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineLimits {
+    /// The max number of operations (roughly, the number of expressions and
+    /// statements that are evaluated).
+    pub max_operations: u64,
+    /// The max depth of nested function calls.
+    pub max_call_levels: usize,
+    /// The max number of items in an array.
+    pub max_array_size: usize,
+    /// The max length of a string in bytes.
+    pub max_string_size: usize,
+}
+
+impl Default for EngineLimits {
+    fn default() -> Self {
+        EngineLimits {
+            max_operations: 10_000,
+            max_call_levels: 32,
+            max_array_size: 100,
+            max_string_size: 1_000,
+        }
+    }
+}
+
+/// A language construct which can be disabled for a level (e.g. to focus on
+/// one concept at a time).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageFeature {
+    /// `loop { ... }`
+    Loop,
+    /// `while cond { ... }` and `do { ... } while/until cond`
+    WhileLoop,
+    /// `for x in ... { ... }`
+    ForLoop,
+    /// `fn name() { ... }`
+    FunctionDefinitions,
+    /// Array literals and indexing.
+    Arrays,
+}
+
+impl LanguageFeature {
+    /// The Rhai symbols that need to be disabled in order to disable this
+    /// feature.
+    pub fn symbols(&self) -> &'static [&'static str] {
+        match self {
+            LanguageFeature::Loop => &["loop"],
+            LanguageFeature::WhileLoop => &["while", "do", "until"],
+            LanguageFeature::ForLoop => &["for"],
+            LanguageFeature::FunctionDefinitions => &["fn"],
+            LanguageFeature::Arrays => &["["],
+        }
+    }
 }
 
-fn compute_stats(engine: &Engine, script: &str, states: &Vec<State>) -> ScriptStats {
+fn compute_stats(
+    engine: &Engine,
+    script: &str,
+    code_metrics: CodeMetrics,
+    states: &[State],
+    operations: u64,
+) -> ScriptStats {
     let energy_used = states.last().unwrap().player.total_energy_used;
     let time_taken = states.len() as u32;
     // Note that we use compact_script to remove all comments and unnecessary whitespace
     // prior to computing the length.
     let code_len = engine.compact_script(script).unwrap().len();
+    let CodeMetrics {
+        statements,
+        ast_nodes,
+        builtin_calls,
+        user_functions,
+        max_loop_depth,
+        cyclomatic_complexity,
+    } = code_metrics;
     ScriptStats {
         code_len,
         energy_used,
         time_taken,
+        operations,
+        statements,
+        ast_nodes,
+        builtin_calls,
+        user_functions,
+        max_loop_depth,
+        cyclomatic_complexity,
     }
 }
 
-// Note(albrow): This code is pretty brittle. We want to simplify the learning process by making
-// semicolons required in places where Rhai considers them optional (e.g. like in the last statement
-// of a block). It's difficult to do this right without building a full-blown tokenizer/parser. For
-// now, we just aim to cover common cases. Unfortunately there are still some edge cases which result
-// in false positives or false negatives.
-fn check_semicolons(source: &str) -> Result<(), Box<EvalAltResult>> {
-    let mut in_block_comment = false;
-    let lines: Vec<&str> = source.lines().collect();
-    'lines: for (i, line) in lines.iter().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.is_empty()
-            || trimmed.starts_with("//")
-            || trimmed.ends_with('{')
-            || trimmed.ends_with('}')
-            || trimmed.ends_with("*/")
-        {
-            continue;
-        }
-        for (j, c) in trimmed.chars().enumerate() {
-            if !in_block_comment
-                && c == '/'
-                && j + 1 < trimmed.len()
-                && trimmed.chars().nth(j + 1) == Some('/')
-            {
-                continue 'lines;
-            } else if c == '/' && j + 1 < trimmed.len() && trimmed.chars().nth(j + 1) == Some('*') {
-                in_block_comment = true;
-            } else if c == '*' && j + 1 < trimmed.len() && trimmed.chars().nth(j + 1) == Some('/') {
-                in_block_comment = false;
-            }
-        }
-        // Check if this line ends in an opening paren. This could be splitting a function's arguments
-        // across multiple lines. Ideally, we would allow this, but we can't tell whether and where semicolons
-        // would be required. For now, return an error explaining that arguments need to be on the same line.
-        if trimmed.ends_with('(') {
-            return Err(Box::new(EvalAltResult::ErrorParsing(
-                rhai::ParseErrorType::BadInput(rhai::LexError::UnexpectedInput(String::from(
-                    BAD_INPUT_UNEXPECTED_LINE_BREAK_IN_FUNCTION_CALL,
-                ))),
-                rhai::Position::new(
-                    (i + 1).try_into().unwrap(),
-                    (line.len() - 1).try_into().unwrap(),
-                ),
-            )));
-        }
-
-        // Check if the next line is an opening bracket. This should be allowed.
-        if let Some(next_line) = lines.get(i + 1) {
-            let trimmed_next = next_line.trim();
-            if trimmed_next.starts_with('{') {
-                continue;
-            }
-        }
-        if !in_block_comment && !trimmed.ends_with(';') {
-            let line_num: u16 = (i + 1).try_into().unwrap();
-            let col: u16 = (line.len() - 1).try_into().unwrap();
-            return Err(Box::new(EvalAltResult::ErrorParsing(
-                rhai::ParseErrorType::MissingToken(
-                    String::from(';'),
-                    String::from("at end of line"),
-                ),
-                rhai::Position::new(line_num, col),
-            )));
-        }
-    }
-
-    Ok(())
-}
-
 fn set_engine_config(engine: &mut Engine) {
     // Causes unknown identifiers to be a compile-time error.
     // See: https://rhai.rs/book/language/variables.html?highlight=strict#strict-variables-mode
     engine.set_strict_variables(true);
+    // Compile without optimizations so that we can compute code metrics
+    // before the AST is optimized (see ScriptRunner::run).
+    engine.set_optimization_level(OptimizationLevel::None);
 }
 
-fn set_engine_safeguards(engine: &mut Engine) {
+fn set_engine_safeguards(engine: &mut Engine, limits: &EngineLimits) {
     // See https://rhai.rs/book/safety/
-    engine.set_max_string_size(1_000);
-    engine.set_max_array_size(100);
+    engine.set_max_string_size(limits.max_string_size);
+    engine.set_max_array_size(limits.max_array_size);
     engine.set_max_map_size(100);
-    engine.set_max_operations(10_000);
-    engine.set_max_call_levels(32);
+    engine.set_max_operations(limits.max_operations);
+    engine.set_max_call_levels(limits.max_call_levels);
     engine.set_max_expr_depths(64, 32);
 }
 
-fn set_print_fn(engine: &mut Engine) {
-    engine.on_print(move |s: &str| {
-        log!("{}", s);
+fn disable_language_features(engine: &mut Engine, features: &[LanguageFeature]) {
+    for feature in features {
+        for symbol in feature.symbols() {
+            engine.disable_symbol(*symbol);
+        }
+    }
+}
+
+/// Keeps track of the number of operations performed by the engine.
+fn count_operations(engine: &mut Engine, operations: Rc<Cell<u64>>) {
+    engine.on_progress(move |count| {
+        operations.set(count);
+        None
     });
 }
 
+/// Functions and operators which work with Positions, along with the types of
+/// their inputs. Unlike built-in functions, these are always available. This
+/// is used for error messages (see register_custom_types for the
+/// implementations).
+pub const POSITION_FUNCTIONS: &[(&str, &[&str])] = &[
+    ("new_position", &["number", "number"]),
+    ("manhattan_distance", &["Position", "Position"]),
+    ("neighbors", &["Position"]),
+    ("+", &["Position", "Position"]),
+    ("-", &["Position", "Position"]),
+];
+
 fn register_custom_types(engine: &mut Engine) {
     engine
         .register_type_with_name::<Pos>("Position")
         .register_get("x", Pos::get_x)
-        .register_get("y", Pos::get_y);
+        .register_get("y", Pos::get_y)
+        .register_fn(
+            "new_position",
+            |x: i64, y: i64| -> Result<Pos, Box<EvalAltResult>> {
+                checked_pos(i32::try_from(x).ok(), i32::try_from(y).ok())
+            },
+        )
+        .register_fn("==", |a: Pos, b: Pos| a == b)
+        .register_fn("!=", |a: Pos, b: Pos| a != b)
+        .register_fn("+", |a: Pos, b: Pos| -> Result<Pos, Box<EvalAltResult>> {
+            checked_pos(a.x.checked_add(b.x), a.y.checked_add(b.y))
+        })
+        .register_fn("-", |a: Pos, b: Pos| -> Result<Pos, Box<EvalAltResult>> {
+            checked_pos(a.x.checked_sub(b.x), a.y.checked_sub(b.y))
+        })
+        .register_fn("manhattan_distance", |a: &mut Pos, b: Pos| {
+            a.manhattan_distance(&b)
+        })
+        // Neighbors are returned in clockwise order, starting with the one
+        // above.
+        .register_fn(
+            "neighbors",
+            |pos: &mut Pos| -> Result<rhai::Array, Box<EvalAltResult>> {
+                [(0, -1), (1, 0), (0, 1), (-1, 0)]
+                    .iter()
+                    .map(|&(dx, dy)| {
+                        checked_pos(pos.x.checked_add(dx), pos.y.checked_add(dy)).map(Dynamic::from)
+                    })
+                    .collect()
+            },
+        )
+        .register_fn("to_string", |pos: &mut Pos| pos.to_string())
+        .register_fn("to_debug", |pos: &mut Pos| pos.to_string())
+        // get_position used to return an array of [x, y], so we also support
+        // indexing for backwards compatibility.
+        .register_indexer_get(
+            |pos: &mut Pos, index: i64| -> Result<i64, Box<EvalAltResult>> {
+                match index {
+                    0 => Ok(pos.x as i64),
+                    1 => Ok(pos.y as i64),
+                    _ => Err(ERR_POSITION_INDEX.into()),
+                }
+            },
+        );
 }
 
-fn eval_call_args_as_int(context: &EvalContext, fn_call_expr: &FnCallExpr) -> Result<i64, Error> {
-    if fn_call_expr.args.len() != 1 {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Expected exactly one argument to function call",
-        ));
+/// Returns a Position with the given x and y parts, or an error if either part
+/// is None (i.e. it was out of range or the arithmetic overflowed).
+fn checked_pos(x: Option<i32>, y: Option<i32>) -> Result<Pos, Box<EvalAltResult>> {
+    match (x, y) {
+        (Some(x), Some(y)) => Ok(Pos::new(x, y)),
+        _ => Err(ERR_POSITION_OUT_OF_RANGE.into()),
     }
-    let arg = fn_call_expr.args[0].clone();
-    let arg_val = match arg.get_literal_value() {
-        // Arg is a literal. Much easier to parse.
-        Some(dyn_val) => match dyn_val.as_int() {
-            Ok(int_val) => int_val,
-            Err(actual_type) => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("Expected argument to be an integer but got {}", actual_type),
-                ))
-            }
-        },
-        _ => {
-            // Arg is not a literal, evaluate it using the current context.
-            // To do this, we need to first collect all the modules in the current
-            // context into a single module. (The main thing we care about for our
-            // usecase is user-defined functions inside the script.)
-            let mut module = rhai::Module::new();
-            for m in context.iter_namespaces() {
-                module.combine(m.to_owned());
-            }
-            // With the module constructed, we can now evaluate the arg expression
-            // inside it's own AST tree.
-            let arg_ast = rhai::AST::new([rhai::Stmt::Expr(Box::new(arg))], module);
-            let mut scope = context.scope().clone();
-            let arg_val = context
-                .engine()
-                .eval_ast_with_scope::<i64>(&mut scope, &arg_ast);
-            match arg_val {
-                Ok(val) => val,
-                Err(err) => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        format!("Error evaluating argument: {}", err),
-                    ))
-                }
-            }
-        }
-    };
-    Ok(arg_val)
 }
 
-/// Returns the current direction that the rover is facing. Intended to be
-/// used inside the debugger.
-fn eval_curr_orientation(context: &EvalContext) -> Result<Orientation, Error> {
-    let mut module = rhai::Module::new();
-    for m in context.iter_namespaces() {
-        module.combine(m.to_owned());
+/// Returns true if the error is ERR_SIMULATION_END (possibly wrapped in an
+/// error from a function call).
+fn is_simulation_end(err: &EvalAltResult) -> bool {
+    match err {
+        EvalAltResult::ErrorRuntime(..) => err.to_string().contains(ERR_SIMULATION_END),
+        EvalAltResult::ErrorInFunctionCall(_, _, inner, _) => is_simulation_end(inner),
+        _ => false,
     }
-    // With the module constructed, we can now evaluate th special
-    // __get_orientation__() call inside the current scope.
-    let mut scope = context.scope().clone();
-    let orientation_val = context
-        .engine()
-        .eval_expression_with_scope::<String>(&mut scope, "__get_orientation__()");
-    match orientation_val {
-        Ok(dir) => match dir.as_str() {
-            "up" => Ok(Orientation::Up),
-            "down" => Ok(Orientation::Down),
-            "left" => Ok(Orientation::Left),
-            "right" => Ok(Orientation::Right),
-            _ => Err(Error::new(
-                ErrorKind::Other,
-                format!("Unknown orientation: {}", dir),
-            )),
-        },
-        Err(err) => Err(Error::new(
-            ErrorKind::Other,
-            format!("Error evaluating orientation: {}", err),
-        )),
+}
+
+/// Returns a Pause if the statement at the given index and position matches
+/// the given pause target. The statement index is given along with the current
+/// step of the simulation. If a breakpoint condition can't be evaluated, the
+/// breakpoint is treated as not hit and the error is added to condition_errors.
+fn check_pause_target(
+    target: &PauseTarget,
+    condition_engine: &Engine,
+    condition_errors: &RefCell<Vec<BetterError>>,
+    context: &EvalContext,
+    (stmt_index, step): (usize, usize),
+    pos: Position,
+) -> Option<Pause> {
+    let line = pos.line()?;
+    if target.after.is_some_and(|after| stmt_index <= after) {
+        return None;
+    }
+    if target
+        .after_step
+        .is_some_and(|after_step| step < after_step)
+    {
+        return None;
+    }
+    let call_stack = context.global_runtime_state().debugger().call_stack();
+    let depth = call_stack.len();
+    if target.max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return None;
+    }
+    if let Some(breakpoints) = &target.breakpoints {
+        let hit = breakpoints
+            .iter()
+            .filter(|b| b.line == line)
+            .any(|breakpoint| {
+                let condition = match &breakpoint.condition {
+                    Some(condition) => condition,
+                    None => return true,
+                };
+                match eval_condition(condition_engine, context, condition) {
+                    Ok(hit) => hit,
+                    Err(message) => {
+                        // Conditions often run many times (e.g. inside a loop), but
+                        // we only need to report each one once.
+                        let mut errors = condition_errors.borrow_mut();
+                        if !errors.iter().any(|err| err.line == Some(line)) {
+                            errors.push(BetterError {
+                                message,
+                                line: Some(line),
+                                col: None,
+                                output: vec![],
+                            });
+                        }
+                        false
+                    }
+                }
+            });
+        if !hit {
+            return None;
+        }
     }
+    Some(Pause {
+        stmt_index,
+        line,
+        depth,
+        call_stack: call_stack
+            .iter()
+            .map(|frame| StackFrame {
+                fn_name: frame.fn_name.to_string(),
+                line: frame.pos.line().unwrap_or(0),
+            })
+            .collect(),
+        vars: snapshot_vars(context),
+    })
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::levels::SANDBOX_LEVEL_WITH_DATA_POINT;
+/// Returns a new Engine configured for the given level, i.e. with the level's
+/// engine limits and disabled language features. Player functions are not
+/// registered.
+pub(crate) fn new_level_engine(level: &dyn Level) -> Engine {
+    let mut engine = Engine::new();
+    set_engine_config(&mut engine);
+    set_engine_safeguards(&mut engine, &level.engine_limits());
+    disable_language_features(&mut engine, &level.disabled_features());
+    register_custom_types(&mut engine);
+    engine
+}
 
-    #[test]
-    fn test_check_semicolons() {
-        let source = r#"
-            // This is a comment
-            fn foo() {
-                // This is a comment
-                let a = 1;
-                let b = 2; // This is an inline comment.
-                let c = 3; /* This is a block comment */
-                let /* this is an intrusive comment */ d = 4;
-                /* 
-                    This is a multiline comment.
-                    It has more than one line.
-                 */
-            }
-        "#;
-        check_semicolons(source).unwrap();
+/// Returns a new Engine for compiling and evaluating breakpoint conditions.
+/// It has the same safeguards as the engine used for running scripts, and
+/// knows about custom types like Position.
+///
+/// Note that we use a separate Engine so that evaluating the condition does
+/// not call the debugger or any of the player functions (which would step the
+/// simulation forward). This means that conditions can only use variables,
+/// operators, and custom types. Calling a built-in or user-defined function
+/// is an error.
+pub(crate) fn new_condition_engine(limits: &EngineLimits) -> Engine {
+    let mut engine = Engine::new();
+    set_engine_safeguards(&mut engine, limits);
+    register_custom_types(&mut engine);
+    engine
+}
 
-        let source = r#"
-            // This is a comment
-            fn foo() {
-                // This is a comment
-                let a = 1
-                let b = 2; // This is an inline comment.
-                let c = 3; /* This is a block comment */
-                let /* this is an intrusive comment */ d = 4;
-                /* 
-                    This is a multiline comment.
-                    It has more than one line.
-                */
-            }
-        "#;
-        assert!(check_semicolons(source).is_err());
-
-        // Should not be a missing semicolon error if a bracket is on
-        // the next line. This is a regression test for
-        // https://github.com/albrow/elara/issues/67
-        let source = r"
-            loop
-            {
-                move_forward(1);
-            }
-            if true
-            {
-                move_forward(1);
-            }
-            else
-            {
-                move_forward(1);
-            }
-            while true
-            {
-                move_forward(1);
-            }
-        ";
-        check_semicolons(source).unwrap();
-
-        // TODO(albrow): Uncomment this test once we handle the edge case.
-        // let source = r#"
-        //     // This is a comment
-        //     fn foo() {
-        //         // This is a comment
-        //         let a = 1;
-        //         let b = 2 // This is an inline comment.
-        //         let c = 3; /* This is a block comment */
-        //         let /* this is an intrusive comment */ d = 4;
-        //         /*
-        //             This is a multiline comment.
-        //             It has more than one line.
-        //         */
-        //     }
-        // "#;
-        // assert!(check_semicolons(source).is_err());
-
-        // TODO(albrow): Uncomment this test once we handle the edge case.
-        // let source = r#"
-        //     // This is a comment
-        //     fn foo() {
-        //         // This is a comment
-        //         let a = 1;
-        //         let b = 2; // This is an inline comment.
-        //         let c = 3 /* This is a block comment */
-        //         let /* this is an intrusive comment */ d = 4;
-        //         /*
-        //             This is a multiline comment.
-        //             It has more than one line.
-        //         */
-        //     }
-        // "#;
-        // assert!(check_semicolons(source).is_err());
-
-        let source = r#"
-        // This is a comment
-        fn foo() {
-            // This is a comment
-            let a = 1;
-            let b = 2; // This is an inline comment.
-            let c = 3; /* This is a block comment */
-            let /* this is an intrusive comment */ d = 4
-            /* 
-                This is a multiline comment.
-                It has more than one line.
-            */
+/// Evaluates a breakpoint condition in the current scope. Returns an error
+/// message if the condition can't be evaluated or does not evaluate to a bool.
+fn eval_condition(engine: &Engine, context: &EvalContext, condition: &str) -> Result<bool, String> {
+    let mut scope = context.scope().clone();
+    engine
+        .eval_expression_with_scope::<bool>(&mut scope, condition)
+        .map_err(|err| format!("Invalid breakpoint condition: {}", err))
+}
+
+/// Returns a snapshot of all the variables which are currently in scope.
+fn snapshot_vars(context: &EvalContext) -> Vec<VarSnapshot> {
+    let mut vars: Vec<VarSnapshot> = vec![];
+    for (name, _, value) in context.scope().iter() {
+        // If a variable is shadowed, only the newest one is visible.
+        vars.retain(|var| var.name != name);
+        // Dynamic doesn't know how to format custom types, so we need to
+        // handle them separately.
+        let mut formatted = match value.clone().try_cast::<Pos>() {
+            Some(pos) => pos.to_string(),
+            None => format!("{:?}", value),
+        };
+        if let Some((end, _)) = formatted.char_indices().nth(MAX_VAR_VALUE_LEN) {
+            formatted.truncate(end);
+            formatted.push_str("...");
         }
-    "#;
-        assert!(check_semicolons(source).is_err());
+        vars.push(VarSnapshot {
+            name: name.to_string(),
+            type_name: context
+                .engine()
+                .map_type_name(value.type_name())
+                .to_string(),
+            value: formatted,
+        });
     }
+    vars
+}
 
-    #[test]
-    fn test_unexpected_line_break_in_function_call() {
-        let script = r#"
-            fn foo() {
-                move_forward(
-                    1
-                );
-            }"#;
-        let result = check_semicolons(script);
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert_eq!(
-            err.to_string(),
-            "Syntax error: Unexpected 'line break in function call' (line 3, position 28)"
-        );
-    }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::levels::{SANDBOX_LEVEL, SANDBOX_LEVEL_WITH_DATA_POINT};
 
     /// Asserts that result is not a failure and then checks the each
     /// line number in results.trace. Note that we only check the line number,
@@ -1153,4 +1044,204 @@ mod test {
             vec![vec![], vec![9, 7, 3], vec![9, 7, 3], vec![9, 7, 4]],
         );
     }
+
+    /// A test for function calls whose arguments step the simulation forward.
+    /// The arguments should only be evaluated once and each step should be
+    /// attributed to the call which actually took it.
+    #[test]
+    fn test_trace_for_args_with_side_effects() {
+        let mut game = crate::Game::new();
+
+        let script = r#"
+            fn two() {
+                say("two");
+                return 2;
+            }
+            move_forward(two());
+            say(read_data());
+        "#;
+        let result = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec![
+                    "move_forward".to_string(),
+                    "say".to_string(),
+                    "read_data".to_string(),
+                ],
+                script.to_string(),
+            )
+            .unwrap();
+        assert_eq!(result.states.len(), 6);
+        assert_trace_eq(
+            &result,
+            vec![vec![], vec![6, 3], vec![6], vec![6], vec![7], vec![7]],
+        );
+        assert_eq!(result.vars.len(), result.trace.len());
+    }
+
+    #[test]
+    fn output() {
+        let mut game = crate::Game::new();
+
+        let script = r#"
+            print("start");
+            move_forward(2);
+            fn report(n) {
+                debug(n);
+            }
+            report("moved");
+            print(`x is ${get_position().x}`);
+        "#;
+        let result = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL,
+                &vec!["move_forward".to_string(), "get_position".to_string()],
+                script.to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            result.output,
+            vec![
+                OutputLine {
+                    step: 0,
+                    line: 2,
+                    kind: OutputKind::Print,
+                    text: String::from("start"),
+                },
+                OutputLine {
+                    step: 2,
+                    line: 5,
+                    kind: OutputKind::Debug,
+                    text: String::from(r#""moved""#),
+                },
+                OutputLine {
+                    step: 2,
+                    line: 8,
+                    kind: OutputKind::Print,
+                    text: String::from("x is 2"),
+                },
+            ]
+        );
+
+        // If the script fails with an error instead of an outcome, the output
+        // is returned along with the error.
+        let err = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL,
+                &vec!["get_position".to_string()],
+                "print(\"before\");\nlet pos = get_position() + 1;".to_string(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Error: The + operator can only combine a Position with another Position."
+        );
+        assert_eq!(
+            err.output,
+            vec![OutputLine {
+                step: 0,
+                line: 1,
+                kind: OutputKind::Print,
+                text: String::from("before"),
+            }]
+        );
+    }
+
+    #[test]
+    fn position_api() {
+        let mut game = crate::Game::new();
+
+        // The rover starts at (0, 0) and the data point is at (2, 2).
+        let script = r#"
+            let start = get_position();
+            let pos = new_position(2, 3) + new_position(1, -1) - start;
+            let neighbors = pos.neighbors();
+            say(`${pos} ${pos.x} ${pos[1]} ${pos == new_position(3, 2)} ${pos != start} ${start.manhattan_distance(pos)} ${neighbors[1]} ${neighbors.len()}`);
+        "#;
+        let result = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec!["get_position".to_string(), "say".to_string()],
+                script.to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            result.states.last().unwrap().player.message,
+            "(3, 2) 3 2 true true 5 (4, 2) 4"
+        );
+        assert!(result.vars.last().unwrap().contains(&VarSnapshot {
+            name: String::from("pos"),
+            type_name: String::from("Position"),
+            value: String::from("(3, 2)"),
+        }));
+
+        let result = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec!["get_position".to_string()],
+                "get_position()[2];".to_string(),
+            )
+            .unwrap();
+        assert!(
+            matches!(result.outcome, Outcome::Failure(msg) if msg.contains(ERR_POSITION_INDEX))
+        );
+
+        let err = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec!["get_position".to_string()],
+                "let pos = get_position() + 1;".to_string(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Error: The + operator can only combine a Position with another Position."
+        );
+
+        let err = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec!["get_position".to_string()],
+                "let d = get_position().manhattan_distance(1);".to_string(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Error: Wrong inputs for the manhattan_distance function. Should have 2 inputs (Position, Position)."
+        );
+
+        // Positions which are out of range are an error instead of
+        // overflowing.
+        for script in [
+            "new_position(2147483648, 0);",
+            "new_position(2147483647, 0) + new_position(1, 0);",
+            "new_position(0, -2147483648) - new_position(0, 1);",
+            "new_position(2147483647, 0).neighbors();",
+        ] {
+            let result = game
+                .run_player_script_internal(
+                    SANDBOX_LEVEL_WITH_DATA_POINT,
+                    &vec![],
+                    script.to_string(),
+                )
+                .unwrap();
+            assert!(
+                matches!(result.outcome, Outcome::Failure(ref msg) if msg.contains(ERR_POSITION_OUT_OF_RANGE)),
+                "unexpected outcome for {}: {:?}",
+                script,
+                result.outcome
+            );
+        }
+
+        // The distance between any two positions fits in a number.
+        let result = game
+            .run_player_script_internal(
+                SANDBOX_LEVEL_WITH_DATA_POINT,
+                &vec!["say".to_string()],
+                "say(new_position(-2147483648, 0).manhattan_distance(new_position(2147483647, 0)));"
+                    .to_string(),
+            )
+            .unwrap();
+        assert_eq!(result.states.last().unwrap().player.message, "4294967295");
+    }
 }
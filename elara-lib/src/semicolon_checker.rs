@@ -0,0 +1,382 @@
+use std::convert::TryInto;
+
+use rhai::{Engine, EvalAltResult, Position, Token};
+
+/// The different kinds of braces. Only statement blocks require a semicolon
+/// after the last statement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Brace {
+    /// A block of statements, e.g. the body of a loop or function.
+    Block,
+    /// A block whose last statement is the value of an if or switch
+    /// expression, e.g. `let x = if a { 1 } else { 2 };`.
+    ExprBlock,
+    /// The body of a switch statement. Cases are separated by commas. expr is
+    /// true if the switch is used as an expression.
+    Switch { expr: bool },
+    /// An object map literal, i.e. `#{ ... }`.
+    Map,
+    /// An expression inside of an interpolated string, i.e. `${ ... }`.
+    Interpolation,
+}
+
+/// Checks that every statement ends with a semicolon, including in places
+/// where Rhai considers them optional (i.e. the last statement in a block or
+/// in the script). This simplifies the learning process since the rule is
+/// always the same. Statements which end in a block (e.g. if statements and
+/// loops) don't need a semicolon. When an if or switch is used as an
+/// expression, the last statement in each of its blocks is the value and
+/// doesn't need a semicolon, but the whole expression does.
+///
+/// The script is expected to have already been compiled successfully. Since
+/// Rhai already requires semicolons between statements, we mainly need to
+/// check the tokens before each closing brace and at the end of the script.
+/// Because this works on tokens instead of lines, comments, strings, and
+/// expressions which span multiple lines are all handled correctly.
+pub fn check_semicolons(script: &str) -> Result<(), Box<EvalAltResult>> {
+    let engine = Engine::new_raw();
+    let (tokens, control) = engine.lex([&script]);
+
+    let mut braces: Vec<Brace> = vec![];
+    // The last token that was not a comment, and whether it is the end of a
+    // statement which doesn't need a semicolon.
+    let mut prev: Option<(Token, Position)> = None;
+    let mut prev_ends_stmt = true;
+    let mut after_switch = false;
+    // Whether the next block belongs to an if or switch which is used as an
+    // expression.
+    let mut expr_pending = false;
+    // The kind of brace which was closed most recently.
+    let mut last_closed: Option<Brace> = None;
+
+    for (token, pos) in tokens {
+        let mut ends_stmt = false;
+        match token {
+            Token::EOF => break,
+            Token::LexError(_) => return Ok(()),
+            Token::Comment(_) => continue,
+            // An if or switch is used as an expression if it doesn't start a
+            // new statement (e.g. it comes after `=`). Note that `else if`
+            // continues the same if statement.
+            Token::If | Token::Switch => {
+                let after_else = matches!(prev, Some((Token::Else, _)));
+                if !prev_ends_stmt && !after_else {
+                    expr_pending = true;
+                }
+                if token == Token::Switch {
+                    after_switch = true;
+                }
+            }
+            Token::Else => {
+                expr_pending = matches!(prev, Some((Token::RightBrace, _)))
+                    && last_closed == Some(Brace::ExprBlock);
+            }
+            // These keywords always start a new statement, so the previous
+            // statement (if any) must have ended. Rhai would also catch this
+            // when compiling, but checking here means we don't depend on the
+            // script being compiled first. A `private fn` is a single
+            // statement, so `fn` can also come right after `private`.
+            Token::Let | Token::Const | Token::Private if !prev_ends_stmt => {
+                return Err(missing_semicolon_err(&prev));
+            }
+            Token::Fn if !prev_ends_stmt && !matches!(prev, Some((Token::Private, _))) => {
+                return Err(missing_semicolon_err(&prev));
+            }
+            Token::MapStart => braces.push(Brace::Map),
+            Token::LeftBrace => {
+                let after_interpolated_string =
+                    matches!(prev, Some((Token::InterpolatedString(_), _)));
+                let is_expr_arm = matches!(prev, Some((Token::DoubleArrow, _)))
+                    && braces.last() == Some(&Brace::Switch { expr: true });
+                braces.push(if after_interpolated_string {
+                    Brace::Interpolation
+                } else if after_switch {
+                    Brace::Switch { expr: expr_pending }
+                } else if expr_pending || is_expr_arm {
+                    Brace::ExprBlock
+                } else {
+                    Brace::Block
+                });
+                after_switch = false;
+                expr_pending = false;
+                // A block may be empty.
+                ends_stmt = true;
+            }
+            Token::SemiColon => ends_stmt = true,
+            Token::RightBrace => match braces.pop() {
+                Some(Brace::Block) => {
+                    if !prev_ends_stmt {
+                        return Err(missing_semicolon_err(&prev));
+                    }
+                    // Statements which end in a block (e.g. if statements and
+                    // loops) don't need a semicolon.
+                    ends_stmt = true;
+                    last_closed = Some(Brace::Block);
+                }
+                // The expression continues after the block, so whatever
+                // comes after it still needs a semicolon.
+                Some(Brace::ExprBlock) => last_closed = Some(Brace::ExprBlock),
+                Some(Brace::Switch { expr }) => ends_stmt = !expr,
+                Some(Brace::Interpolation) => {
+                    // Switch the tokenizer back to parsing the rest of the
+                    // string. Normally the parser does this for us.
+                    control.borrow_mut().is_within_text = true;
+                }
+                Some(Brace::Map) | None => {}
+            },
+            _ => {}
+        }
+        prev_ends_stmt = ends_stmt;
+        prev = Some((token, pos));
+    }
+
+    if !prev_ends_stmt {
+        return Err(missing_semicolon_err(&prev));
+    }
+    Ok(())
+}
+
+/// Returns a missing semicolon error positioned right after the given token.
+fn missing_semicolon_err(prev: &Option<(Token, Position)>) -> Box<EvalAltResult> {
+    let pos = match prev {
+        Some((token, pos)) => {
+            let len = token.to_string().chars().count();
+            Position::new(
+                pos.line().unwrap_or(1).try_into().unwrap(),
+                (pos.position().unwrap_or(1) + len).try_into().unwrap(),
+            )
+        }
+        None => Position::new(1, 1),
+    };
+    Box::new(EvalAltResult::ErrorParsing(
+        rhai::ParseErrorType::MissingToken(String::from(';'), String::from("at end of line")),
+        pos,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        let source = r#"
+            // This is a comment
+            fn foo() {
+                // This is a comment
+                let a = 1;
+                let b = 2; // This is an inline comment.
+                let c = 3; /* This is a block comment */
+                let /* this is an intrusive comment */ d = 4;
+                /* 
+                    This is a multiline comment.
+                    It has more than one line.
+                 */
+            }
+        "#;
+        check_semicolons(source).unwrap();
+
+        let source = r#"
+            // This is a comment
+            fn foo() {
+                // This is a comment
+                let a = 1
+                let b = 2; // This is an inline comment.
+                let c = 3; /* This is a block comment */
+                let /* this is an intrusive comment */ d = 4;
+                /* 
+                    This is a multiline comment.
+                    It has more than one line.
+                */
+            }
+        "#;
+        assert!(check_semicolons(source).is_err());
+
+        let source = r#"
+            // This is a comment
+            fn foo() {
+                // This is a comment
+                let a = 1;
+                let b = 2 // This is an inline comment.
+                let c = 3; /* This is a block comment */
+                let /* this is an intrusive comment */ d = 4;
+                /*
+                    This is a multiline comment.
+                    It has more than one line.
+                */
+            }
+        "#;
+        assert!(check_semicolons(source).is_err());
+
+        let source = r#"
+            // This is a comment
+            fn foo() {
+                // This is a comment
+                let a = 1;
+                let b = 2; // This is an inline comment.
+                let c = 3 /* This is a block comment */
+                let /* this is an intrusive comment */ d = 4;
+                /*
+                    This is a multiline comment.
+                    It has more than one line.
+                */
+            }
+        "#;
+        assert!(check_semicolons(source).is_err());
+
+        let source = r#"
+        // This is a comment
+        fn foo() {
+            // This is a comment
+            let a = 1;
+            let b = 2; // This is an inline comment.
+            let c = 3; /* This is a block comment */
+            let /* this is an intrusive comment */ d = 4
+            /* 
+                This is a multiline comment.
+                It has more than one line.
+            */
+        }
+    "#;
+        assert!(check_semicolons(source).is_err());
+    }
+
+    #[test]
+    fn blocks() {
+        // Should not be a missing semicolon error if a bracket is on
+        // the next line. This is a regression test for
+        // https://github.com/albrow/elara/issues/67
+        let source = r"
+            loop
+            {
+                move_forward(1);
+            }
+            if true
+            {
+                move_forward(1);
+            }
+            else
+            {
+                move_forward(1);
+            }
+            while true
+            {
+                move_forward(1);
+            }
+        ";
+        check_semicolons(source).unwrap();
+
+        // Empty blocks, switch statements, and maps.
+        let source = r"
+            fn foo() {}
+            let m = #{ a: 1, b: #{ c: 2 } };
+            switch m.a {
+                1 => { move_forward(1); },
+                _ => move_backward(1)
+            }
+            if m.a == 1 { move_forward(1); } else {}
+        ";
+        check_semicolons(source).unwrap();
+
+        // The last statement in the script needs a semicolon too.
+        assert!(check_semicolons("move_forward(1);\nmove_forward(1)").is_err());
+        assert!(check_semicolons("let m = #{ a: 1 }").is_err());
+        assert!(check_semicolons("loop {\n  move_forward(1)\n}").is_err());
+        check_semicolons("").unwrap();
+    }
+
+    #[test]
+    fn private_functions() {
+        let source = r"private fn foo() {
+                move_forward(1);
+            }
+            move_forward(1);
+            private fn bar() {}
+        ";
+        check_semicolons(source).unwrap();
+
+        // A private function still needs the previous statement to end.
+        assert!(check_semicolons("move_forward(1)\nprivate fn foo() {}").is_err());
+    }
+
+    #[test]
+    fn expression_blocks() {
+        // The last statement in an if or switch expression is its value, so
+        // it doesn't need a semicolon.
+        let source = r"
+            let x = if true { 1 } else { 2 };
+            move_forward(x);
+            let y = if x > 1 { 1 } else if x > 0 { 2 } else { 3 };
+            let z = switch y {
+                1 => { let a = 2; a },
+                _ => 3
+            };
+            move_forward(if z > 0 { z } else { 1 } + 1);
+            if x > 1 {
+                move_forward(1);
+            } else if x > 0 {
+                move_forward(2);
+            } else {
+                move_forward(3);
+            }
+        ";
+        check_semicolons(source).unwrap();
+
+        // The expression as a whole still needs a semicolon.
+        assert!(check_semicolons("let x = if true { 1 } else { 2 }").is_err());
+        assert!(check_semicolons("let x = switch 1 { _ => 2 }").is_err());
+        // Blocks in if statements are not expressions.
+        assert!(
+            check_semicolons("if true {\n  move_forward(1)\n} else {\n  move_forward(2);\n}")
+                .is_err()
+        );
+        assert!(
+            check_semicolons("if true {\n  move_forward(1);\n} else {\n  move_forward(2)\n}")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let source = r#"
+            say("{");
+            say("}");
+            say(";");
+            say(`${1 + 1} {`);
+            say(`a ${"}"} b ${ `${"c"}` } }`);
+        "#;
+        check_semicolons(source).unwrap();
+
+        assert!(check_semicolons("if true {\n  say(\";\")\n}").is_err());
+    }
+
+    #[test]
+    fn multi_line_expressions() {
+        let source = r#"
+            fn foo() {
+                move_forward(
+                    1
+                );
+                let a = 1 +
+                    2 +
+                    3;
+                say(
+                    "a" +
+                    "b"
+                );
+            }"#;
+        check_semicolons(source).unwrap();
+
+        let source = r#"
+            fn foo() {
+                move_forward(
+                    1
+                )
+            }"#;
+        let err = check_semicolons(source).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Syntax error: Expecting ';' at end of line (line 5, position 18)"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The current version of the serialization format for the state model (e.g.
+/// State, ScriptResult, and Outcome). This should be incremented whenever a
+/// change is made which breaks compatibility with previously serialized data.
+///
+/// Backwards-compatible changes don't require a new version. In particular,
+/// new fields should use `#[serde(default)]` and new enum variants may be
+/// added freely, so that data serialized with an older build of the same
+/// version still loads (see data/serialization for examples).
+pub const SERIALIZATION_VERSION: u32 = 1;
+
+/// A wrapper which adds the serialization format version to some serialized
+/// data. All top-level data that is saved or sent outside of Rust should be
+/// wrapped in Versioned so that we can detect outdated data when loading it
+/// back in.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Wraps the given data with the current serialization version.
+    pub fn new(data: T) -> Versioned<T> {
+        Versioned {
+            version: SERIALIZATION_VERSION,
+            data,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SerializationError {
+    /// The data could not be encoded or decoded as JSON.
+    Json(serde_json::Error),
+    /// The data was serialized with a version of the format that we don't
+    /// know how to read.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Json(err) => write!(f, "invalid JSON: {}", err),
+            SerializationError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported serialization version: {} (expected {})",
+                version, SERIALIZATION_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl From<serde_json::Error> for SerializationError {
+    fn from(err: serde_json::Error) -> Self {
+        SerializationError::Json(err)
+    }
+}
+
+/// Serializes the given data to a pretty-printed JSON string, including the
+/// current serialization version.
+pub fn to_json<T: Serialize>(data: &T) -> Result<String, SerializationError> {
+    let versioned = Versioned {
+        version: SERIALIZATION_VERSION,
+        data,
+    };
+    Ok(serde_json::to_string_pretty(&versioned)?)
+}
+
+/// Deserializes data from a JSON string which was created by to_json. Returns
+/// an error if the data was serialized with a different version.
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, SerializationError> {
+    // Check the version first so that outdated data results in a more helpful
+    // error message than a missing or invalid field.
+    #[derive(Deserialize)]
+    struct VersionOnly {
+        version: u32,
+    }
+    let VersionOnly { version } = serde_json::from_str(json)?;
+    if version != SERIALIZATION_VERSION {
+        return Err(SerializationError::UnsupportedVersion(version));
+    }
+    let versioned: Versioned<T> = serde_json::from_str(json)?;
+    Ok(versioned.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{Outcome, LEVELS};
+    use crate::script_runner::ScriptResult;
+    use crate::simulation::{State, TermData};
+    use crate::Game;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn state_round_trip() {
+        for level in LEVELS.values() {
+            for state in level.initial_states() {
+                let json = to_json(&state).unwrap();
+                let loaded: State = from_json(&json).unwrap();
+                assert_eq!(loaded, state, "level: {}", level.short_name());
+            }
+        }
+    }
+
+    #[test]
+    fn state_without_input_terminals() {
+        // States serialized before input terminals were added should still
+        // load.
+        let state = State::new();
+        let mut value = serde_json::to_value(Versioned::new(&state)).unwrap();
+        value["data"]
+            .as_object_mut()
+            .unwrap()
+            .remove("input_terminals");
+        let loaded: State = from_json(&value.to_string()).unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn script_result_round_trip() {
+        let mut game = Game::new();
+        let result = game
+            .run_player_script_with_all_funcs_unlocked(
+                LEVELS.get("movement").unwrap().as_ref(),
+                String::from("move_forward(3); turn_right(); move_forward(3);"),
+            )
+            .unwrap();
+        assert_eq!(result.outcome, Outcome::Success);
+        let json = to_json(&result).unwrap();
+        let loaded: ScriptResult = from_json(&json).unwrap();
+        assert_eq!(loaded, result);
+    }
+
+    #[test]
+    fn outcome_and_term_data_format() {
+        assert_eq!(
+            serde_json::to_string(&Outcome::Failure(String::from("oops"))).unwrap(),
+            r#"{"Failure":"oops"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&TermData::from(vec!["a", "b"])).unwrap(),
+            r#"["a","b"]"#
+        );
+        let data: TermData = serde_json::from_str(r#"["a",["b"]]"#).unwrap();
+        assert_eq!(
+            data,
+            TermData::Array(vec![
+                TermData::from("a"),
+                TermData::Array(vec![TermData::from("b")])
+            ])
+        );
+
+        let json = r#"{"a":1,"b":[true,"x"]}"#;
+        let data: TermData = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            data,
+            TermData::from(BTreeMap::from([
+                (String::from("a"), TermData::from(1)),
+                (
+                    String::from("b"),
+                    TermData::Array(vec![TermData::from(true), TermData::from("x")])
+                ),
+            ]))
+        );
+        assert_eq!(serde_json::to_string(&data).unwrap(), json);
+    }
+
+    #[test]
+    fn script_result_from_version_one() {
+        // Serialized before fields like vars, output, initial_state_index,
+        // and most of stats were added.
+        let json = include_str!("../data/serialization/script_result_v1.json");
+        let result: ScriptResult = from_json(json).unwrap();
+        assert_eq!(result.outcome, Outcome::Continue);
+        assert_eq!(result.states.len(), 3);
+        assert_eq!(result.trace, vec![vec![], vec![1], vec![2]]);
+        assert!(result.output.is_empty());
+        assert_eq!(result.stats.energy_used, 1);
+        assert_eq!(result.stats.builtin_calls, BTreeMap::new());
+
+        let loaded: ScriptResult = from_json(&to_json(&result).unwrap()).unwrap();
+        assert_eq!(loaded, result);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let json = format!(
+            r#"{{"version": {}, "data": "Success"}}"#,
+            SERIALIZATION_VERSION + 1
+        );
+        let result: Result<Outcome, _> = from_json(&json);
+        assert!(matches!(
+            result,
+            Err(SerializationError::UnsupportedVersion(v)) if v == SERIALIZATION_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,679 @@
+use rand::Rng;
+
+use crate::better_errors::BetterError;
+use crate::levels::{Level, Outcome};
+use crate::script_runner::{
+    new_condition_engine, Breakpoint, EngineLimits, Pause, PauseTarget, ScriptResult, VarSnapshot,
+};
+use crate::simulation::State;
+use crate::{get_avail_funcs, Game};
+
+/// A session allows running a script one step at a time instead of all at
+/// once (e.g. for single-stepping and pausing in the editor).
+///
+/// Rhai scripts can't be suspended in the middle of running, so a session
+/// works by running the script from the beginning, stopping the simulation
+/// once the desired step is reached. Since the initial state is fixed for the
+/// lifetime of the session and scripts are deterministic, each run produces
+/// the same states as the last one, just with more of them. The result of the
+/// latest run is cached, and steps which have already been computed are
+/// served from the cache. When the script needs to run further, the step
+/// limit is doubled so that stepping through the whole script only takes a
+/// logarithmic number of runs.
+///
+/// Sessions can also be paused at any statement (e.g. at a breakpoint) using
+/// resume_internal. This works the same way, except that the script is stopped
+/// once the desired statement is reached (see ScriptRunner::set_pause_target).
+pub struct Session {
+    level: &'static dyn Level,
+    avail_funcs: Vec<String>,
+    script: String,
+    initial_state_index: usize,
+    /// The current step of the session.
+    curr_step: usize,
+    /// Where the session is currently paused. Only set if the session was last
+    /// moved with resume_internal.
+    pause: Option<Pause>,
+    /// The result of the run with the most steps so far, along with whether
+    /// the script finished in that run (i.e. it was not cut off by the step
+    /// limit or a pause).
+    cached: Option<(ScriptResult, bool)>,
+}
+
+/// The different ways to resume a session (see Game::resume_internal).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResumeMode {
+    /// Pause at the next statement, including statements inside of
+    /// function calls.
+    StepInto,
+    /// Pause at the next statement, skipping over function calls.
+    StepOver,
+    /// Pause at the next statement after the current function returns.
+    StepOut,
+    /// Pause at the next breakpoint.
+    Continue,
+}
+
+/// The state and active lines for a single step of a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionStep {
+    /// The step number (step 0 is the initial state).
+    pub step: usize,
+    pub state: State,
+    /// The active line numbers for this step (see ScriptResult::trace).
+    pub lines: Vec<usize>,
+    /// The variables in scope for this step (see ScriptResult::vars).
+    pub vars: Vec<VarSnapshot>,
+    /// The outcome as of this step.
+    pub outcome: Outcome,
+    /// True if this is the last step, i.e. the script finished running or the
+    /// simulation ended (e.g. because the objective was met).
+    pub done: bool,
+    /// The full result of running the script. Only present if done is true.
+    pub result: Option<ScriptResult>,
+    /// Where the script is paused (if it is). If set, lines and vars reflect
+    /// the statement the script is paused at rather than the last function
+    /// call which stepped the simulation forward.
+    pub pause: Option<Pause>,
+    /// Errors from evaluating breakpoint conditions while resuming (see
+    /// Game::set_breakpoint_internal). These are mistakes in the breakpoint
+    /// rather than in the script, so the breakpoint is treated as not hit and
+    /// the script keeps running.
+    pub breakpoint_errors: Vec<BetterError>,
+}
+
+impl Session {
+    /// Caches the given result if it has more steps than the cached one.
+    fn cache_result(&mut self, result: ScriptResult, finished: bool) {
+        let is_longer = match &self.cached {
+            Some((cached, cached_finished)) => {
+                !cached_finished && (finished || result.states.len() > cached.states.len())
+            }
+            None => true,
+        };
+        if is_longer {
+            self.cached = Some((result, finished));
+        }
+    }
+}
+
+fn err_no_session() -> BetterError {
+    BetterError {
+        message: String::from("No session is running. Start a session first."),
+        line: None,
+        col: None,
+        output: vec![],
+    }
+}
+
+impl Game {
+    /// Starts a new session for running the given script one step at a time.
+    /// If state_index is None, one of the possible initial states for the level
+    /// is chosen at random. Returns step 0 (i.e. the initial state). Any
+    /// existing session is replaced.
+    ///
+    /// Returns an error if the script can't be compiled or the state index is
+    /// invalid.
+    pub fn start_internal(
+        &mut self,
+        level: &'static dyn Level,
+        unlocked_funcs: &Vec<String>,
+        script: String,
+        state_index: Option<usize>,
+    ) -> Result<SessionStep, BetterError> {
+        self.session = None;
+        let num_states = level.initial_states().len();
+        let initial_state_index = match state_index {
+            Some(i) if i >= num_states => {
+                return Err(BetterError {
+                    message: format!(
+                        "Invalid initial state index {} (level {} has {} possible initial states)",
+                        i,
+                        level.short_name(),
+                        num_states
+                    ),
+                    line: None,
+                    col: None,
+                    output: vec![],
+                })
+            }
+            Some(i) => i,
+            None => rand::thread_rng().gen_range(0..num_states),
+        };
+        self.session = Some(Session {
+            level,
+            avail_funcs: get_avail_funcs(level, unlocked_funcs),
+            script,
+            initial_state_index,
+            curr_step: 0,
+            pause: None,
+            cached: None,
+        });
+        let result = self.run_session_to(0);
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+
+    /// Advances the current session by one step and returns the new step. If
+    /// the session is already done, returns the last step again.
+    pub fn step_internal(&mut self) -> Result<SessionStep, BetterError> {
+        let curr_step = match &self.session {
+            Some(session) => session.curr_step,
+            None => return Err(err_no_session()),
+        };
+        self.run_session_to(curr_step + 1)
+    }
+
+    /// Moves the current session to the given step (which may also be an
+    /// earlier step). If the script finishes before the given step, stops at
+    /// the last step.
+    pub fn continue_to_internal(&mut self, step: usize) -> Result<SessionStep, BetterError> {
+        if self.session.is_none() {
+            return Err(err_no_session());
+        }
+        self.run_session_to(step)
+    }
+
+    /// Resumes the current session and pauses again according to the given
+    /// mode. If the session is not paused (e.g. it was moved with
+    /// step_internal), execution resumes from the current step, i.e. it pauses
+    /// at the first statement after the one that produced the current step. If
+    /// the script finishes without pausing, returns the last step.
+    pub fn resume_internal(&mut self, mode: ResumeMode) -> Result<SessionStep, BetterError> {
+        let session = self.session.as_ref().ok_or_else(err_no_session)?;
+        let (after, after_step, depth) = match &session.pause {
+            Some(pause) => (Some(pause.stmt_index), None, pause.depth),
+            None => (None, Some(session.curr_step), 0),
+        };
+        let pause_target = match mode {
+            ResumeMode::StepInto => PauseTarget {
+                after,
+                after_step,
+                ..PauseTarget::default()
+            },
+            ResumeMode::StepOver => PauseTarget {
+                after,
+                after_step,
+                max_depth: Some(depth),
+                ..PauseTarget::default()
+            },
+            ResumeMode::StepOut if depth > 0 => PauseTarget {
+                after,
+                after_step,
+                max_depth: Some(depth - 1),
+                ..PauseTarget::default()
+            },
+            // Stepping out of the top level of the script is the same as
+            // continuing.
+            ResumeMode::StepOut | ResumeMode::Continue => PauseTarget {
+                after,
+                after_step,
+                breakpoints: Some(self.breakpoints.clone()),
+                ..PauseTarget::default()
+            },
+        };
+        let (result, _, pause) = self.run_session(None, Some(pause_target))?;
+        let breakpoint_errors = self.script_runner.take_condition_errors();
+        let level = self.session.as_ref().ok_or_else(err_no_session)?.level;
+
+        let step = result.states.len() - 1;
+        let state = result.states[step].clone();
+        let session_step = match pause.clone() {
+            Some(pause) => {
+                let mut lines: Vec<usize> = pause.call_stack.iter().map(|f| f.line).collect();
+                lines.push(pause.line);
+                SessionStep {
+                    step,
+                    outcome: level.check_win(&state),
+                    state,
+                    lines,
+                    vars: pause.vars.clone(),
+                    done: false,
+                    result: None,
+                    pause: Some(pause),
+                    breakpoint_errors,
+                }
+            }
+            None => SessionStep {
+                step,
+                state,
+                lines: result.trace.get(step).cloned().unwrap_or_default(),
+                vars: result.vars.get(step).cloned().unwrap_or_default(),
+                outcome: result.outcome.clone(),
+                done: true,
+                result: Some(result.clone()),
+                pause: None,
+                breakpoint_errors,
+            },
+        };
+        if let Some(session) = self.session.as_mut() {
+            session.curr_step = step;
+            session.cache_result(result, pause.is_none());
+            session.pause = pause;
+        }
+        Ok(session_step)
+    }
+
+    /// Sets a breakpoint at the given line, replacing any existing breakpoint
+    /// on the same line. If condition is given, the session only pauses at the
+    /// breakpoint if the condition (a Rhai expression) evaluates to true.
+    /// Breakpoints are kept when a new session is started.
+    ///
+    /// Conditions are evaluated on a separate engine which doesn't have any of
+    /// the built-in functions (e.g. get_position) or the functions defined in
+    /// the script, so they can only use variables in scope and operators. If a
+    /// condition can't be evaluated or doesn't evaluate to a bool, the
+    /// breakpoint is treated as not hit and the error is reported in
+    /// SessionStep::breakpoint_errors.
+    ///
+    /// Returns an error if the condition can't be compiled.
+    pub fn set_breakpoint_internal(
+        &mut self,
+        line: usize,
+        condition: Option<String>,
+    ) -> Result<(), BetterError> {
+        if let Some(condition) = &condition {
+            let limits = match &self.session {
+                Some(session) => session.level.engine_limits(),
+                None => EngineLimits::default(),
+            };
+            if let Err(err) = new_condition_engine(&limits).compile_expression(condition) {
+                return Err(BetterError {
+                    message: format!("Invalid breakpoint condition: {}", err.0),
+                    line: Some(line),
+                    col: None,
+                    output: vec![],
+                });
+            }
+        }
+        self.remove_breakpoint_internal(line);
+        self.breakpoints.push(Breakpoint { line, condition });
+        Ok(())
+    }
+
+    /// Removes the breakpoint at the given line (if any).
+    pub fn remove_breakpoint_internal(&mut self, line: usize) {
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.line != line);
+    }
+
+    /// Moves the current session to the given step, running the script
+    /// further if the step hasn't been computed yet, and updates the current
+    /// step.
+    fn run_session_to(&mut self, step: usize) -> Result<SessionStep, BetterError> {
+        let session = self.session.as_ref().ok_or_else(err_no_session)?;
+        let level = session.level;
+
+        // We need one step more than the given step. This makes it possible to
+        // tell whether the given step is the last one.
+        let needs_run = match &session.cached {
+            Some((result, finished)) => !finished && result.states.len() <= step + 1,
+            None => true,
+        };
+        if needs_run {
+            let computed = session.cached.as_ref().map_or(0, |(r, _)| r.states.len());
+            let limit = std::cmp::max(step + 1, computed * 2);
+            let (result, limit_reached, _) = self.run_session(Some(limit), None)?;
+            if let Some(session) = self.session.as_mut() {
+                session.cache_result(result, !limit_reached);
+            }
+        }
+
+        // Only clone the parts of the cached result that are needed for this
+        // step, since the cache may hold a lot of states.
+        let session = self.session.as_mut().ok_or_else(err_no_session)?;
+        let (result, finished) = match &session.cached {
+            Some((result, finished)) => (result, *finished),
+            None => return Err(err_no_session()),
+        };
+
+        // If the script finished at or before the given step, stop at the last
+        // step.
+        let last_step = result.states.len() - 1;
+        let (step, done) = if finished && step >= last_step {
+            (last_step, true)
+        } else {
+            (step, false)
+        };
+        session.curr_step = step;
+        session.pause = None;
+
+        let state = result.states[step].clone();
+        let lines = result.trace.get(step).cloned().unwrap_or_default();
+        let vars = result.vars.get(step).cloned().unwrap_or_default();
+        let outcome = if done {
+            result.outcome.clone()
+        } else {
+            level.check_win(&state)
+        };
+        Ok(SessionStep {
+            step,
+            state,
+            lines,
+            vars,
+            outcome,
+            done,
+            result: if done { Some(result.clone()) } else { None },
+            pause: None,
+            breakpoint_errors: vec![],
+        })
+    }
+
+    /// Runs the script for the current session from the beginning, with
+    /// the given step limit and pause target. Returns the result, whether the
+    /// step limit was reached, and where the script was paused (if it was).
+    fn run_session(
+        &mut self,
+        step_limit: Option<usize>,
+        pause_target: Option<PauseTarget>,
+    ) -> Result<(ScriptResult, bool, Option<Pause>), BetterError> {
+        let session = self.session.as_ref().ok_or_else(err_no_session)?;
+        {
+            let mut simulation = self.simulation.borrow_mut();
+            simulation.load_level(session.level, session.initial_state_index);
+            simulation.set_step_limit(step_limit);
+        }
+        // Drain the channel.
+        while self.player_action_rx.clone().borrow().try_recv().is_ok() {}
+        self.script_runner.set_pause_target(pause_target);
+        let result =
+            self.script_runner
+                .run(&session.avail_funcs, session.level, session.script.as_str());
+        let limit_reached = self.simulation.borrow().reached_step_limit();
+        let pause = self.script_runner.take_pause();
+        self.script_runner.set_pause_target(None);
+        self.simulation.borrow_mut().set_step_limit(None);
+        Ok((result?, limit_reached, pause))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::levels::{Outcome, LEVELS};
+    use crate::script_runner::StackFrame;
+    use crate::session::ResumeMode;
+    use crate::simulation::Pos;
+    use crate::Game;
+
+    fn all_funcs() -> Vec<String> {
+        crate::BUILTIN_FUNCTIONS
+            .keys()
+            .map(|f| f.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn step_through_script() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = "move_forward(3);\nturn_right();\nmove_forward(3);";
+        let mut game = Game::new();
+        let full_result = game
+            .run_player_script_internal(level, &all_funcs(), script.to_string())
+            .unwrap();
+
+        let first = game
+            .start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+        assert_eq!(first.step, 0);
+        assert_eq!(first.state, full_result.states[0]);
+        assert_eq!(first.lines, Vec::<usize>::new());
+        assert_eq!(first.outcome, Outcome::Continue);
+        assert!(!first.done);
+
+        // Stepping through the script should give the same states and trace
+        // as running it all at once.
+        for i in 1..full_result.states.len() {
+            let step = game.step_internal().unwrap();
+            assert_eq!(step.step, i);
+            assert_eq!(step.state, full_result.states[i]);
+            assert_eq!(step.lines, full_result.trace[i]);
+            assert_eq!(step.vars, full_result.vars[i]);
+            let is_last = i == full_result.states.len() - 1;
+            assert_eq!(step.done, is_last);
+            if is_last {
+                assert_eq!(step.outcome, Outcome::Success);
+                assert_eq!(step.result.unwrap(), full_result);
+            } else {
+                assert_eq!(step.outcome, Outcome::Continue);
+                assert!(step.result.is_none());
+            }
+        }
+
+        // Stepping past the end should keep returning the last step.
+        let step = game.step_internal().unwrap();
+        assert_eq!(step.step, full_result.states.len() - 1);
+        assert!(step.done);
+    }
+
+    #[test]
+    fn continue_to_and_abort() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = "move_forward(3);\nturn_right();\nmove_forward(3);";
+        let mut game = Game::new();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+
+        let step = game.continue_to_internal(4).unwrap();
+        assert_eq!(step.step, 4);
+        assert_eq!(step.state.player.pos, Pos::new(3, 0));
+        assert_eq!(step.lines, vec![2]);
+
+        // Going backwards is also allowed.
+        let step = game.continue_to_internal(2).unwrap();
+        assert_eq!(step.step, 2);
+        assert_eq!(step.state.player.pos, Pos::new(2, 0));
+        let step = game.step_internal().unwrap();
+        assert_eq!(step.step, 3);
+
+        // Continuing past the end stops at the last step.
+        let step = game.continue_to_internal(100).unwrap();
+        assert_eq!(step.step, 7);
+        assert!(step.done);
+        assert_eq!(step.outcome, Outcome::Success);
+
+        game.abort();
+        assert!(game.step_internal().is_err());
+        assert!(game.continue_to_internal(1).is_err());
+    }
+
+    #[test]
+    fn runtime_error_ends_session() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = "move_forward(1);\nread_data();\nmove_forward(1);";
+        let mut game = Game::new();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+        let step = game.step_internal().unwrap();
+        assert_eq!(step.step, 1);
+        assert!(!step.done);
+        let step = game.step_internal().unwrap();
+        assert_eq!(step.step, 2);
+        assert!(step.done);
+        assert!(matches!(step.outcome, Outcome::Failure(_)));
+    }
+
+    #[test]
+    fn start_errors() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let mut game = Game::new();
+        // Syntax errors are reported when starting the session.
+        assert!(game
+            .start_internal(level, &all_funcs(), "move_forward(".to_string(), None)
+            .is_err());
+        assert!(game.step_internal().is_err());
+        // So are invalid initial state indexes.
+        assert!(game
+            .start_internal(level, &all_funcs(), "move_forward(1);".to_string(), Some(1))
+            .is_err());
+    }
+
+    #[test]
+    fn step_into_over_and_out() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = r#"fn turn() {
+  turn_right();
+  let x = 1;
+}
+move_forward(3);
+turn();
+move_forward(3);
+"#;
+        let mut game = Game::new();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+
+        // The first resume starts from the beginning of the script.
+        let step = game.resume_internal(ResumeMode::StepInto).unwrap();
+        let pause = step.pause.unwrap();
+        assert_eq!(pause.line, 5);
+        assert_eq!(pause.depth, 0);
+        assert_eq!(step.step, 0);
+        assert!(!step.done);
+
+        let step = game.resume_internal(ResumeMode::StepInto).unwrap();
+        assert_eq!(step.pause.unwrap().line, 6);
+        assert_eq!(step.step, 3);
+        assert_eq!(step.state.player.pos, Pos::new(3, 0));
+
+        // Step into the function.
+        let step = game.resume_internal(ResumeMode::StepInto).unwrap();
+        let pause = step.pause.unwrap();
+        assert_eq!(pause.line, 2);
+        assert_eq!(pause.depth, 1);
+        assert_eq!(
+            pause.call_stack,
+            vec![StackFrame {
+                fn_name: String::from("turn"),
+                line: 6
+            }]
+        );
+        assert_eq!(step.lines, vec![6, 2]);
+
+        let step = game.resume_internal(ResumeMode::StepOver).unwrap();
+        assert_eq!(step.pause.unwrap().line, 3);
+        assert_eq!(step.step, 4);
+
+        let step = game.resume_internal(ResumeMode::StepOut).unwrap();
+        let pause = step.pause.unwrap();
+        assert_eq!(pause.line, 7);
+        assert_eq!(pause.depth, 0);
+
+        // Stepping past the last statement finishes the script.
+        let step = game.resume_internal(ResumeMode::StepOver).unwrap();
+        assert!(step.done);
+        assert!(step.pause.is_none());
+        assert_eq!(step.outcome, Outcome::Success);
+    }
+
+    #[test]
+    fn resume_after_step() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = "move_forward(1);\nturn_right();\nmove_forward(1);\n";
+        let mut game = Game::new();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+        let step = game.step_internal().unwrap();
+        assert_eq!(step.step, 1);
+
+        // Resuming a session that was moved with step_internal continues from
+        // the current step instead of going back to the start.
+        let step = game.resume_internal(ResumeMode::StepOver).unwrap();
+        assert_eq!(step.pause.unwrap().line, 2);
+        assert_eq!(step.step, 1);
+        assert_eq!(step.state.player.pos, Pos::new(1, 0));
+
+        let step = game.resume_internal(ResumeMode::StepOver).unwrap();
+        assert_eq!(step.pause.unwrap().line, 3);
+        assert_eq!(step.step, 2);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = r#"let i = 0;
+while i < 3 {
+  move_forward(1);
+  i += 1;
+}
+turn_right();
+"#;
+        let mut game = Game::new();
+        game.set_breakpoint_internal(4, Some(String::from("i == 1")))
+            .unwrap();
+        game.set_breakpoint_internal(6, None).unwrap();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+
+        // The condition is only true on the second iteration.
+        let step = game.resume_internal(ResumeMode::Continue).unwrap();
+        let pause = step.pause.unwrap();
+        assert_eq!(pause.line, 4);
+        assert_eq!(pause.vars[0].name, "i");
+        assert_eq!(pause.vars[0].value, "1");
+        assert_eq!(step.step, 2);
+
+        let step = game.resume_internal(ResumeMode::Continue).unwrap();
+        assert_eq!(step.pause.unwrap().line, 6);
+        assert_eq!(step.step, 3);
+
+        // Stepping out of the top level is the same as continuing.
+        game.remove_breakpoint_internal(6);
+        let step = game.continue_to_internal(1).unwrap();
+        assert!(step.pause.is_none());
+        let step = game.resume_internal(ResumeMode::StepOut).unwrap();
+        assert_eq!(step.pause.unwrap().line, 4);
+
+        let step = game.resume_internal(ResumeMode::Continue).unwrap();
+        assert!(step.done);
+        assert_eq!(step.step, 4);
+
+        assert!(game
+            .set_breakpoint_internal(1, Some(String::from("i ==")))
+            .is_err());
+    }
+
+    #[test]
+    fn breakpoint_condition_errors() {
+        let level = LEVELS.get("movement").unwrap().as_ref();
+        let script = r#"let i = 0;
+while i < 3 {
+  move_forward(1);
+  i += 1;
+}
+"#;
+        let mut game = Game::new();
+        // The condition compiles, but doesn't evaluate to a bool.
+        game.set_breakpoint_internal(4, Some(String::from("i + 1")))
+            .unwrap();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+
+        // The breakpoint is treated as not hit, so the script runs to the end
+        // and the error is only reported once.
+        let step = game.resume_internal(ResumeMode::Continue).unwrap();
+        assert!(step.done);
+        assert!(step.pause.is_none());
+        assert!(!matches!(step.outcome, Outcome::Failure(_)));
+        assert_eq!(step.breakpoint_errors.len(), 1);
+        assert_eq!(step.breakpoint_errors[0].line, Some(4));
+        assert!(step.breakpoint_errors[0]
+            .message
+            .starts_with("Invalid breakpoint condition"));
+
+        // Built-in functions can't be called in conditions. A breakpoint on
+        // another line still works.
+        game.set_breakpoint_internal(4, Some(String::from("get_position().x > 1")))
+            .unwrap();
+        game.set_breakpoint_internal(3, Some(String::from("i == 2")))
+            .unwrap();
+        game.start_internal(level, &all_funcs(), script.to_string(), None)
+            .unwrap();
+        let step = game.resume_internal(ResumeMode::Continue).unwrap();
+        assert!(!step.done);
+        assert_eq!(step.pause.unwrap().line, 3);
+        assert_eq!(step.breakpoint_errors.len(), 1);
+        assert_eq!(step.breakpoint_errors[0].line, Some(4));
+    }
+}
@@ -1,19 +1,35 @@
 use rhai::Dynamic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
+use std::rc::Rc;
 
 use crate::{
     actors::PlayerChannelActor,
     constants::MAX_ENERGY,
     levels::{Level, Outcome, LEVELS},
+    spatial_index::SpatialIndex,
 };
 
 pub trait Actor {
-    fn apply(&mut self, state: State) -> State;
+    /// Returns the next state after the actor takes its action. spatial_index
+    /// must be the SpatialIndex for the initial state that the given state was
+    /// derived from.
+    fn apply(&mut self, state: State, spatial_index: &SpatialIndex) -> State;
 }
 
 pub struct Simulation {
     state_idx: usize,
+    /// The index of the initial state (out of all possible initial states for
+    /// the current level) that was used for the current run.
+    initial_state_idx: usize,
+    /// If set, the simulation will not step forward past this step. This is
+    /// used to run a script only up to a certain step (see Session).
+    step_limit: Option<usize>,
     states: Vec<State>,
+    /// Index of the entities in the initial state which never move. Used by
+    /// actors for fast position lookups.
+    spatial_index: SpatialIndex,
     player_actor: PlayerChannelActor,
     level: &'static dyn Level,
     last_outcome: Outcome,
@@ -23,7 +39,10 @@ impl Simulation {
     pub fn new(player_actor: PlayerChannelActor) -> Simulation {
         let sim = Simulation {
             state_idx: 0,
+            initial_state_idx: 0,
+            step_limit: None,
             states: vec![],
+            spatial_index: SpatialIndex::default(),
             player_actor,
             // Start with the first level by default. Will be overwritten by
             // load_level.
@@ -42,9 +61,14 @@ impl Simulation {
     pub fn load_level(&mut self, level: &'static dyn Level, seed: usize) {
         self.level = level;
         self.state_idx = 0;
+        self.initial_state_idx = seed;
+        self.step_limit = None;
         self.player_actor.set_bounds(level.bounds());
+        self.player_actor.set_level(level);
+        let initial_state = self.level.initial_states()[seed].clone();
+        self.spatial_index = SpatialIndex::new(&initial_state);
         self.states.clear();
-        self.states.push(self.level.initial_states()[seed].clone());
+        self.states.push(initial_state);
         self.last_outcome = Outcome::Continue;
     }
 
@@ -52,14 +76,45 @@ impl Simulation {
         self.level
     }
 
+    /// Returns the index of the initial state that was loaded by load_level.
+    pub fn initial_state_index(&self) -> usize {
+        self.initial_state_idx
+    }
+
+    /// Sets the maximum number of steps that the simulation may take. Calls to
+    /// step_forward have no effect once the limit has been reached. Note that
+    /// load_level resets the limit.
+    pub fn set_step_limit(&mut self, step_limit: Option<usize>) {
+        self.step_limit = step_limit;
+    }
+
+    /// Returns true if there is a step limit and it has been reached.
+    pub fn reached_step_limit(&self) -> bool {
+        match self.step_limit {
+            Some(limit) => self.state_idx >= limit,
+            None => false,
+        }
+    }
+
     pub fn curr_state(&self) -> State {
         self.states[self.state_idx].clone()
     }
 
+    /// Returns the SpatialIndex for the current run.
+    pub fn spatial_index(&self) -> &SpatialIndex {
+        &self.spatial_index
+    }
+
     pub fn get_history(&self) -> Vec<State> {
         self.states.to_vec()
     }
 
+    /// Returns the number of states in the history (including the initial
+    /// state).
+    pub fn history_len(&self) -> usize {
+        self.states.len()
+    }
+
     // TODO(albrow): Can we avoid cloning the outcome here and in other places?
     pub fn last_outcome(&self) -> Outcome {
         self.last_outcome.clone()
@@ -71,11 +126,15 @@ impl Simulation {
         if self.last_outcome != Outcome::Continue && self.last_outcome != Outcome::NoObjective {
             return self.last_outcome.clone();
         }
+        // Likewise, we can't take any more steps if we've reached the step limit.
+        if self.reached_step_limit() {
+            return self.last_outcome.clone();
+        }
 
         // Otherwise, compute the next state and store it.
         let mut next_state = self.curr_state().clone();
         // 1. Apply the player actor first, separately from the other actors.
-        next_state = self.player_actor.apply(next_state);
+        next_state = self.player_actor.apply(next_state, &self.spatial_index);
         // 2. Check for win or lose conditions.
         let outcome = self.level.check_win(&next_state);
         match outcome {
@@ -98,7 +157,7 @@ impl Simulation {
         }
         // 3. Apply the other actors.
         for actor in &mut self.level.actors() {
-            next_state = actor.apply(next_state);
+            next_state = actor.apply(next_state, &self.spatial_index);
         }
         // 4. Check for win or lose conditions again.
         let outcome = self.level.check_win(&next_state);
@@ -132,15 +191,25 @@ impl Simulation {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct State {
     pub player: Player,
     pub goals: Vec<Goal>,
-    pub obstacles: Vec<Obstacle>,
+    /// Obstacles never change, so they are shared between all the states
+    /// that follow from the same initial state instead of being copied on
+    /// every step.
+    pub obstacles: Rc<Vec<Obstacle>>,
     pub energy_cells: Vec<EnergyCell>,
     pub buttons: Vec<Button>,
     pub gates: Vec<Gate>,
-    pub data_points: Vec<DataPoint>,
+    /// Data points are also shared between states. The only thing that can
+    /// change is the reading state, so use Rc::make_mut to update it (which
+    /// only copies the data points when they are actually changed).
+    pub data_points: Rc<Vec<DataPoint>>,
+    /// Defaults to empty so that states serialized before input terminals
+    /// were added can still be loaded.
+    #[serde(default)]
+    pub input_terminals: Vec<InputTerminal>,
     pub password_gates: Vec<PasswordGate>,
     pub telepads: Vec<Telepad>,
     pub enemies: Vec<Enemy>,
@@ -152,12 +221,13 @@ impl State {
         State {
             player: Player::new(0, 0, MAX_ENERGY, Orientation::Right),
             goals: vec![],
-            obstacles: vec![],
+            obstacles: Rc::new(vec![]),
             energy_cells: vec![],
             buttons: vec![],
             gates: vec![],
             password_gates: vec![],
-            data_points: vec![],
+            data_points: Rc::new(vec![]),
+            input_terminals: vec![],
             telepads: vec![],
             enemies: vec![],
             big_enemies: vec![],
@@ -183,6 +253,7 @@ impl fmt::Debug for State {
             .field("buttons", &self.buttons)
             .field("gates", &self.gates)
             .field("data_points", &self.data_points)
+            .field("input_terminals", &self.input_terminals)
             .field("password_gates", &self.password_gates)
             .field("telepads", &self.telepads)
             .field("enemies", &self.enemies)
@@ -190,7 +261,7 @@ impl fmt::Debug for State {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Copy)]
+#[derive(Clone, PartialEq, Debug, Copy, Serialize, Deserialize)]
 pub enum Orientation {
     Up,
     Down,
@@ -227,7 +298,7 @@ impl Orientation {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Copy)]
+#[derive(Clone, PartialEq, Debug, Copy, Serialize, Deserialize)]
 pub enum OrientationWithDiagonals {
     Up,
     Down,
@@ -300,7 +371,7 @@ impl OrientationWithDiagonals {
 /// The animation state of the player sprite. This is used in
 /// in the UI to give more clarity to the player about what is
 /// happening.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PlayerAnimState {
     Idle,
     Moving,
@@ -309,20 +380,20 @@ pub enum PlayerAnimState {
     Bumping(BumpAnimData),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct TeleAnimData {
     pub start_pos: Pos, // The position of the rover before entering telepad.
     pub enter_pos: Pos, // The position of the telepad entrance.
     pub exit_pos: Pos,  // The position of the telepad exit.
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct BumpAnimData {
     pub pos: Pos,          // The position of the rover.
     pub obstacle_pos: Pos, // The position of the obstacle the rover is bumping into.
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Player {
     pub pos: Pos,
     pub energy: u32,
@@ -345,7 +416,7 @@ impl Player {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct EnergyCell {
     pub pos: Pos,
     pub collected: bool,
@@ -364,7 +435,7 @@ impl EnergyCell {
 }
 
 // Indicates what the button is connected to.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum ButtonConnection {
     /// The button is not connected to anything.
     None,
@@ -372,7 +443,7 @@ pub enum ButtonConnection {
     Gate(usize),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Button {
     pub pos: Pos,
     pub currently_pressed: bool,
@@ -412,7 +483,7 @@ impl Button {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Goal {
     pub pos: Pos,
 }
@@ -429,7 +500,7 @@ impl Goal {
 }
 
 /// The animation state of the enemy sprite.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum EnemyAnimState {
     Idle,
     Moving,
@@ -439,7 +510,7 @@ pub enum EnemyAnimState {
 }
 
 /// The animation state of the big enemy sprite.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum BigEnemyAnimState {
     Idle,
     Moving,
@@ -447,7 +518,7 @@ pub enum BigEnemyAnimState {
     Bumping(BumpAnimData),
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Enemy {
     pub pos: Pos,
     pub facing: Orientation,
@@ -467,7 +538,7 @@ impl Enemy {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct BigEnemy {
     /// The position of the top left corner of the enemy.
     pub pos: Pos,
@@ -488,7 +559,7 @@ impl BigEnemy {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 /// The kind of obstacle. This determines how the obstacle is drawn, but
 /// doesn't affect the behavior of the simulation.
 pub enum ObstacleKind {
@@ -497,7 +568,7 @@ pub enum ObstacleKind {
     Asteroid,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Obstacle {
     pub pos: Pos,
     pub kind: ObstacleKind,
@@ -527,19 +598,19 @@ impl Obstacle {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Hash, Eq)]
+#[derive(Clone, PartialEq, Debug, Hash, Eq, Serialize, Deserialize)]
 pub struct AsteroidWarning {
     pub pos: Pos,
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum GateVariant {
     NWSE,
     NESW,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Gate {
     pub pos: Pos,
     pub open: bool,
@@ -582,7 +653,7 @@ impl Gate {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct PasswordGate {
     pub pos: Pos,
     pub open: bool,
@@ -634,10 +705,59 @@ impl PasswordGate {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+/// Data which is output by a data point or written to an input terminal.
+/// TermData is serialized without a tag
+/// so that it maps directly to the corresponding JSON type (a string, number,
+/// boolean, array, or object).
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
 pub enum TermData {
     String(String),
     Array(Vec<TermData>),
+    Int(i64),
+    Bool(bool),
+    /// A map from field names to values. A BTreeMap is used so that the
+    /// fields are always in the same order.
+    Map(BTreeMap<String, TermData>),
+}
+
+impl TermData {
+    /// Returns the name of the type as it is described to the user (e.g. in
+    /// the hover info for a data point).
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TermData::String(_) => "string",
+            TermData::Array(_) => "array",
+            TermData::Int(_) => "number",
+            TermData::Bool(_) => "boolean",
+            TermData::Map(_) => "map",
+        }
+    }
+
+    /// Converts a value from a script. Returns None if the value (or any
+    /// value nested inside of it) can't be represented as TermData.
+    pub fn from_dynamic(value: &Dynamic) -> Option<TermData> {
+        if let Ok(s) = value.clone().into_string() {
+            Some(TermData::String(s))
+        } else if let Ok(n) = value.as_int() {
+            Some(TermData::Int(n))
+        } else if let Ok(b) = value.as_bool() {
+            Some(TermData::Bool(b))
+        } else if let Some(array) = value.read_lock::<rhai::Array>() {
+            array
+                .iter()
+                .map(TermData::from_dynamic)
+                .collect::<Option<_>>()
+                .map(TermData::Array)
+        } else if let Some(map) = value.read_lock::<rhai::Map>() {
+            map.iter()
+                .map(|(k, v)| Some((k.to_string(), TermData::from_dynamic(v)?)))
+                .collect::<Option<_>>()
+                .map(TermData::Map)
+        } else {
+            None
+        }
+    }
 }
 
 impl From<String> for TermData {
@@ -652,22 +772,45 @@ impl From<&str> for TermData {
     }
 }
 
+impl From<i64> for TermData {
+    fn from(n: i64) -> TermData {
+        TermData::Int(n)
+    }
+}
+
+impl From<bool> for TermData {
+    fn from(b: bool) -> TermData {
+        TermData::Bool(b)
+    }
+}
+
 impl<T: Into<TermData>> From<Vec<T>> for TermData {
     fn from(v: Vec<T>) -> TermData {
         TermData::Array(v.into_iter().map(|x| x.into()).collect())
     }
 }
 
+impl<T: Into<TermData>> From<BTreeMap<String, T>> for TermData {
+    fn from(m: BTreeMap<String, T>) -> TermData {
+        TermData::Map(m.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
 impl From<TermData> for Dynamic {
     fn from(data: TermData) -> Dynamic {
         match data {
             TermData::String(s) => s.into(),
             TermData::Array(v) => Dynamic::from_array(v.into_iter().map(|x| x.into()).collect()),
+            TermData::Int(n) => n.into(),
+            TermData::Bool(b) => b.into(),
+            TermData::Map(m) => {
+                Dynamic::from_map(m.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+            }
         }
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct DataPoint {
     pub pos: Pos,
     pub data: TermData,
@@ -702,7 +845,51 @@ impl DataPoint {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Hash, Eq)]
+/// A terminal which the rover can write data to (see write_data). The level
+/// decides whether the data is correct (see Level::check_input).
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct InputTerminal {
+    pub pos: Pos,
+    /// What the terminal is connected to. A connected gate is opened when
+    /// correct data is written to the terminal.
+    pub connection: ButtonConnection,
+    /// The last data that was written to the terminal (if any).
+    pub last_input: Option<TermData>,
+    /// Whether the last data written to the terminal was correct.
+    pub accepted: bool,
+    /// Whether data was written to the terminal in the current step. (Only
+    /// affects the UI).
+    pub writing: bool,
+    /// Additional information that will be displayed in the UI.
+    /// (e.g. explain what data the terminal expects)
+    pub additional_info: String,
+}
+
+impl InputTerminal {
+    pub fn new(x: u32, y: u32, connection: ButtonConnection) -> InputTerminal {
+        InputTerminal::new_with_info(x, y, connection, String::new())
+    }
+    pub fn new_with_info(
+        x: u32,
+        y: u32,
+        connection: ButtonConnection,
+        additional_info: String,
+    ) -> InputTerminal {
+        InputTerminal {
+            pos: Pos {
+                x: x as i32,
+                y: y as i32,
+            },
+            connection,
+            last_input: None,
+            accepted: false,
+            writing: false,
+            additional_info,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Hash, Eq, Serialize, Deserialize)]
 pub struct Pos {
     pub x: i32,
     pub y: i32,
@@ -713,6 +900,23 @@ impl Pos {
         Pos { x, y }
     }
 
+    /// Returns the position next to this one in the given direction.
+    pub fn adjacent(&self, direction: Orientation) -> Pos {
+        match direction {
+            Orientation::Up => Pos::new(self.x, self.y - 1),
+            Orientation::Down => Pos::new(self.x, self.y + 1),
+            Orientation::Left => Pos::new(self.x - 1, self.y),
+            Orientation::Right => Pos::new(self.x + 1, self.y),
+        }
+    }
+
+    /// Returns the number of spaces between this position and the other one,
+    /// moving only horizontally and vertically. Returns i64 so that the
+    /// distance between any two positions fits without overflowing.
+    pub fn manhattan_distance(&self, other: &Pos) -> i64 {
+        (self.x as i64 - other.x as i64).abs() + (self.y as i64 - other.y as i64).abs()
+    }
+
     // Returns i64 for better Rhai compatibility.
     pub fn get_x(&mut self) -> i64 {
         self.x as i64
@@ -724,50 +928,16 @@ impl Pos {
     }
 }
 
-/// Returns the index of the data point adjacent to the given
-/// position. Returns None if there is no adjacent data point.
-pub fn get_adjacent_point(state: &State, pos: &Pos) -> Option<usize> {
-    for (i, d_point) in state.data_points.iter().enumerate() {
-        if d_point.pos.x == pos.x && d_point.pos.y == pos.y + 1 {
-            return Some(i);
-        }
-        if pos.y != 0 && d_point.pos.x == pos.x && d_point.pos.y == pos.y - 1 {
-            return Some(i);
-        }
-        if d_point.pos.x == pos.x + 1 && d_point.pos.y == pos.y {
-            return Some(i);
-        }
-        if pos.x != 0 && d_point.pos.x == pos.x - 1 && d_point.pos.y == pos.y {
-            return Some(i);
-        }
-    }
-    None
-}
-
-/// Returns the index of the button adjacent to the given
-/// position. Returns None if there is no adjacent button.
-pub fn get_adjacent_button(state: &State, pos: &Pos) -> Option<usize> {
-    for (i, button) in state.buttons.iter().enumerate() {
-        if button.pos.x == pos.x && button.pos.y == pos.y + 1 {
-            return Some(i);
-        }
-        if pos.y != 0 && button.pos.x == pos.x && button.pos.y == pos.y - 1 {
-            return Some(i);
-        }
-        if button.pos.x == pos.x + 1 && button.pos.y == pos.y {
-            return Some(i);
-        }
-        if pos.x != 0 && button.pos.x == pos.x - 1 && button.pos.y == pos.y {
-            return Some(i);
-        }
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
     }
-    None
 }
 
 /// Teleportation pads instantly move a rover from one location to another.
 /// As a side-effect, telepads may also change which direction the rover is
 /// facing.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Telepad {
     pub start_pos: Pos,
     pub end_pos: Pos,
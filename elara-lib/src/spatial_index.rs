@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use crate::simulation::{Pos, State};
+
+/// The entities at a single position. Each field holds the index of the
+/// entity in the corresponding Vec in State (if any).
+#[derive(Clone, Debug, Default)]
+struct Cell {
+    obstacle: Option<usize>,
+    data_point: Option<usize>,
+    input_terminal: Option<usize>,
+    button: Option<usize>,
+    gate: Option<usize>,
+    password_gate: Option<usize>,
+    /// Only the entrance (i.e. start_pos) of a telepad is indexed.
+    telepad: Option<usize>,
+}
+
+/// An index of the positions of all entities in a State which never move
+/// (i.e. everything except the player and enemies). It can answer "what is at
+/// (or next to) this position" in constant time instead of scanning every Vec
+/// in State.
+///
+/// Since these entities never move, the index only needs to be built once for
+/// each initial state and is valid for every state that follows from it. Note
+/// that it only stores positions. Anything else (e.g. whether a gate is open)
+/// still needs to be looked up in the current State.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+    cells: HashMap<Pos, Cell>,
+}
+
+impl SpatialIndex {
+    pub fn new(state: &State) -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        // If there is more than one entity of the same kind at a position, we
+        // keep the first one. This matches the behavior of a linear scan.
+        for (i, obstacle) in state.obstacles.iter().enumerate() {
+            index.cell_mut(&obstacle.pos).obstacle.get_or_insert(i);
+        }
+        for (i, data_point) in state.data_points.iter().enumerate() {
+            index.cell_mut(&data_point.pos).data_point.get_or_insert(i);
+        }
+        for (i, terminal) in state.input_terminals.iter().enumerate() {
+            index
+                .cell_mut(&terminal.pos)
+                .input_terminal
+                .get_or_insert(i);
+        }
+        for (i, button) in state.buttons.iter().enumerate() {
+            index.cell_mut(&button.pos).button.get_or_insert(i);
+        }
+        for (i, gate) in state.gates.iter().enumerate() {
+            index.cell_mut(&gate.pos).gate.get_or_insert(i);
+        }
+        for (i, gate) in state.password_gates.iter().enumerate() {
+            index.cell_mut(&gate.pos).password_gate.get_or_insert(i);
+        }
+        for (i, telepad) in state.telepads.iter().enumerate() {
+            index.cell_mut(&telepad.start_pos).telepad.get_or_insert(i);
+        }
+        index
+    }
+
+    fn cell_mut(&mut self, pos: &Pos) -> &mut Cell {
+        self.cells.entry(pos.clone()).or_default()
+    }
+
+    fn cell(&self, pos: &Pos) -> Option<&Cell> {
+        self.cells.get(pos)
+    }
+
+    /// Returns true if there is an obstacle at the given position. Data points,
+    /// input terminals, and buttons are also treated as obstacles since they
+    /// can never move or be opened.
+    pub fn is_obstacle_at(&self, pos: &Pos) -> bool {
+        match self.cell(pos) {
+            Some(cell) => {
+                cell.obstacle.is_some()
+                    || cell.data_point.is_some()
+                    || cell.input_terminal.is_some()
+                    || cell.button.is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if there is a gate at the given position which is closed
+    /// in the given state.
+    pub fn is_closed_gate_at(&self, state: &State, pos: &Pos) -> bool {
+        match self.cell(pos).and_then(|cell| cell.gate) {
+            Some(i) => !state.gates[i].open,
+            None => false,
+        }
+    }
+
+    /// Returns true if there is a password gate at the given position which is
+    /// closed in the given state.
+    pub fn is_closed_password_gate_at(&self, state: &State, pos: &Pos) -> bool {
+        match self.cell(pos).and_then(|cell| cell.password_gate) {
+            Some(i) => !state.password_gates[i].open,
+            None => false,
+        }
+    }
+
+    /// Returns the index of the telepad whose entrance is at the given
+    /// position (if any).
+    pub fn telepad_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.telepad)
+    }
+
+    /// Returns the index of the gate at the given position (if any).
+    pub fn gate_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.gate)
+    }
+
+    /// Returns the index of the password gate at the given position (if any).
+    pub fn password_gate_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.password_gate)
+    }
+
+    /// Returns the index of the data point at the given position (if any).
+    pub fn data_point_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.data_point)
+    }
+
+    /// Returns the index of the input terminal at the given position (if any).
+    pub fn input_terminal_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.input_terminal)
+    }
+
+    /// Returns the index of the button at the given position (if any).
+    pub fn button_at(&self, pos: &Pos) -> Option<usize> {
+        self.cell(pos).and_then(|cell| cell.button)
+    }
+
+    /// Returns the index of the data point adjacent to the given position.
+    /// If there is more than one, returns the one with the lowest index.
+    pub fn adjacent_data_point(&self, pos: &Pos) -> Option<usize> {
+        self.adjacent_cells(pos)
+            .filter_map(|cell| cell.data_point)
+            .min()
+    }
+
+    /// Returns the index of the input terminal adjacent to the given position.
+    /// If there is more than one, returns the one with the lowest index.
+    pub fn adjacent_input_terminal(&self, pos: &Pos) -> Option<usize> {
+        self.adjacent_cells(pos)
+            .filter_map(|cell| cell.input_terminal)
+            .min()
+    }
+
+    /// Returns the index of the button adjacent to the given position. If
+    /// there is more than one, returns the one with the lowest index.
+    pub fn adjacent_button(&self, pos: &Pos) -> Option<usize> {
+        self.adjacent_cells(pos)
+            .filter_map(|cell| cell.button)
+            .min()
+    }
+
+    /// Returns the indexes of all password gates adjacent to the given
+    /// position in ascending order.
+    pub fn adjacent_password_gates(&self, pos: &Pos) -> Vec<usize> {
+        let mut gate_indexes: Vec<usize> = self
+            .adjacent_cells(pos)
+            .filter_map(|cell| cell.password_gate)
+            .collect();
+        gate_indexes.sort_unstable();
+        gate_indexes
+    }
+
+    fn adjacent_cells<'a>(&'a self, pos: &Pos) -> impl Iterator<Item = &'a Cell> {
+        IntoIterator::into_iter([
+            Pos::new(pos.x, pos.y - 1),
+            Pos::new(pos.x, pos.y + 1),
+            Pos::new(pos.x - 1, pos.y),
+            Pos::new(pos.x + 1, pos.y),
+        ])
+        .filter_map(move |adjacent| self.cell(&adjacent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{
+        Button, ButtonConnection, DataPoint, Gate, GateVariant, InputTerminal, Obstacle,
+        Orientation, PasswordGate, Telepad,
+    };
+    use crate::state_maker::StateMaker;
+
+    #[test]
+    fn lookups() {
+        let mut state = StateMaker::new()
+            .with_obstacles(vec![Obstacle::new(1, 1)])
+            .with_data_points(vec![DataPoint::new(2, 2, "foo".into())])
+            .with_buttons(vec![Button::new(3, 3, ButtonConnection::None)])
+            .with_gates(vec![
+                Gate::new(4, 4, false, GateVariant::NWSE),
+                Gate::new(5, 5, true, GateVariant::NWSE),
+            ])
+            .with_password_gates(vec![PasswordGate::new(
+                6,
+                6,
+                "password".into(),
+                false,
+                GateVariant::NESW,
+            )])
+            .with_telepads(vec![Telepad::new((7, 7), (8, 8), Orientation::Up)])
+            .build();
+        state.input_terminals = vec![InputTerminal::new(9, 9, ButtonConnection::None)];
+        let index = SpatialIndex::new(&state);
+
+        assert!(index.is_obstacle_at(&Pos::new(1, 1)));
+        assert!(index.is_obstacle_at(&Pos::new(2, 2)));
+        assert!(index.is_obstacle_at(&Pos::new(3, 3)));
+        assert!(index.is_obstacle_at(&Pos::new(9, 9)));
+        assert!(!index.is_obstacle_at(&Pos::new(4, 4)));
+        assert!(!index.is_obstacle_at(&Pos::new(0, 0)));
+
+        assert!(index.is_closed_gate_at(&state, &Pos::new(4, 4)));
+        assert!(!index.is_closed_gate_at(&state, &Pos::new(5, 5)));
+        assert!(index.is_closed_password_gate_at(&state, &Pos::new(6, 6)));
+        assert!(!index.is_closed_password_gate_at(&state, &Pos::new(4, 4)));
+
+        // Gates can be opened without rebuilding the index.
+        state.gates[0].open = true;
+        assert!(!index.is_closed_gate_at(&state, &Pos::new(4, 4)));
+
+        // Only the telepad entrance is indexed.
+        assert_eq!(index.telepad_at(&Pos::new(7, 7)), Some(0));
+        assert_eq!(index.telepad_at(&Pos::new(8, 8)), None);
+
+        assert_eq!(index.input_terminal_at(&Pos::new(9, 9)), Some(0));
+        assert_eq!(index.adjacent_input_terminal(&Pos::new(9, 8)), Some(0));
+        assert_eq!(index.adjacent_input_terminal(&Pos::new(8, 8)), None);
+    }
+
+    #[test]
+    fn adjacent_lookups() {
+        let state = StateMaker::new()
+            .with_data_points(vec![
+                DataPoint::new(2, 1, "a".into()),
+                DataPoint::new(1, 2, "b".into()),
+            ])
+            .with_buttons(vec![Button::new(5, 5, ButtonConnection::None)])
+            .with_password_gates(vec![
+                PasswordGate::new(3, 2, "a".into(), false, GateVariant::NWSE),
+                PasswordGate::new(2, 3, "b".into(), false, GateVariant::NWSE),
+            ])
+            .build();
+        let index = SpatialIndex::new(&state);
+
+        // Both data points are adjacent to (2, 2). The one with the lowest
+        // index wins.
+        assert_eq!(index.adjacent_data_point(&Pos::new(2, 2)), Some(0));
+        assert_eq!(index.adjacent_data_point(&Pos::new(1, 3)), Some(1));
+        assert_eq!(index.adjacent_data_point(&Pos::new(2, 1)), None);
+        // Diagonals don't count as adjacent.
+        assert_eq!(index.adjacent_button(&Pos::new(4, 4)), None);
+        assert_eq!(index.adjacent_button(&Pos::new(5, 4)), Some(0));
+        assert_eq!(index.adjacent_password_gates(&Pos::new(2, 2)), vec![0, 1]);
+        assert_eq!(
+            index.adjacent_password_gates(&Pos::new(0, 0)),
+            Vec::<usize>::new()
+        );
+    }
+}
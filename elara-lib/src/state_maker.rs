@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::simulation::{
     BigEnemy, Button, DataPoint, Enemy, EnergyCell, Gate, Goal, Obstacle, PasswordGate, Player,
     State, Telepad,
@@ -27,7 +29,7 @@ impl StateMaker {
     }
 
     pub fn with_obstacles(&mut self, obstacles: Vec<Obstacle>) -> &mut Self {
-        self.state.obstacles = obstacles;
+        self.state.obstacles = Rc::new(obstacles);
         self
     }
 
@@ -57,7 +59,7 @@ impl StateMaker {
     }
 
     pub fn with_data_points(&mut self, data_points: Vec<DataPoint>) -> &mut Self {
-        self.state.data_points = data_points;
+        self.state.data_points = Rc::new(data_points);
         self
     }
 
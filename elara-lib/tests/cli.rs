@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use elara_lib::{from_json, BetterError, ScriptResult};
+
+/// Runs elara-cli with the given arguments, passing the script via stdin.
+fn run_cli(args: &[&str], script: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_elara-cli"))
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn run_json_big_enemy() {
+    // The big enemy turns towards the rover while it moves, so this makes sure
+    // that nothing else is printed to stdout along with the JSON.
+    let output = run_cli(
+        &["run", "--level", "big_enemy", "--state", "0", "--json"],
+        "move_left(4); wait(10);",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let result: ScriptResult = from_json(&stdout).unwrap();
+    assert!(result.states.len() > 1);
+}
+
+#[test]
+fn errors_include_output() {
+    let script = "print(\"before\");\nlet pos = get_position() + 1;";
+
+    let output = run_cli(&["run", "--level", "movement", "--json"], script);
+    assert_eq!(output.status.code(), Some(2));
+    let err: BetterError = from_json(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    assert_eq!(err.line, Some(2));
+    assert_eq!(err.output.len(), 1);
+    assert_eq!(err.output[0].text, "before");
+
+    let output = run_cli(&["check", "--level", "movement"], script);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Print before"),
+        "unexpected stderr: {}",
+        stderr
+    );
+    assert!(stderr.contains("(line 2"), "unexpected stderr: {}", stderr);
+}
+
+#[test]
+fn run_prints_stats() {
+    let output = run_cli(
+        &["run", "--level", "movement"],
+        "move_forward(1);\nmove_forward(2);\nturn_right();",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(" operations="),
+        "unexpected stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Code metrics: statements=3 ast_nodes=8 user_functions=0 max_loop_depth=0 cyclomatic_complexity=1"),
+        "unexpected stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Built-in calls: move_forward=2 turn_right=1"),
+        "unexpected stdout: {}",
+        stdout
+    );
+}
@@ -1,4 +1,5 @@
 import { Box } from "@chakra-ui/react";
+import { useMemo } from "react";
 
 import {
   DataPoint as RDataPoint,
@@ -11,16 +12,12 @@ import {
   Telepad as RTelepad,
   Button as RButton,
   Gate as RGate,
+  InputTerminal as RInputTerminal,
   BigEnemy as RBigEnemy,
   AsteroidWarning as RAsteroidWarning,
 } from "../../../elara-lib/pkg";
-import {
-  AXIS_HEIGHT,
-  AXIS_WIDTH,
-  HEIGHT,
-  TILE_SIZE,
-  WIDTH,
-} from "../../lib/constants";
+import { AXIS_HEIGHT, AXIS_WIDTH, TILE_SIZE } from "../../lib/constants";
+import { boardSize, BoardSizeContext } from "../../contexts/board_size";
 import { Offset, posToOffset, range } from "../../lib/utils";
 import "./board.css";
 import lunarSurfaceBgUrl from "../../images/board/lunar_surface_bg.png";
@@ -41,6 +38,9 @@ import AsteroidWarning from "./asteroid_warning";
 
 interface BoardProps {
   gameState: RState;
+  // Width and height of the board in spaces (from the level data).
+  width: number;
+  height: number;
   asteroidWarnings: RAsteroidWarning[];
   enableAnimations: boolean;
   enableHoverInfo: boolean;
@@ -72,8 +72,13 @@ function getConnectionOffset(
 }
 
 export default function Board(props: BoardProps) {
+  const size = useMemo(
+    () => boardSize(props.width, props.height),
+    [props.width, props.height]
+  );
+
   return (
-    <>
+    <BoardSizeContext.Provider value={size}>
       <div
         id="board"
         style={{
@@ -82,8 +87,8 @@ export default function Board(props: BoardProps) {
       >
         <table
           style={{
-            width: `${AXIS_WIDTH + TILE_SIZE * WIDTH + 1}px`,
-            height: `${AXIS_HEIGHT + TILE_SIZE * HEIGHT + 2}px`,
+            width: `${AXIS_WIDTH + TILE_SIZE * props.width + 1}px`,
+            height: `${AXIS_HEIGHT + TILE_SIZE * props.height + 2}px`,
           }}
         >
           <tbody>
@@ -100,13 +105,13 @@ export default function Board(props: BoardProps) {
                 id="axis-spacer"
                 style={{ width: `${AXIS_WIDTH}px`, height: `${AXIS_WIDTH}px` }}
               />
-              {range(WIDTH).map((x) => (
+              {range(props.width).map((x) => (
                 <th key={x} style={{ height: `${AXIS_HEIGHT}px` }}>
                   {x}
                 </th>
               ))}
             </tr>
-            {range(HEIGHT).map((y) => (
+            {range(props.height).map((y) => (
               <tr key={y} className="row">
                 <td
                   id="y-axis-label"
@@ -120,10 +125,10 @@ export default function Board(props: BoardProps) {
                 >
                   {y}
                 </td>
-                {/* {range(WIDTH).map((x) => (
+                {/* {range(props.width).map((x) => (
                   <Square key={`${x},${y}`} x={x} y={y} />
                 ))} */}
-                {range(WIDTH).map((x) => (
+                {range(props.width).map((x) => (
                   <td key={`${x},${y}`} />
                 ))}
               </tr>
@@ -256,11 +261,27 @@ export default function Board(props: BoardProps) {
           key={i}
           offset={posToOffset(dataPoint.pos)}
           reading={dataPoint.reading}
+          dataType={dataPoint.data_type}
           additionalInfo={dataPoint.additional_info}
           enableHoverInfo={props.enableHoverInfo}
           enableSfx={props.enableAnimations}
         />
       ))}
+      {/* Input terminals look like data points but are written to instead. */}
+      {(props.gameState.input_terminals as RInputTerminal[]).map(
+        (terminal, i) => (
+          <DataPoint
+            // eslint-disable-next-line react/no-array-index-key
+            key={i}
+            offset={posToOffset(terminal.pos)}
+            kind="input_terminal"
+            reading={terminal.writing}
+            additionalInfo={terminal.additional_info}
+            enableHoverInfo={props.enableHoverInfo}
+            enableSfx={props.enableAnimations}
+          />
+        )
+      )}
       {(props.gameState.telepads as RTelepad[]).map((telepad, i) => (
         // eslint-disable-next-line react/no-array-index-key
         <Box key={i}>
@@ -294,6 +315,6 @@ export default function Board(props: BoardProps) {
           enableHoverInfo={props.enableHoverInfo}
         />
       ))}
-    </>
+    </BoardSizeContext.Provider>
   );
 }
@@ -2,12 +2,9 @@ import { Box } from "@chakra-ui/react";
 import { useMemo, useState } from "react";
 
 import { MDXProps } from "mdx/types";
-import {
-  BOARD_HOVER_INFO_Z_INDEX,
-  BOARD_INNER_WIDTH,
-  TILE_SIZE,
-} from "../../lib/constants";
+import { BOARD_HOVER_INFO_Z_INDEX, TILE_SIZE } from "../../lib/constants";
 import { Offset } from "../../lib/utils";
+import { useBoardSize } from "../../hooks/board_size_hooks";
 
 export interface BoardHoverInfoProps {
   offset: Offset;
@@ -25,6 +22,7 @@ export interface BoardHoverInfoProps {
 
 export default function BoardHoverInfo(props: BoardHoverInfoProps) {
   const [isHovered, setIsHovered] = useState(false);
+  const { innerWidth: boardInnerWidth, height: boardHeight } = useBoardSize();
 
   // Width of the hover info in pixels.
   const pixelWidth = 400;
@@ -32,7 +30,7 @@ export default function BoardHoverInfo(props: BoardHoverInfoProps) {
   // If the hover info is too close to the right or left edge of the screen, we
   // offset it so that it doesn't hang off the edge.
   const rightOffset = useMemo(() => {
-    if (props.offset.leftNum + pixelWidth > BOARD_INNER_WIDTH) {
+    if (props.offset.leftNum + pixelWidth > boardInnerWidth) {
       // Would hang off the right.
       if (props.offset.leftNum - pixelWidth < 0) {
         // Would hang off the left too. Position in the middle.
@@ -42,17 +40,20 @@ export default function BoardHoverInfo(props: BoardHoverInfoProps) {
       return "0px";
     }
     return "auto";
-  }, [props.offset.leftNum]);
+  }, [boardInnerWidth, props.offset.leftNum]);
 
   // Bottom offset is used to make sure the hover info doesn't hang off the top or
   // bottom of the screen.
   const bottomOffset = useMemo(() => {
-    if (props.offset.pos && props.offset.pos.y + props.height! <= 5) {
+    if (
+      props.offset.pos &&
+      props.offset.pos.y + props.height! <= boardHeight - 3
+    ) {
       return `auto`;
     }
 
     return `100%`;
-  }, [props.height, props.offset.pos]);
+  }, [boardHeight, props.height, props.offset.pos]);
 
   return (
     <Box
@@ -3,8 +3,6 @@ import { useCallback, useEffect, useMemo, useRef } from "react";
 
 import { Offset } from "../../lib/utils";
 import {
-  BOARD_TOTAL_HEIGHT,
-  BOARD_TOTAL_WIDTH,
   BUTTON_WIRE_Z_INDEX,
   BUTTON_Z_INDEX,
   SPRITE_DROP_SHADOW,
@@ -13,6 +11,7 @@ import {
 import buttonImgUrl from "../../images/board/button.png";
 import buttonPressedImgUrl from "../../images/board/button_pressed.png";
 import { useSoundManager } from "../../hooks/sound_manager_hooks";
+import { useBoardSize } from "../../hooks/board_size_hooks";
 import BoardHoverInfo from "./board_hover_info";
 import ButtonPage from "./hover_info_pages/button.mdx";
 
@@ -37,6 +36,7 @@ export default function Button(props: ButtonProps) {
   const imgRef = useRef<HTMLImageElement | null>(null);
 
   const { getSound } = useSoundManager();
+  const boardSize = useBoardSize();
   const buttonPressOnSound = useMemo(
     () => getSound("button_press_on"),
     [getSound]
@@ -108,8 +108,8 @@ export default function Button(props: ButtonProps) {
           position="absolute"
           left={0}
           top={0}
-          w={BOARD_TOTAL_WIDTH}
-          h={BOARD_TOTAL_HEIGHT}
+          w={boardSize.totalWidth}
+          h={boardSize.totalHeight}
           overflow="visible"
           zIndex={BUTTON_WIRE_Z_INDEX}
         >
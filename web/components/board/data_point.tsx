@@ -12,10 +12,16 @@ import { Offset } from "../../lib/utils";
 import { useSoundManager } from "../../hooks/sound_manager_hooks";
 import BoardHoverInfo from "./board_hover_info";
 import DataPointPage from "./hover_info_pages/data_point.mdx";
+import InputTerminalPage from "./hover_info_pages/input_terminal.mdx";
 
 interface DataPointProps {
   offset: Offset;
+  // Input terminals look the same as data points, but are written to instead
+  // of read from, so they have their own hover info page (default "data_point").
+  kind?: "data_point" | "input_terminal";
   reading: boolean;
+  // The type of data held by the data point (e.g. "string" or "number").
+  dataType?: string;
   additionalInfo?: string;
   enableHoverInfo: boolean;
   // Whether to enable sound effects.
@@ -34,6 +40,19 @@ export default function DataPoint(props: DataPointProps) {
     return `left ${CSS_ANIM_DURATION}s, top ${CSS_ANIM_DURATION}s`;
   }, [props.animatePos]);
 
+  // Let the user know what kind of data to expect, followed by any
+  // level-specific info.
+  const hoverInfo = useMemo(() => {
+    if (!props.dataType) {
+      return props.additionalInfo;
+    }
+    const article = props.dataType === "array" ? "an" : "a";
+    const typeInfo = `Holds ${article} ${props.dataType}.`;
+    return props.additionalInfo
+      ? `${typeInfo}\n\n${props.additionalInfo}`
+      : typeInfo;
+  }, [props.additionalInfo, props.dataType]);
+
   const stopMySoundEffects = useCallback(() => {
     readingDataSound.stop();
   }, [readingDataSound]);
@@ -57,9 +76,11 @@ export default function DataPoint(props: DataPointProps) {
     <>
       {props.enableHoverInfo && (
         <BoardHoverInfo
-          page={DataPointPage}
+          page={
+            props.kind === "input_terminal" ? InputTerminalPage : DataPointPage
+          }
           offset={props.offset}
-          additionalInfo={props.additionalInfo}
+          additionalInfo={hoverInfo}
         />
       )}
       <Box
@@ -101,5 +122,6 @@ export default function DataPoint(props: DataPointProps) {
 }
 
 DataPoint.defaultProps = {
+  kind: "data_point",
   animatePos: false,
 };
@@ -29,6 +29,12 @@ import GetOrientation from "./pages/get_orientation.mdx";
 import GetPosition from "./pages/get_position.mdx";
 import Push from "./pages/push.mdx";
 import PressButton from "./pages/press_button.mdx";
+import LookAhead from "./pages/look_ahead.mdx";
+import GetEnergy from "./pages/get_energy.mdx";
+import IsGateOpen from "./pages/is_gate_open.mdx";
+import DistanceToGoal from "./pages/distance_to_goal.mdx";
+import GetGoalPosition from "./pages/get_goal_position.mdx";
+import WriteData from "./pages/write_data.mdx";
 
 const hoverWords = [
   "turn_right",
@@ -46,6 +52,12 @@ const hoverWords = [
   "get_position",
   "push",
   "press_button",
+  "look_ahead",
+  "get_energy",
+  "is_gate_open",
+  "distance_to_goal",
+  "get_goal_position",
+  "write_data",
 ] as const;
 
 export type HoverWord = typeof hoverWords[number];
@@ -68,6 +80,12 @@ export const docPages: {
   get_position: GetPosition,
   push: Push,
   press_button: PressButton,
+  look_ahead: LookAhead,
+  get_energy: GetEnergy,
+  is_gate_open: IsGateOpen,
+  distance_to_goal: DistanceToGoal,
+  get_goal_position: GetGoalPosition,
+  write_data: WriteData,
 };
 
 // A plugin that shows a tooltip with documentation for built-in functions.
@@ -0,0 +1,41 @@
+import { createContext } from "react";
+
+import {
+  AXIS_HEIGHT,
+  AXIS_WIDTH,
+  DEFAULT_HEIGHT,
+  DEFAULT_WIDTH,
+  TILE_SIZE,
+} from "../lib/constants";
+
+// The size of the game board for the current level.
+export interface BoardSize {
+  // Width and height in board spaces.
+  width: number;
+  height: number;
+  // Size of the board in pixels, not including the axis labels.
+  innerWidth: number;
+  innerHeight: number;
+  // Size of the board in pixels, including the axis labels and borders.
+  totalWidth: number;
+  totalHeight: number;
+}
+
+export function boardSize(width: number, height: number): BoardSize {
+  const innerWidth = TILE_SIZE * width;
+  const innerHeight = TILE_SIZE * height;
+  return {
+    width,
+    height,
+    innerWidth,
+    innerHeight,
+    totalWidth: innerWidth + AXIS_WIDTH + 2,
+    totalHeight: innerHeight + AXIS_HEIGHT + 2,
+  };
+}
+
+// Provided by the Board component so that the sprites on the board can
+// access its size.
+export const BoardSizeContext = createContext<BoardSize>(
+  boardSize(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+);
@@ -0,0 +1,6 @@
+import { useContext } from "react";
+
+import { BoardSizeContext } from "../contexts/board_size";
+
+// A custom hook for getting the size of the current game board.
+export const useBoardSize = () => useContext(BoardSizeContext);
@@ -12,6 +12,8 @@ import { CompletionContext, Completion } from "@codemirror/autocomplete";
 import { Compartment } from "@codemirror/state";
 import { EditorView } from "@codemirror/view";
 
+import { BuiltinDoc, get_builtin_docs } from "../../elara-lib/pkg";
+
 interface PartialFuncOption {
   label: string;
   info: string;
@@ -49,78 +51,28 @@ function applyFuncWithArgs(
   });
 }
 
-const builtInFuncs: PartialFuncOption[] = [
-  {
-    label: "turn_right",
-    info: "Turn right (i.e. clockwise) by 90 degrees.",
-    apply: applyFuncWithoutArgs,
-  },
-  {
-    label: "turn_left",
-    info: "Turn left (i.e. counter-clockwise) by 90 degrees.",
-    apply: applyFuncWithoutArgs,
-  },
-  {
-    label: "move_forward",
-    info: "Move forward by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "move_backward",
-    info: "Move backward by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "move_up",
-    info: "Move up by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "move_down",
-    info: "Move down by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "move_left",
-    info: "Move left by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "move_right",
-    info: "Move right by a number of spaces.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "get_orientation",
-    info: "Outputs whatever direction G.R.O.V.E.R. is facing.",
-    apply: applyFuncWithoutArgs,
-  },
-  {
-    label: "read_data",
-    info: "Get the data from a nearby data point.",
-    apply: applyFuncWithoutArgs,
-  },
-  {
-    label: "say",
-    info: "Cause G.R.O.V.E.R. to say something.",
-    apply: applyFuncWithArgs,
-  },
-  {
-    label: "press_button",
-    info: "Press a nearby button.",
-    apply: applyFuncWithoutArgs,
-  },
-  {
-    label: "push",
-    info: "Add an element to the end of an array.",
-    apply: applyFuncWithArgs,
-  },
-];
+// Autocomplete options for all built-in functions. These are generated from
+// the docs in elara-lib (see Builtin::doc) so that they stay in sync with the
+// actual functions.
+// Note that this can't be computed when the module is loaded, since the Wasm
+// module might not be initialized yet.
+let builtinFuncOptions: (PartialFuncOption & { type: string })[] | null = null;
 
-const builtinFuncOptions = builtInFuncs.map((func) => ({
-  ...func,
-  type: "function",
-}));
+function getBuiltinFuncOptions() {
+  if (builtinFuncOptions == null) {
+    builtinFuncOptions = (get_builtin_docs() as BuiltinDoc[]).map((doc) => ({
+      label: doc.name,
+      info:
+        doc.energy_per_space > 0
+          ? `${doc.doc} Uses ${doc.energy_per_space} energy per space.`
+          : doc.doc,
+      apply:
+        doc.arg_types.length === 0 ? applyFuncWithoutArgs : applyFuncWithArgs,
+      type: "function",
+    }));
+  }
+  return builtinFuncOptions;
+}
 
 function completeBuiltinFunction(autocompleteFuncs: string[]) {
   return (context: CompletionContext) => {
@@ -141,7 +93,7 @@ function completeBuiltinFunction(autocompleteFuncs: string[]) {
 
     return {
       from: word.from,
-      options: builtinFuncOptions.filter((f) =>
+      options: getBuiltinFuncOptions().filter((f) =>
         autocompleteFuncs.includes(f.label)
       ),
     };
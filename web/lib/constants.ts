@@ -11,15 +11,13 @@ export const SOUND_DELAY_TIME_MS = 100;
 export const MUSIC_FADE_OUT_TIME_MS = 1000;
 
 // Constants related to the game board and game logic.
-export const WIDTH = 12;
-export const HEIGHT = 8;
+// The actual board size comes from the level (see contexts/board_size.tsx).
+// These are only used as a fallback outside of a board.
+export const DEFAULT_WIDTH = 12;
+export const DEFAULT_HEIGHT = 8;
 export const TILE_SIZE = 50;
 export const AXIS_HEIGHT = 18; // Width of the axis labels at left of the game board.
 export const AXIS_WIDTH = 18; // Width of the axis labels at top of the game board.
-export const BOARD_INNER_WIDTH = TILE_SIZE * WIDTH;
-export const BOARD_INNER_HEIGHT = TILE_SIZE * HEIGHT;
-export const BOARD_TOTAL_WIDTH = BOARD_INNER_WIDTH + AXIS_WIDTH + 2;
-export const BOARD_TOTAL_HEIGHT = BOARD_INNER_HEIGHT + AXIS_HEIGHT + 2;
 export const DEFAULT_ENERGY_CELL_GAIN = 10;
 export const SPRITE_DROP_SHADOW = "drop-shadow(0px 3px 1px rgba(0, 0, 0, 0.3))";
 
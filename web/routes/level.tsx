@@ -461,6 +461,8 @@ export default function Level() {
                     enableHoverInfo={editorState !== "running"}
                     showInitialState={editorState === "editing"}
                     asteroidWarnings={currLevel().asteroid_warnings}
+                    width={currLevel().width}
+                    height={currLevel().height}
                   />
                 </Box>
               </Box>